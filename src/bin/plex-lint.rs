@@ -0,0 +1,342 @@
+//! `plex-lint`, a host-side checker for `plex.toml`.
+//!
+//! Loads the configuration exactly as plex does at boot, with its includes
+//! and drop-ins relative to the directory of the file, checks it with
+//! [`plex_boot::config::validate`] and prints the diagnostics for humans or
+//! as JSON. Paths are
+//! checked against partitions mounted on the host if `--esp` or `--mount`
+//! are given, paths on any other partition are not checked.
+//!
+//! Exits with 0 if no errors were found, 1 if a file has errors (or
+//! warnings, with `--deny-warnings`) and 2 on usage errors or unreadable
+//! files.
+
+#[cfg(feature = "global_allocator")]
+compile_error!("plex-lint must be built with --no-default-features --features lint");
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use plex_boot::config::loader::Files;
+use plex_boot::config::validate::{Diagnostic, Environment, Severity};
+use plex_boot::config::{Config, ConfigError};
+use plex_boot::path::{PartitionReference, PathReference};
+use serde::Serialize;
+
+const USAGE: &str = "\
+usage: plex-lint [OPTIONS] FILE...
+
+Checks plex.toml files for the problems plex reports at boot.
+
+options:
+  --format human|json  output format, defaults to human
+  --esp DIR            check boot() paths against the ESP mounted at DIR
+  --mount GUID=DIR     check guid(GUID) paths against the partition mounted at DIR
+  --deny-warnings      fail on warnings too
+  -h, --help           show this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Human,
+    Json,
+}
+
+struct Args {
+    format: Format,
+    mounts: Mounts,
+    deny_warnings: bool,
+    files: Vec<PathBuf>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut parsed = Self {
+            format: Format::Human,
+            mounts: Mounts::default(),
+            deny_warnings: false,
+            files: Vec::new(),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} expects a value"));
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--deny-warnings" => parsed.deny_warnings = true,
+                "--format" => {
+                    parsed.format = match value()?.as_str() {
+                        "human" => Format::Human,
+                        "json" => Format::Json,
+                        other => return Err(format!("unknown format \"{other}\"")),
+                    }
+                }
+                "--esp" => parsed.mounts.add(PartitionReference::Boot, value()?.into()),
+                "--mount" => {
+                    let value = value()?;
+                    let (guid, dir) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("expected GUID=DIR, got \"{value}\""))?;
+                    let location = PartitionReference::parse(&format!("guid({guid})"))
+                        .map_err(|e| format!("{guid}: {e}"))?;
+                    parsed.mounts.add(location, dir.into());
+                }
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option {arg}"));
+                }
+                _ => parsed.files.push(arg.into()),
+            }
+        }
+
+        if parsed.files.is_empty() {
+            return Err(String::from("no files given"));
+        }
+        Ok(Some(parsed))
+    }
+}
+
+/// Partitions mounted on the host.
+#[derive(Default)]
+struct Mounts(Vec<(PartitionReference, PathBuf)>);
+
+impl Mounts {
+    fn add(&mut self, location: PartitionReference, dir: PathBuf) {
+        self.0.retain(|(l, _)| *l != location);
+        self.0.push((location, dir));
+    }
+
+    fn root(&self, location: &PartitionReference) -> Option<&Path> {
+        self.0
+            .iter()
+            .find(|(l, _)| l == location)
+            .map(|(_, dir)| dir.as_path())
+    }
+}
+
+impl Environment for Mounts {
+    fn has_partition(&self, _location: &PartitionReference) -> bool {
+        // The host cannot tell which partitions the target machine has.
+        true
+    }
+
+    fn has_file(&self, path: &PathReference) -> bool {
+        self.root(&path.location)
+            .is_none_or(|root| lookup(root, &path.path).is_some_and(|found| found.is_file()))
+    }
+}
+
+/// Configuration files in a directory on the host, which stands in for the
+/// root of the ESP.
+struct HostFiles {
+    root: PathBuf,
+}
+
+impl HostFiles {
+    /// The directory holding `file`.
+    fn beside(file: &Path) -> Self {
+        let root = file
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+
+    /// Where a path on the ESP is on the host, for messages.
+    fn host_path(&self, path: &str) -> PathBuf {
+        lookup(&self.root, path).unwrap_or_else(|| self.root.join(path.replace('\\', "/")))
+    }
+}
+
+impl Files for HostFiles {
+    fn read(&self, path: &str) -> Result<String, ConfigError> {
+        let found = lookup(&self.root, path).ok_or(ConfigError::FileNotFound)?;
+        let bytes = std::fs::read(found).map_err(|_| ConfigError::FsError)?;
+        String::from_utf8(bytes).map_err(|_| ConfigError::EncodingError)
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>, ConfigError> {
+        let found = lookup(&self.root, dir).ok_or(ConfigError::FileNotFound)?;
+        Ok(std::fs::read_dir(found)
+            .map_err(|_| ConfigError::FsError)?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect())
+    }
+}
+
+/// Finds `path` below `root`, ignoring case like FAT does.
+fn lookup(root: &Path, path: &str) -> Option<PathBuf> {
+    path.split(['\\', '/'])
+        .filter(|component| !component.is_empty())
+        .try_fold(root.to_path_buf(), |dir, component| {
+            let exact = dir.join(component);
+            if exact.exists() {
+                return Some(exact);
+            }
+            std::fs::read_dir(&dir)
+                .ok()?
+                .filter_map(Result::ok)
+                .find(|entry| {
+                    entry
+                        .file_name()
+                        .to_str()
+                        .is_some_and(|name| name.eq_ignore_ascii_case(component))
+                })
+                .map(|entry| entry.path())
+        })
+}
+
+/// The diagnostics of one configuration, including the files it pulls in.
+#[derive(Serialize)]
+struct Report {
+    file: String,
+    errors: usize,
+    warnings: usize,
+    diagnostics: Vec<Diagnostic>,
+    #[serde(skip)]
+    files: HostFiles,
+    #[serde(skip)]
+    source: String,
+}
+
+impl Report {
+    fn new(file: &Path, files: HostFiles, source: String, diagnostics: Vec<Diagnostic>) -> Self {
+        let count = |severity| {
+            diagnostics
+                .iter()
+                .filter(|d| d.severity == severity)
+                .count()
+        };
+        Self {
+            file: file.display().to_string(),
+            errors: count(Severity::Error),
+            warnings: count(Severity::Warning),
+            diagnostics,
+            files,
+            source,
+        }
+    }
+}
+
+fn lint(file: &Path, mounts: &Mounts) -> io::Result<Report> {
+    let source = std::fs::read_to_string(file)?;
+    let files = HostFiles::beside(file);
+    let path = format!("\\{}", file.file_name().unwrap_or_default().display());
+    let env = (!mounts.0.is_empty()).then_some(mounts as &dyn Environment);
+    let diagnostics = match Config::load(&files, &path, env) {
+        Ok((_, diagnostics)) => diagnostics,
+        Err(e) => vec![Diagnostic::from(e)],
+    };
+    Ok(Report::new(file, files, source, diagnostics))
+}
+
+/// Prints a diagnostic with the offending line, like rustc does.
+fn print_human(report: &Report, diagnostic: &Diagnostic) {
+    println!("{}: {}", diagnostic.severity, diagnostic.message);
+
+    let (mut location, source) = match &diagnostic.file {
+        Some(file) => (
+            report.files.host_path(file).display().to_string(),
+            report.files.read(file).unwrap_or_default(),
+        ),
+        None => (report.file.clone(), report.source.clone()),
+    };
+    if let Some((line, column)) = diagnostic.position {
+        location = format!("{location}:{line}:{column}");
+    }
+    match diagnostic.subject() {
+        Some(subject) => println!("  --> {location} ({subject})"),
+        None => println!("  --> {location}"),
+    }
+
+    let (Some((line, column)), Some(span)) = (diagnostic.position, &diagnostic.span) else {
+        return;
+    };
+    let Some(text) = source.lines().nth(line - 1) else {
+        return;
+    };
+    let width = source
+        .get(span.clone())
+        .and_then(|s| s.lines().next())
+        .map_or(1, |s| s.chars().count().max(1));
+    let gutter = " ".repeat(line.to_string().len());
+    println!("{gutter} |");
+    println!("{line} | {text}");
+    println!("{gutter} | {}{}", " ".repeat(column - 1), "^".repeat(width));
+}
+
+fn summary(report: &Report) -> String {
+    let plural = |n: usize, word: &str| match n {
+        1 => format!("1 {word}"),
+        n => format!("{n} {word}s"),
+    };
+    match (report.errors, report.warnings) {
+        (0, 0) => format!("{}: ok", report.file),
+        (errors, warnings) => format!(
+            "{}: {}, {}",
+            report.file,
+            plural(errors, "error"),
+            plural(warnings, "warning")
+        ),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprint!("plex-lint: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut unreadable = false;
+    let reports: Vec<Report> = args
+        .files
+        .iter()
+        .map(|file| {
+            lint(file, &args.mounts).unwrap_or_else(|e| {
+                unreadable = true;
+                let error = Diagnostic::error(format!("cannot read file: {e}"));
+                Report::new(file, HostFiles::beside(file), String::new(), vec![error])
+            })
+        })
+        .collect();
+
+    match args.format {
+        Format::Human => {
+            for report in &reports {
+                for diagnostic in &report.diagnostics {
+                    print_human(report, diagnostic);
+                    println!();
+                }
+                println!("{}", summary(report));
+            }
+        }
+        Format::Json => match serde_json::to_string_pretty(&reports) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("plex-lint: {e}");
+                return ExitCode::from(2);
+            }
+        },
+    }
+
+    let failed = reports
+        .iter()
+        .any(|report| report.errors > 0 || (args.deny_warnings && report.warnings > 0));
+    if unreadable {
+        ExitCode::from(2)
+    } else if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
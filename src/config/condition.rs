@@ -0,0 +1,181 @@
+//! Conditions limiting boot targets to some machines.
+//!
+//! One ESP image can serve different machines by giving entries a `when`
+//! table. An entry is only shown if every condition in it holds:
+//!
+//! ```toml
+//! [[boot_targets]]
+//! type = "generic"
+//! label = "Arch Linux"
+//! executable = "boot():\\EFI\\arch\\vmlinuz-linux.efi"
+//! when = { arch = ["x86_64", "aarch64"], secure_boot = false }
+//! ```
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::str::FromStr;
+use serde::{Deserialize, Deserializer};
+
+use crate::config::validate::Environment;
+use crate::core::system::SystemInfo;
+use crate::path::PartitionReference;
+
+/// Architecture names accepted in `when.arch`.
+pub const ARCHES: &[&str] = &["x86_64", "x86", "aarch64", "arm", "riscv64", "loongarch64"];
+
+/// The `when` table of a boot target. Conditions that are not set always
+/// hold.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct When {
+    /// CPU architectures, one of [`ARCHES`]. Matches any of them.
+    #[serde(deserialize_with = "one_or_many")]
+    pub arch: Vec<String>,
+    /// Whether Secure Boot must be enabled or disabled.
+    pub secure_boot: Option<bool>,
+    /// Part of the firmware vendor, e.g. `EDK II`, ignoring case.
+    pub firmware_vendor: Option<String>,
+    /// The firmware revision, either exactly or as a range.
+    pub firmware_revision: Option<Revision>,
+    /// GUID of a partition that must be present.
+    pub partition: Option<String>,
+}
+
+/// A firmware revision condition, `0x10000` or `{ min = 0x10000, max = 0x1ffff }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Revision {
+    /// Exactly this revision.
+    Exact(u32),
+    /// Any revision in the inclusive range.
+    Range {
+        /// Lowest matching revision.
+        min: Option<u32>,
+        /// Highest matching revision.
+        max: Option<u32>,
+    },
+}
+
+impl Revision {
+    /// Whether `revision` satisfies the condition.
+    #[must_use]
+    pub fn contains(self, revision: u32) -> bool {
+        match self {
+            Self::Exact(exact) => revision == exact,
+            Self::Range { min, max } => {
+                min.is_none_or(|min| revision >= min) && max.is_none_or(|max| revision <= max)
+            }
+        }
+    }
+}
+
+impl When {
+    /// Whether the entry applies to the machine described by `system`, with
+    /// partitions looked up in `env`.
+    #[must_use]
+    pub fn matches(&self, system: &SystemInfo, env: &dyn Environment) -> bool {
+        (self.arch.is_empty() || self.arch.iter().any(|arch| arch == system.arch))
+            && self
+                .secure_boot
+                .is_none_or(|secure_boot| secure_boot == system.secure_boot)
+            && self
+                .firmware_vendor
+                .as_ref()
+                .is_none_or(|vendor| contains_ignore_case(&system.firmware_vendor, vendor))
+            && self
+                .firmware_revision
+                .is_none_or(|revision| revision.contains(system.firmware_revision))
+            && self.partition.as_deref().is_none_or(|guid| {
+                uefi::Guid::from_str(guid)
+                    .is_ok_and(|guid| env.has_partition(&PartitionReference::Guid(guid)))
+            })
+    }
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack
+        .to_ascii_lowercase()
+        .contains(&needle.to_ascii_lowercase())
+}
+
+/// Accepts a single string as well as an array of strings.
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::PathReference;
+
+    const ESP: &str = "550e8400-e29b-41d4-a716-446655440000";
+
+    /// A machine with a single partition.
+    struct Disk;
+
+    impl Environment for Disk {
+        fn has_partition(&self, location: &PartitionReference) -> bool {
+            *location == PartitionReference::Guid(uefi::Guid::from_str(ESP).unwrap())
+        }
+
+        fn has_file(&self, _path: &PathReference) -> bool {
+            false
+        }
+    }
+
+    fn when(source: &str) -> When {
+        toml::from_str(source).unwrap()
+    }
+
+    fn system() -> SystemInfo {
+        SystemInfo {
+            arch: "x86_64",
+            secure_boot: true,
+            firmware_vendor: String::from("American Megatrends"),
+            firmware_revision: 0x0005_0011,
+            smbios: None,
+            firmware_setup: false,
+        }
+    }
+
+    #[test]
+    fn matches_conditions() {
+        let matches = |source: &str| when(source).matches(&system(), &Disk);
+
+        assert!(matches(""));
+        assert!(matches("arch = \"x86_64\""));
+        assert!(matches("arch = [\"aarch64\", \"x86_64\"]"));
+        assert!(!matches("arch = \"aarch64\""));
+        assert!(matches("secure_boot = true"));
+        assert!(!matches("secure_boot = false"));
+        assert!(matches("firmware_vendor = \"american\""));
+        assert!(!matches("firmware_vendor = \"EDK II\""));
+        assert!(matches("firmware_revision = 0x50011"));
+        assert!(matches("firmware_revision = { min = 0x50000 }"));
+        assert!(!matches(
+            "firmware_revision = { min = 0x10000, max = 0x4ffff }"
+        ));
+        assert!(matches(&alloc::format!("partition = \"{ESP}\"")));
+        assert!(!matches(
+            "partition = \"8c8f8eff-ac95-4770-814a-21994f2dbc8f\""
+        ));
+        assert!(!matches("partition = \"not a guid\""));
+        assert!(!matches("arch = \"x86_64\"\nsecure_boot = false"));
+    }
+
+    #[test]
+    fn rejects_unknown_conditions() {
+        assert!(toml::from_str::<When>("cpu = \"x86_64\"").is_err());
+    }
+}
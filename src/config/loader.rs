@@ -0,0 +1,526 @@
+//! Loading `plex.toml` together with the files it pulls in.
+//!
+//! A configuration file may list other files in `include = [...]`, and every
+//! `*.toml` file in the drop-in directory next to the main file (`\plex.d`
+//! for `\plex.toml`) is loaded as well, e.g. entries written by kernel
+//! install hooks. Files are merged in this order:
+//!
+//! 1. the files a file includes, in the order listed, each merged the same
+//!    way,
+//! 2. the file itself,
+//! 3. for the main file only, the drop-ins, sorted by file name.
+//!
+//! `boot_targets` and `machine` blocks are concatenated in that order.
+//! Every other setting is taken from the last file that sets it, so a file
+//! overrides the files it includes and drop-ins override `plex.toml`.
+//! `resolvers` is replaced as a whole, not merged.
+//!
+//! Include paths starting with `\` are relative to the root of the
+//! partition, others to the directory of the including file. Missing files,
+//! files that fail to parse and include cycles are reported as diagnostics
+//! and skipped, only the main file is required.
+//!
+//! Templates and variables are collected from all files before any entry is
+//! resolved, see [`template`](super::template).
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::Deserialize;
+use toml::Table;
+use uefi::fs::{FileSystem, PathBuf};
+use uefi::CString16;
+
+use super::machine::MachineProfile;
+use super::template::Definitions;
+use super::validate::{self, Diagnostic, Environment, SourceMap};
+use super::{Config, ConfigError, DefaultEntry, TargetConfig};
+use crate::core::display::ConsoleMode;
+use crate::core::resolver::Resolver;
+use crate::helpers::text::strip_suffix_ignore_case;
+use crate::path::PartitionReference;
+use crate::ui::boot_menu::MenuMode;
+use crate::ui::theme::Theme;
+
+/// Access to the files the configuration is read from.
+pub trait Files {
+    /// Reads a file as UTF-8.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not valid UTF-8.
+    fn read(&self, path: &str) -> Result<String, ConfigError>;
+
+    /// Lists the names of the regular files in a directory.
+    ///
+    /// # Errors
+    /// Returns an error if the directory cannot be read.
+    fn list(&self, dir: &str) -> Result<Vec<String>, ConfigError>;
+}
+
+/// The file system of the partition plex was loaded from.
+pub struct ImageFiles;
+
+impl ImageFiles {
+    fn file_system() -> Result<FileSystem, ConfigError> {
+        uefi::boot::get_image_file_system(uefi::boot::image_handle())
+            .map(FileSystem::new)
+            .map_err(|_| ConfigError::FsError)
+    }
+}
+
+impl Files for ImageFiles {
+    fn read(&self, path: &str) -> Result<String, ConfigError> {
+        let path = CString16::try_from(path).map_err(|_| ConfigError::InvalidPath)?;
+        let buf = Self::file_system()?
+            .read(path.as_ref())
+            .map_err(|_| ConfigError::FileNotFound)?;
+        String::from_utf8(buf).map_err(|_| ConfigError::EncodingError)
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>, ConfigError> {
+        let dir = PathBuf::from(CString16::try_from(dir).map_err(|_| ConfigError::InvalidPath)?);
+        Ok(Self::file_system()?
+            .read_dir(&dir)
+            .map_err(|_| ConfigError::FileNotFound)?
+            .filter_map(Result::ok)
+            .filter(|info| info.is_regular_file())
+            .map(|info| info.file_name().to_string())
+            .collect())
+    }
+}
+
+/// The contents of a single configuration file. Settings it does not
+/// contain are left to the other files.
+#[derive(Debug, Deserialize)]
+pub(super) struct Fragment {
+    #[serde(default)]
+    include: Vec<String>,
+    theme: Option<Theme>,
+    console: Option<ConsoleMode>,
+    timeout: Option<u64>,
+    default: Option<DefaultEntry>,
+    menu: Option<MenuMode>,
+    reveal_window: Option<u64>,
+    search_order: Option<Vec<PartitionReference>>,
+    #[serde(default)]
+    templates: BTreeMap<String, Table>,
+    #[serde(default)]
+    vars: BTreeMap<String, String>,
+    /// Entries as written, resolved by [`Self::resolve_targets`].
+    #[serde(default)]
+    boot_targets: Vec<Table>,
+    resolvers: Option<Vec<Resolver>>,
+    #[serde(default)]
+    machine: Vec<MachineProfile>,
+}
+
+impl Fragment {
+    pub(super) fn parse(source: &str) -> Result<Self, ConfigError> {
+        toml::from_str(source)
+            .map_err(|e| ConfigError::Parse(Diagnostic::from_toml(&e, source).into()))
+    }
+
+    /// Adds the templates and variables of this file to `definitions`.
+    pub(super) fn define(&self, definitions: &mut Definitions) {
+        definitions.extend(&self.templates, &self.vars);
+    }
+
+    /// Resolves the entries of this file, located with `spans`, and returns
+    /// them with their index in the file. Entries that fail to resolve are
+    /// reported and skipped.
+    pub(super) fn resolve_targets(
+        &mut self,
+        definitions: &Definitions,
+        spans: &SourceMap,
+    ) -> (Vec<(usize, TargetConfig)>, Vec<Diagnostic>) {
+        let mut targets = Vec::new();
+        let mut diagnostics = Vec::new();
+
+        for (idx, entry) in core::mem::take(&mut self.boot_targets)
+            .into_iter()
+            .enumerate()
+        {
+            match definitions.resolve(entry) {
+                Ok(target) => targets.push((idx, target)),
+                Err((error, field)) => {
+                    let span = field
+                        .as_deref()
+                        .and_then(|field| {
+                            spans
+                                .entry_field(idx, field)
+                                .or_else(|| spans.entry_field(idx, &format!("{field}_append")))
+                        })
+                        .or_else(|| spans.element("boot_targets", idx));
+                    let diagnostic = Diagnostic::error(error.to_string()).at(spans.source, span);
+                    diagnostics.push(match field {
+                        Some(field) => diagnostic.in_entry(idx, field),
+                        None => Diagnostic {
+                            entry: Some(idx),
+                            ..diagnostic
+                        },
+                    });
+                }
+            }
+        }
+
+        (targets, diagnostics)
+    }
+
+    /// Merges the settings of the fragment other than its entries into
+    /// `config`, see the module documentation.
+    pub(super) fn apply(self, config: &mut Config) {
+        if let Some(theme) = self.theme {
+            config.theme = theme;
+        }
+        if let Some(console) = self.console {
+            config.console = console;
+        }
+        if self.timeout.is_some() {
+            config.timeout = self.timeout;
+        }
+        if let Some(default) = self.default {
+            config.default = default;
+        }
+        if let Some(menu) = self.menu {
+            config.menu = menu;
+        }
+        if let Some(reveal_window) = self.reveal_window {
+            config.reveal_window = reveal_window;
+        }
+        if let Some(search_order) = self.search_order {
+            config.search_order = search_order;
+        }
+        if let Some(resolvers) = self.resolvers {
+            config.resolvers = resolvers;
+        }
+        config.machines.extend(self.machine);
+    }
+}
+
+/// Where an include is listed, for diagnostics.
+struct IncludedFrom<'a> {
+    /// The including file, `None` for the main file.
+    file: Option<&'a str>,
+    spans: &'a SourceMap<'a>,
+    /// Index in its `include` list.
+    index: usize,
+}
+
+impl IncludedFrom<'_> {
+    fn report(&self, diagnostic: Diagnostic) -> Diagnostic {
+        let diagnostic =
+            diagnostic.at(self.spans.source, self.spans.element("include", self.index));
+        let diagnostic = Diagnostic {
+            field: Some(String::from("include")),
+            ..diagnostic
+        };
+        match self.file {
+            Some(file) => diagnostic.in_file(file),
+            None => diagnostic,
+        }
+    }
+}
+
+/// A configuration file read by the loader.
+struct File {
+    path: String,
+    is_main: bool,
+    source: String,
+    fragment: Fragment,
+}
+
+impl File {
+    /// Attributes a diagnostic to this file.
+    fn report(&self, diagnostic: Diagnostic) -> Diagnostic {
+        if self.is_main {
+            diagnostic
+        } else {
+            diagnostic.in_file(&self.path)
+        }
+    }
+}
+
+struct Loader<'a> {
+    files: &'a dyn Files,
+    /// The files whose includes are being read, innermost last, to detect
+    /// cycles.
+    stack: Vec<String>,
+    /// Every file read so far, in merge order.
+    loaded: Vec<File>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Loader<'_> {
+    /// Reads the includes of a parsed file, then queues the file itself.
+    fn add(&mut self, mut file: File) {
+        self.stack.push(file.path.clone());
+        let spans = SourceMap::new(&file.source);
+        for (index, include) in core::mem::take(&mut file.fragment.include)
+            .iter()
+            .enumerate()
+        {
+            let from = IncludedFrom {
+                file: (!file.is_main).then_some(file.path.as_str()),
+                spans: &spans,
+                index,
+            };
+            self.include(&resolve(&file.path, include), Some(&from));
+        }
+        self.stack.pop();
+        self.loaded.push(file);
+    }
+
+    /// Reads and parses an included file or drop-in.
+    fn include(&mut self, path: &str, from: Option<&IncludedFrom>) {
+        let report = |diagnostic| match from {
+            Some(from) => from.report(diagnostic),
+            None => diagnostic,
+        };
+
+        if let Some(start) = self.stack.iter().position(|p| p.eq_ignore_ascii_case(path)) {
+            let cycle: Vec<&str> = self.stack[start..].iter().map(String::as_str).collect();
+            let message = format!("include cycle: {} -> {path}", cycle.join(" -> "));
+            self.diagnostics.push(report(Diagnostic::error(message)));
+            return;
+        }
+        if self
+            .loaded
+            .iter()
+            .any(|f| f.path.eq_ignore_ascii_case(path))
+        {
+            let message = format!("{path} is already included");
+            self.diagnostics.push(report(Diagnostic::warning(message)));
+            return;
+        }
+
+        let source = match self.files.read(path) {
+            Ok(source) => source,
+            Err(e) => {
+                let message = format!("cannot include {path}: {e}");
+                self.diagnostics.push(report(Diagnostic::error(message)));
+                return;
+            }
+        };
+        match Fragment::parse(&source) {
+            Ok(fragment) => self.add(File {
+                path: String::from(path),
+                is_main: false,
+                source,
+                fragment,
+            }),
+            Err(e) => self.diagnostics.push(Diagnostic::from(e).in_file(path)),
+        }
+    }
+}
+
+/// Loads the configuration at `path` with its includes and drop-ins, and
+/// checks every file with [`validate`](super::validate).
+pub(super) fn load(
+    files: &dyn Files,
+    path: &str,
+    env: Option<&dyn Environment>,
+) -> Result<(Config, Vec<Diagnostic>), ConfigError> {
+    let source = files.read(path)?;
+    let fragment = Fragment::parse(&source)?;
+
+    let mut loader = Loader {
+        files,
+        stack: Vec::new(),
+        loaded: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    loader.add(File {
+        path: String::from(path),
+        is_main: true,
+        source,
+        fragment,
+    });
+    for drop_in in drop_ins(files, path) {
+        loader.include(&drop_in, None);
+    }
+
+    let mut definitions = Definitions::default();
+    for file in &loader.loaded {
+        file.fragment.define(&mut definitions);
+    }
+
+    let mut config = Config::default();
+    let mut diagnostics = loader.diagnostics;
+    let mut labels = Vec::new();
+    for mut file in loader.loaded {
+        let spans = SourceMap::new(&file.source);
+        let (targets, errors) = file.fragment.resolve_targets(&definitions, &spans);
+        let checked = validate::check_targets(
+            targets.iter().map(|(idx, target)| (*idx, target)),
+            &spans,
+            env,
+            &mut labels,
+        );
+        let machines = validate::check_machines(file.fragment.machine.iter().enumerate(), &spans);
+        diagnostics.extend(
+            errors
+                .into_iter()
+                .chain(checked)
+                .chain(machines)
+                .map(|d| file.report(d)),
+        );
+        config
+            .boot_targets
+            .extend(targets.into_iter().map(|(_, target)| target));
+        file.fragment.apply(&mut config);
+    }
+
+    Ok((config, diagnostics))
+}
+
+/// The `*.toml` files in the drop-in directory of `path`, sorted by name.
+fn drop_ins(files: &dyn Files, path: &str) -> Vec<String> {
+    let Some(dir) = strip_suffix_ignore_case(path, ".toml").map(|stem| format!("{stem}.d")) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = match files.list(&dir) {
+        Ok(names) => names,
+        Err(e) => {
+            log::debug!("no drop-ins in {dir}: {e}");
+            return Vec::new();
+        }
+    };
+    names.retain(|name| strip_suffix_ignore_case(name, ".toml").is_some());
+    // FAT is case insensitive and directory order is arbitrary.
+    names.sort_by_cached_key(|name| name.to_ascii_lowercase());
+    names
+        .into_iter()
+        .map(|name| format!("{dir}\\{name}"))
+        .collect()
+}
+
+/// Resolves an include of the file at `from`.
+fn resolve(from: &str, include: &str) -> String {
+    let include = include.replace('/', "\\");
+    if include.starts_with('\\') {
+        return include;
+    }
+    let dir = from.rfind('\\').map_or("", |idx| &from[..idx]);
+    format!("{dir}\\{include}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Files held in memory, by absolute path.
+    struct Memory(Vec<(&'static str, String)>);
+
+    impl Files for Memory {
+        fn read(&self, path: &str) -> Result<String, ConfigError> {
+            self.0
+                .iter()
+                .find(|(p, _)| p.eq_ignore_ascii_case(path))
+                .map(|(_, source)| source.clone())
+                .ok_or(ConfigError::FileNotFound)
+        }
+
+        fn list(&self, dir: &str) -> Result<Vec<String>, ConfigError> {
+            let names: Vec<String> = self
+                .0
+                .iter()
+                .filter_map(|(p, _)| p.strip_prefix(dir)?.strip_prefix('\\'))
+                .map(String::from)
+                .collect();
+            if names.is_empty() {
+                Err(ConfigError::FileNotFound)
+            } else {
+                Ok(names)
+            }
+        }
+    }
+
+    fn labels(config: &Config) -> Vec<&str> {
+        config
+            .boot_targets
+            .iter()
+            .map(TargetConfig::label)
+            .collect()
+    }
+
+    fn entry(label: &str) -> String {
+        format!(
+            "[[boot_targets]]\ntype = \"generic\"\nlabel = \"{label}\"\nexecutable = \"boot():\\\\{label}.efi\"\n"
+        )
+    }
+
+    #[test]
+    fn merges_in_order() {
+        let main = format!("include = [\"base.toml\"]\ntimeout = 5\n{}", entry("main"));
+        let base = format!(
+            "timeout = 10\ntheme = \"default\"\nmenu = \"hidden\"\n{}",
+            entry("base")
+        );
+        let files = Memory(vec![
+            ("\\plex.toml", main),
+            ("\\base.toml", base),
+            ("\\plex.d\\b.toml", entry("b")),
+            (
+                "\\plex.d\\A.toml",
+                "timeout = 3\nreveal_window = 250\nsearch_order = [\"label(USB)\", \"boot()\"]\n"
+                    .into(),
+            ),
+            ("\\plex.d\\notes.txt", "not toml".into()),
+        ]);
+
+        let (config, diagnostics) = load(&files, "\\plex.toml", None).unwrap();
+        assert_eq!(diagnostics, []);
+        assert_eq!(labels(&config), ["base", "main", "b"]);
+        assert_eq!(config.timeout, Some(3));
+        assert_eq!(config.menu, MenuMode::Hidden);
+        assert_eq!(config.reveal_window, 250);
+        assert_eq!(
+            config.search_order,
+            [
+                PartitionReference::Label(String::from("USB")),
+                PartitionReference::Boot
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_missing_files_and_cycles() {
+        let files = Memory(vec![
+            (
+                "\\plex.toml",
+                "include = [\"a.toml\", \"missing.toml\"]\n".into(),
+            ),
+            ("\\a.toml", "include = [\"/plex.toml\"]\n".into()),
+            ("\\plex.d\\broken.toml", "timeout = \n".into()),
+        ]);
+
+        let (_, diagnostics) = load(&files, "\\plex.toml", None).unwrap();
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.file.as_deref(), d.position, d.message.as_str()))
+            .collect();
+        assert_eq!(found[0].0, Some("\\a.toml"));
+        assert_eq!(found[0].1, Some((1, 12)));
+        assert_eq!(
+            found[0].2,
+            "include cycle: \\plex.toml -> \\a.toml -> \\plex.toml"
+        );
+        assert_eq!(found[1].0, None);
+        assert_eq!(found[1].1, Some((1, 22)));
+        assert!(found[1].2.starts_with("cannot include \\missing.toml"));
+        assert_eq!(found[2].0, Some("\\plex.d\\broken.toml"));
+        assert_eq!(found.len(), 3);
+    }
+
+    #[test]
+    fn resolves_relative_includes() {
+        assert_eq!(resolve("\\plex.toml", "a.toml"), "\\a.toml");
+        assert_eq!(
+            resolve("\\plex.d\\x.toml", "y/z.toml"),
+            "\\plex.d\\y\\z.toml"
+        );
+        assert_eq!(resolve("\\plex.d\\x.toml", "/z.toml"), "\\z.toml");
+    }
+}
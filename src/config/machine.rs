@@ -0,0 +1,292 @@
+//! Machine profiles.
+//!
+//! One ESP image can adjust its settings to the machine it boots on with
+//! `[[machine]]` blocks, matched against the System Information structure
+//! of the SMBIOS tables:
+//!
+//! ```toml
+//! [[machine]]
+//! name = "Office ThinkPad"
+//! manufacturer = "LENOVO"
+//! product = "20XW*"
+//! default = "Windows Boot Manager"
+//! timeout = 2
+//! hide = ["Custom Kernel"]
+//! options_append = "i915.enable_psr=0"
+//! ```
+//!
+//! A block matches if each of `manufacturer`, `product`, `serial` and
+//! `uuid` it sets matches the machine. Strings ignore case and `*` matches
+//! any run of characters. The first matching block is used, in the order the
+//! files are merged in, see [`loader`](super::loader).
+//!
+//! The matching profile replaces `default` and `timeout`, hides the entries
+//! listed in `hide` by label or id, including discovered ones, and appends
+//! `options_append` to the options or command line of every entry. To only
+//! append to some entries, map their labels or ids to the options instead:
+//! `options_append = { "Arch Linux" = "nomodeset" }`.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str::FromStr;
+use serde::{Deserialize, Deserializer};
+use uefi::Guid;
+
+use super::DefaultEntry;
+use crate::core::app::DisplayEntry;
+use crate::core::smbios::Smbios;
+
+/// A `[[machine]]` block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MachineProfile {
+    /// Name shown in the system information view.
+    pub name: Option<String>,
+    /// Pattern for the system manufacturer.
+    pub manufacturer: Option<String>,
+    /// Pattern for the product name.
+    pub product: Option<String>,
+    /// Pattern for the serial number.
+    pub serial: Option<String>,
+    /// The system UUID.
+    #[serde(deserialize_with = "guid")]
+    pub uuid: Option<Guid>,
+    /// Replaces the `default` entry.
+    pub default: Option<DefaultEntry>,
+    /// Replaces the `timeout`.
+    pub timeout: Option<u64>,
+    /// Labels or ids of the entries to hide.
+    pub hide: Vec<String>,
+    /// Options appended to the entries.
+    pub options_append: OptionsAppend,
+}
+
+/// The `options_append` of a machine profile.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum OptionsAppend {
+    /// Appended to every entry.
+    All(String),
+    /// Appended to the entries with these labels or ids.
+    Entries(BTreeMap<String, String>),
+}
+
+impl Default for OptionsAppend {
+    fn default() -> Self {
+        Self::Entries(BTreeMap::new())
+    }
+}
+
+impl MachineProfile {
+    /// Whether the block identifies any machine at all. Blocks that do not
+    /// are never used.
+    #[must_use]
+    pub const fn has_criteria(&self) -> bool {
+        self.manufacturer.is_some()
+            || self.product.is_some()
+            || self.serial.is_some()
+            || self.uuid.is_some()
+    }
+
+    /// Whether the profile applies to the machine described by `smbios`.
+    #[must_use]
+    pub fn matches(&self, smbios: &Smbios) -> bool {
+        let matches = |pattern: &Option<String>, value: &str| {
+            pattern
+                .as_deref()
+                .is_none_or(|pattern| glob(pattern.trim().as_bytes(), value.as_bytes()))
+        };
+        self.has_criteria()
+            && matches(&self.manufacturer, &smbios.manufacturer)
+            && matches(&self.product, &smbios.product)
+            && matches(&self.serial, &smbios.serial)
+            && self.uuid.is_none_or(|uuid| smbios.uuid == Some(uuid))
+    }
+
+    /// The `name` of the profile, or what it matches if it has none.
+    #[must_use]
+    pub fn display_name(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        let criteria: Vec<String> = [&self.manufacturer, &self.product, &self.serial]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .chain(self.uuid.map(|uuid| format!("{uuid}")))
+            .collect();
+        criteria.join(" ")
+    }
+
+    /// Hides the entries listed in `hide` and appends `options_append` to
+    /// the remaining ones.
+    pub fn apply<T: DisplayEntry>(&self, targets: &mut Vec<T>) {
+        targets.retain(|target| {
+            let hidden = self.hide.iter().any(|key| is_entry(target, key));
+            if hidden {
+                log::info!(
+                    "hiding \"{}\" on this machine",
+                    target.display_options().label
+                );
+            }
+            !hidden
+        });
+
+        for target in targets {
+            let extra = match &self.options_append {
+                OptionsAppend::All(extra) => Some(extra.as_str()),
+                OptionsAppend::Entries(entries) => entries
+                    .iter()
+                    .find(|(key, _)| is_entry(target, key))
+                    .map(|(_, extra)| extra.as_str()),
+            };
+            let (Some(extra), Some(cmdline)) = (extra, target.cmdline()) else {
+                continue;
+            };
+            if extra.is_empty() {
+                continue;
+            }
+            let cmdline = if cmdline.is_empty() {
+                String::from(extra)
+            } else {
+                format!("{cmdline} {extra}")
+            };
+            if let Err(e) = target.set_cmdline(&cmdline) {
+                log::warn!(
+                    "cannot append options to \"{}\": {e}",
+                    target.display_options().label
+                );
+            }
+        }
+    }
+}
+
+/// Whether `key` is the label or the id of `target`.
+fn is_entry<T: DisplayEntry>(target: &T, key: &str) -> bool {
+    target.display_options().label == key || target.id() == key
+}
+
+/// Matches `text` against `pattern`, where `*` matches any run of
+/// characters, ignoring ASCII case.
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob(rest, &text[skip..])),
+        Some((expected, rest)) => text.split_first().is_some_and(|(actual, text)| {
+            expected.eq_ignore_ascii_case(actual) && glob(rest, text)
+        }),
+    }
+}
+
+fn guid<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Guid>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Guid::from_str(text.trim())
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom(format!("invalid UUID \"{text}\"")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::app::{App, AppCtx, AppResult};
+    use crate::core::bootables::DisplayOptions;
+    use crate::AppError;
+    use alloc::vec;
+
+    fn profile(source: &str) -> MachineProfile {
+        toml::from_str(source).unwrap()
+    }
+
+    fn thinkpad() -> Smbios {
+        Smbios {
+            manufacturer: String::from("LENOVO"),
+            product: String::from("20XWCTO1WW"),
+            serial: String::from("PF2ABCDE"),
+            uuid: Some(uefi::guid!("0a3407de-014b-458b-b5c1-848e92a327a3")),
+        }
+    }
+
+    #[test]
+    fn matches_machines() {
+        let matches = |source: &str| profile(source).matches(&thinkpad());
+
+        assert!(!matches(""));
+        assert!(matches("manufacturer = \"lenovo\""));
+        assert!(matches("manufacturer = \"LENOVO\"\nproduct = \"20XW*\""));
+        assert!(!matches("manufacturer = \"LENOVO\"\nproduct = \"20XV*\""));
+        assert!(matches("product = \"*CTO*\""));
+        assert!(!matches("manufacturer = \"LEN\""));
+        assert!(matches("serial = \"PF2ABCDE\""));
+        assert!(matches("uuid = \"0A3407DE-014B-458B-B5C1-848E92A327A3\""));
+        assert!(!matches("uuid = \"8c8f8eff-ac95-4770-814a-21994f2dbc8f\""));
+        assert!(toml::from_str::<MachineProfile>("uuid = \"not a uuid\"").is_err());
+        assert!(toml::from_str::<MachineProfile>("vendor = \"LENOVO\"").is_err());
+    }
+
+    #[test]
+    fn names_profiles() {
+        assert_eq!(
+            profile("name = \"Office\"\nmanufacturer = \"LENOVO\"").display_name(),
+            "Office"
+        );
+        assert_eq!(
+            profile("manufacturer = \"LENOVO\"\nproduct = \"20XW*\"").display_name(),
+            "LENOVO 20XW*"
+        );
+    }
+
+    /// An entry with a label and a command line.
+    struct Entry(&'static str, String);
+
+    impl App for Entry {
+        fn run(&mut self, _ctx: &mut AppCtx) -> AppResult {
+            AppResult::Done
+        }
+    }
+
+    impl DisplayEntry for Entry {
+        fn display_options(&self) -> DisplayOptions {
+            DisplayOptions {
+                label: String::from(self.0),
+            }
+        }
+
+        fn cmdline(&self) -> Option<String> {
+            Some(self.1.clone())
+        }
+
+        fn set_cmdline(&mut self, cmdline: &str) -> Result<(), AppError> {
+            self.1 = String::from(cmdline);
+            Ok(())
+        }
+    }
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            Entry("Arch Linux", String::from("rw quiet")),
+            Entry("Windows", String::new()),
+            Entry("Custom Kernel", String::from("rw")),
+        ]
+    }
+
+    #[test]
+    fn applies_overlay() {
+        let mut targets = entries();
+        profile("hide = [\"Custom Kernel\"]\noptions_append = \"nomodeset\"").apply(&mut targets);
+        let cmdlines: Vec<(&str, &str)> = targets.iter().map(|t| (t.0, t.1.as_str())).collect();
+        assert_eq!(
+            cmdlines,
+            [
+                ("Arch Linux", "rw quiet nomodeset"),
+                ("Windows", "nomodeset")
+            ]
+        );
+
+        let mut targets = entries();
+        profile("options_append = { \"Arch Linux\" = \"splash\" }").apply(&mut targets);
+        let cmdlines: Vec<&str> = targets.iter().map(|t| t.1.as_str()).collect();
+        assert_eq!(cmdlines, ["rw quiet splash", "", "rw"]);
+    }
+}
@@ -1,15 +1,34 @@
 //! Configuration management for Plex.
 //!
 //! Provides structures and functions to parse the `plex.toml` configuration file
-//! and convert it into boot targets that the application can execute.
+//! and convert it into boot targets that the application can execute. The
+//! configuration may be split over several files, see [`loader`].
 
-use alloc::string::String;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use serde::Deserialize;
 
-use crate::core::bootables::{BootTarget, GenericBootTarget};
+use crate::core::bootables::{ActionTarget, BootTarget, GenericBootTarget, LinuxBootTarget};
+use crate::core::resolver::Resolver;
+use crate::core::system::SystemInfo;
+use crate::error::AppError;
+use condition::When;
+use loader::{Files, Fragment};
+use machine::MachineProfile;
+use template::Definitions;
+use validate::{Diagnostic, Environment, SourceMap};
+
+pub mod condition;
+pub mod loader;
+pub mod machine;
+pub mod template;
+pub mod validate;
 
 /// Represents a boot target configuration entry in `plex.toml`.
+///
+/// Entries are resolved before they are deserialized into this type, so
+/// they may also use `extends` and `*_append` fields and `${var}`
+/// references, see [`template`].
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum TargetConfig {
@@ -22,105 +41,482 @@ pub enum TargetConfig {
         /// Command line options to pass to the executable
         #[serde(default)]
         options: String,
+        /// Stable identifier used to remember this entry, defaults to the label
+        #[serde(default)]
+        id: Option<String>,
+        /// Group the entry is listed under, see [`crate::core::bootables::fold_groups`]
+        #[serde(default)]
+        group: Option<String>,
+        /// Machines the entry is shown on
+        #[serde(default)]
+        when: When,
     },
+    /// A Linux kernel booted via its EFI stub, with initrds served by Plex.
+    Linux {
+        /// Display label for the boot menu
+        label: String,
+        /// Path to the kernel image
+        kernel: String,
+        /// Paths to the initrd images, concatenated in order
+        #[serde(default)]
+        initrd: Vec<String>,
+        /// Kernel command line
+        #[serde(default)]
+        cmdline: String,
+        /// Stable identifier used to remember this entry, defaults to the label
+        #[serde(default)]
+        id: Option<String>,
+        /// Group the entry is listed under, see [`crate::core::bootables::fold_groups`]
+        #[serde(default)]
+        group: Option<String>,
+        /// Machines the entry is shown on
+        #[serde(default)]
+        when: When,
+    },
+    /// Reboots into the firmware setup. Hidden if the firmware does not
+    /// support it.
+    FirmwareSetup(ActionConfig),
+    /// Reboots the machine.
+    Reboot(ActionConfig),
+    /// Turns the machine off.
+    Shutdown(ActionConfig),
+}
+
+/// A built-in action in `plex.toml`, e.g. `type = "reboot"`.
+#[derive(Debug, Deserialize)]
+pub struct ActionConfig {
+    /// Display label for the boot menu, defaults to the name of the action
+    #[serde(default)]
+    label: Option<String>,
+    /// Stable identifier used to remember this entry, defaults to the label
+    #[serde(default)]
+    id: Option<String>,
+    /// Group the entry is listed under, see [`crate::core::bootables::fold_groups`]
+    #[serde(default)]
+    group: Option<String>,
+    /// Machines the entry is shown on
+    #[serde(default)]
+    when: When,
+}
+
+impl ActionConfig {
+    fn into_action_target(self, default_label: &str) -> ActionTarget {
+        let mut target = ActionTarget::new(self.label.as_deref().unwrap_or(default_label));
+        if let Some(id) = self.id {
+            target = target.with_id(id);
+        }
+        if let Some(group) = self.group {
+            target = target.with_group(group);
+        }
+        target
+    }
 }
 
 impl TargetConfig {
-    fn into_boot_target(self) -> BootTarget {
+    /// The label shown in the boot menu.
+    #[must_use]
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Generic { label, .. } | Self::Linux { label, .. } => label,
+            Self::FirmwareSetup(action) => action.label.as_deref().unwrap_or("Firmware Setup"),
+            Self::Reboot(action) => action.label.as_deref().unwrap_or("Reboot"),
+            Self::Shutdown(action) => action.label.as_deref().unwrap_or("Shut down"),
+        }
+    }
+
+    /// The conditions under which the entry is shown.
+    #[must_use]
+    pub const fn when(&self) -> &When {
+        match self {
+            Self::Generic { when, .. } | Self::Linux { when, .. } => when,
+            Self::FirmwareSetup(action) | Self::Reboot(action) | Self::Shutdown(action) => {
+                &action.when
+            }
+        }
+    }
+
+    fn into_boot_target(self) -> Result<BootTarget, AppError> {
         match self {
             Self::Generic {
                 label,
                 executable,
                 options,
-            } => BootTarget::Generic(GenericBootTarget::new(label, executable, options)),
+                id,
+                group,
+                when: _,
+            } => {
+                let mut target = GenericBootTarget::new(label, executable, options)?;
+                if let Some(id) = id {
+                    target = target.with_id(id);
+                }
+                if let Some(group) = group {
+                    target = target.with_group(group);
+                }
+                Ok(BootTarget::Generic(target))
+            }
+            Self::Linux {
+                label,
+                kernel,
+                initrd,
+                cmdline,
+                id,
+                group,
+                when: _,
+            } => {
+                let mut target = LinuxBootTarget::new(label, kernel, initrd, cmdline)?;
+                if let Some(id) = id {
+                    target = target.with_id(id);
+                }
+                if let Some(group) = group {
+                    target = target.with_group(group);
+                }
+                Ok(BootTarget::Linux(target))
+            }
+            Self::FirmwareSetup(action) => Ok(BootTarget::FirmwareSetup(
+                action.into_action_target("Firmware Setup"),
+            )),
+            Self::Reboot(action) => Ok(BootTarget::Reboot(action.into_action_target("Reboot"))),
+            Self::Shutdown(action) => {
+                Ok(BootTarget::Shutdown(action.into_action_target("Shut down")))
+            }
         }
     }
 }
 
+/// Selects which boot target is highlighted at startup and booted once
+/// the countdown expires.
+///
+/// Accepts either a zero-based index (`default = 1`), the label of an
+/// entry (`default = "Arch Linux"`), or `"@saved"` to pick the entry that
+/// was booted last.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DefaultEntry {
+    /// Entry at the given position in the menu.
+    Index(usize),
+    /// First entry whose label matches exactly.
+    Label(String),
+    /// Entry with the given stable ID, see
+    /// [`DisplayEntry::id`](crate::core::app::DisplayEntry::id). Not read
+    /// from `plex.toml`, used to boot an entry once.
+    Id(String),
+    /// The last entry that was booted successfully.
+    Saved,
+    /// No preference, the first entry is selected.
+    #[default]
+    First,
+}
+
+impl DefaultEntry {
+    /// Keyword used in `plex.toml` to refer to the last booted entry.
+    pub const SAVED_KEYWORD: &'static str = "@saved";
+}
+
+impl<'de> Deserialize<'de> for DefaultEntry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = DefaultEntry;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                write!(
+                    f,
+                    "an entry index, an entry label or \"{}\"",
+                    DefaultEntry::SAVED_KEYWORD
+                )
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                usize::try_from(v)
+                    .map(DefaultEntry::Index)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Signed(v), &self))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                usize::try_from(v)
+                    .map(DefaultEntry::Index)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                if v == DefaultEntry::SAVED_KEYWORD {
+                    Ok(DefaultEntry::Saved)
+                } else {
+                    Ok(DefaultEntry::Label(v.into()))
+                }
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
 /// Top-level configuration structure
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub struct Config {
     /// The global UI theme
-    #[serde(default)]
     pub theme: crate::ui::theme::Theme,
+    /// Whether to draw the UI on the graphical or the text console
+    pub console: crate::core::display::ConsoleMode,
+    /// Seconds to wait before booting the default entry. No timeout means
+    /// the menu waits for the user indefinitely.
+    pub timeout: Option<u64>,
+    /// The entry selected at startup and booted when the timeout expires.
+    pub default: DefaultEntry,
+    /// Whether the boot menu is shown or only revealed on request.
+    pub menu: crate::ui::boot_menu::MenuMode,
+    /// Milliseconds Space or Shift is looked for before booting the default
+    /// entry with a hidden menu.
+    pub reveal_window: u64,
+    /// Partitions searched first for `any()` paths, see
+    /// [`crate::path::DiskManager::set_search_order`].
+    pub search_order: Vec<crate::path::PartitionReference>,
     /// List of boot targets
     pub boot_targets: Vec<TargetConfig>,
+    /// Resolvers discovering additional boot targets, run in order. None by
+    /// default, so entries only show up once discovery is enabled.
+    pub resolvers: Vec<Resolver>,
+    /// Machine profiles, see [`machine`]. The first matching one is used.
+    pub machines: Vec<MachineProfile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: crate::ui::theme::Theme::default(),
+            console: crate::core::display::ConsoleMode::default(),
+            timeout: None,
+            default: DefaultEntry::First,
+            menu: crate::ui::boot_menu::MenuMode::default(),
+            reveal_window: Self::DEFAULT_REVEAL_WINDOW,
+            search_order: alloc::vec![crate::path::PartitionReference::Boot],
+            boot_targets: Vec::new(),
+            resolvers: Vec::new(),
+            machines: Vec::new(),
+        }
+    }
 }
 
 impl Config {
-    /// Load configuration from a TOML file at the specified path.
+    /// Default `reveal_window` in milliseconds.
+    pub const DEFAULT_REVEAL_WINDOW: u64 = 500;
+
+    /// `timeout` of the [`Self::fallback`] configuration in seconds.
+    pub const FALLBACK_TIMEOUT: u64 = 10;
+
+    /// Load the configuration at `path` with its includes and drop-ins, and
+    /// check every file with [`validate::validate`].
+    ///
+    /// Problems in included files are returned as diagnostics.
     ///
     /// # Errors
-    /// Returns an error if the file cannot be read or the contents are invalid TOML.
-    pub fn load_from_file(path: &str) -> Result<Self, ConfigError> {
-        // Read file from UEFI filesystem
-        let contents = read_file_to_string(path)?;
+    /// Returns an error if the main file cannot be read or is invalid TOML.
+    pub fn load(
+        files: &dyn Files,
+        path: &str,
+        env: Option<&dyn Environment>,
+    ) -> Result<(Self, Vec<Diagnostic>), ConfigError> {
+        loader::load(files, path, env)
+    }
 
-        // Parse TOML
-        let config: Self = toml::from_str(&contents).map_err(|e| {
-            log::error!("TOML parse error: {e:?}");
-            ConfigError::ParseError
-        })?;
+    /// Parse a single configuration file from TOML, ignoring `include`.
+    ///
+    /// Entries are resolved with the templates and variables of the file.
+    ///
+    /// # Errors
+    /// Returns [`ConfigError::Parse`] with the position of the first syntax
+    /// or type error.
+    pub fn parse(source: &str) -> Result<Self, ConfigError> {
+        let mut fragment = Fragment::parse(source)?;
+        let mut definitions = Definitions::default();
+        fragment.define(&mut definitions);
 
+        let (targets, errors) = fragment.resolve_targets(&definitions, &SourceMap::new(source));
+        if let Some(error) = errors.into_iter().next() {
+            return Err(ConfigError::Parse(error.into()));
+        }
+        let mut config = Self {
+            boot_targets: targets.into_iter().map(|(_, target)| target).collect(),
+            ..Self::default()
+        };
+        fragment.apply(&mut config);
         Ok(config)
     }
 
-    /// Convert config into a vector of `GenericBootTarget`.
+    /// The configuration used when `plex.toml` cannot be loaded.
+    ///
+    /// It has no static entries and runs the resolvers from
+    /// [`Resolver::fallback`], so whatever is installed can still be booted.
+    /// The first entry boots after [`Self::FALLBACK_TIMEOUT`], so a machine
+    /// nobody is watching does not wait forever.
     #[must_use]
-    pub fn into_boot_targets(self) -> Vec<BootTarget> {
-        self.boot_targets
-            .into_iter()
-            .map(TargetConfig::into_boot_target)
-            .collect()
+    pub fn fallback() -> Self {
+        Self {
+            timeout: Some(Self::FALLBACK_TIMEOUT),
+            resolvers: Resolver::fallback(),
+            ..Self::default()
+        }
     }
-}
 
-/// Read a file from the UEFI filesystem into a String
-fn read_file_to_string(path: &str) -> Result<String, ConfigError> {
-    use uefi::fs::FileSystem;
-    use uefi::CString16;
-
-    // Convert path to CString16
-    let path_cstr = CString16::try_from(path).map_err(|_| ConfigError::InvalidPath)?;
+    /// Drops the entries whose `when` conditions do not hold on this machine,
+    /// and actions the firmware does not support.
+    pub fn retain_matching(&mut self, system: &SystemInfo, env: &dyn Environment) {
+        self.boot_targets.retain(|target| {
+            if matches!(target, TargetConfig::FirmwareSetup(_)) && !system.firmware_setup {
+                log::info!(
+                    "hiding \"{}\", the firmware cannot boot to its setup",
+                    target.label()
+                );
+                return false;
+            }
+            let matches = target.when().matches(system, env);
+            if !matches {
+                log::info!("hiding \"{}\", its conditions do not match", target.label());
+            }
+            matches
+        });
+    }
 
-    // Get filesystem protocol
-    let mut fs = FileSystem::new(
-        uefi::boot::get_image_file_system(uefi::boot::image_handle())
-            .map_err(|_| ConfigError::FsError)?,
-    );
+    /// Finds the first machine profile matching this machine and applies
+    /// its `default` and `timeout`.
+    ///
+    /// The returned profile still has to be applied to the entries with
+    /// [`MachineProfile::apply`] once the discovered ones are known.
+    #[must_use]
+    pub fn select_machine(&mut self, system: &SystemInfo) -> Option<MachineProfile> {
+        let smbios = system.smbios.as_ref()?;
+        let profile = self.machines.iter().find(|m| m.matches(smbios))?.clone();
+        log::info!("using machine profile \"{}\"", profile.display_name());
 
-    // Open and read the file - fs.read() returns Vec<u8> directly
-    let buf = fs
-        .read(path_cstr.as_ref())
-        .map_err(|_| ConfigError::FileNotFound)?;
+        if let Some(default) = &profile.default {
+            self.default = default.clone();
+        }
+        if profile.timeout.is_some() {
+            self.timeout = profile.timeout;
+        }
+        Some(profile)
+    }
 
-    // Convert to String (assuming UTF-8)
-    String::from_utf8(buf).map_err(|_| ConfigError::EncodingError)
+    /// Convert the static entries into a vector of `BootTarget`.
+    ///
+    /// Entries whose strings cannot be passed to UEFI are dropped, the
+    /// validation pass has already reported them.
+    #[must_use]
+    pub fn into_boot_targets(self) -> Vec<BootTarget> {
+        self.boot_targets
+            .into_iter()
+            .filter_map(|target| {
+                let label = target.label().to_string();
+                target
+                    .into_boot_target()
+                    .inspect_err(|e| log::warn!("skipping \"{label}\": {e}"))
+                    .ok()
+            })
+            .collect()
+    }
 }
 
 /// Errors that can occur when loading or parsing the configuration.
-#[derive(Debug)]
+#[derive(Debug, thiserror_no_std::Error)]
 pub enum ConfigError {
     /// The specified path could not be converted to a valid UEFI path.
+    #[error("Invalid file path")]
     InvalidPath,
     /// The configuration file was not found on the filesystem.
+    #[error("Config file not found")]
     FileNotFound,
     /// An error occurred while accessing the filesystem.
+    #[error("Filesystem error")]
     FsError,
     /// The file contents could not be decoded as UTF-8.
+    #[error("File encoding error")]
     EncodingError,
     /// The file contents could not be parsed as valid TOML.
-    ParseError,
+    #[error("{0}")]
+    Parse(alloc::boxed::Box<Diagnostic>),
 }
 
-impl core::fmt::Display for ConfigError {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        match self {
-            Self::InvalidPath => write!(f, "Invalid file path"),
-            Self::FileNotFound => write!(f, "Config file not found"),
-            Self::FsError => write!(f, "Filesystem error"),
-            Self::EncodingError => write!(f, "File encoding error"),
-            Self::ParseError => write!(f, "TOML parse error"),
+impl From<ConfigError> for Diagnostic {
+    fn from(error: ConfigError) -> Self {
+        match error {
+            ConfigError::Parse(diagnostic) => *diagnostic,
+            error => Self::error(alloc::format!("{error}")),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::app::DisplayEntry;
+    use crate::path::{PartitionReference, PathReference};
+
+    /// A machine without any partitions.
+    struct NoDisks;
+
+    impl Environment for NoDisks {
+        fn has_partition(&self, _location: &PartitionReference) -> bool {
+            false
+        }
+
+        fn has_file(&self, _path: &PathReference) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn hides_unsupported_actions() {
+        let source = "[[boot_targets]]\ntype = \"firmware_setup\"\n\n\
+                      [[boot_targets]]\ntype = \"reboot\"\n\n\
+                      [[boot_targets]]\ntype = \"shutdown\"\nlabel = \"Power off\"\n";
+        let labels = |firmware_setup: bool| {
+            let system = SystemInfo {
+                arch: "x86_64",
+                secure_boot: false,
+                firmware_vendor: String::from("EDK II"),
+                firmware_revision: 0x0001_0000,
+                smbios: None,
+                firmware_setup,
+            };
+            let mut config = Config::parse(source).unwrap();
+            config.retain_matching(&system, &NoDisks);
+            config
+                .boot_targets
+                .iter()
+                .map(|t| String::from(t.label()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(labels(false), ["Reboot", "Power off"]);
+        assert_eq!(labels(true), ["Firmware Setup", "Reboot", "Power off"]);
+    }
+
+    #[test]
+    fn drops_entries_uefi_cannot_represent() {
+        let source = "[[boot_targets]]\ntype = \"generic\"\nlabel = \"Arch\"\n\
+                      executable = \"boot():\\\\vmlinuz\"\noptions = \"quiet \u{1F600}\"\n\n\
+                      [[boot_targets]]\ntype = \"generic\"\nlabel = \"Shell\"\n\
+                      executable = \"boot():\\\\shell.efi\"\n\n\
+                      [[boot_targets]]\ntype = \"linux\"\nlabel = \"Fedora\"\n\
+                      kernel = \"boot():\\\\vmlinuz\"\ncmdline = \"rhgb \u{1F600}\"\n";
+        let targets = Config::parse(source).unwrap().into_boot_targets();
+        let labels: Vec<_> = targets.iter().map(|t| t.display_options().label).collect();
+        assert_eq!(labels, ["Shell"]);
+    }
+
+    #[test]
+    fn discovery_is_opt_in() {
+        assert_eq!(Config::parse("").unwrap().resolvers, []);
+        let config = Config::parse("[[resolvers]]\nname = \"bls\"\n").unwrap();
+        assert_eq!(
+            config.resolvers,
+            [Resolver::Bls(
+                crate::core::resolver::bls::BlsOptions::default()
+            )]
+        );
+        assert_eq!(Config::fallback().resolvers[..2], Resolver::defaults());
+    }
+}
@@ -0,0 +1,326 @@
+//! Entry templates and variables.
+//!
+//! Entries that share most of their settings can inherit them from a
+//! template:
+//!
+//! ```toml
+//! [vars]
+//! root = "root=UUID=0a3407de-014b-458b-b5c1-848e92a327a3"
+//!
+//! [templates.arch]
+//! type = "linux"
+//! cmdline = "${root} rw quiet"
+//!
+//! [[boot_targets]]
+//! extends = "arch"
+//! label = "Arch Linux"
+//! kernel = "boot():\\vmlinuz-linux"
+//! cmdline_append = "splash"
+//! ```
+//!
+//! An entry with `extends` starts from every field of the template and
+//! overrides the ones it sets itself. Templates may extend other templates.
+//! A field suffixed with `_append`, e.g. `options_append` or `initrd_append`,
+//! adds to the inherited value instead of replacing it: strings are joined
+//! with a space and arrays are concatenated.
+//!
+//! Once inherited, `${name}` in any string of the entry is replaced with the
+//! value of `name` in `[vars]`. Templates and variables are shared by all
+//! files of the configuration, the last definition of a name wins.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use toml::{Table, Value};
+
+use super::TargetConfig;
+
+/// Suffix of fields that add to the inherited value.
+const APPEND_SUFFIX: &str = "_append";
+
+/// Field naming the template an entry or template inherits from.
+const EXTENDS: &str = "extends";
+
+/// Errors resolving an entry into a [`TargetConfig`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror_no_std::Error)]
+pub enum TemplateError {
+    /// `extends` names a template that is not defined.
+    #[error("unknown template \"{0}\"")]
+    UnknownTemplate(String),
+    /// Templates extend each other in a loop.
+    #[error("template cycle: {0}")]
+    Cycle(String),
+    /// `extends` is not a string.
+    #[error("extends must be the name of a template")]
+    InvalidExtends,
+    /// An `_append` field does not match the type of the inherited value.
+    #[error("cannot append to {0}, it must be a string or an array like the inherited value")]
+    Append(String),
+    /// `${name}` refers to a variable that is not defined.
+    #[error("unknown variable \"{0}\"")]
+    UnknownVar(String),
+    /// A `${` without the closing brace.
+    #[error("unterminated \"${{\"")]
+    Unterminated,
+    /// The resolved entry is not a valid boot target.
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// The templates and variables of a configuration.
+#[derive(Debug, Default)]
+pub(super) struct Definitions {
+    templates: BTreeMap<String, Table>,
+    vars: BTreeMap<String, String>,
+}
+
+impl Definitions {
+    /// Adds the definitions of a file, replacing earlier ones of the same name.
+    pub(super) fn extend(
+        &mut self,
+        templates: &BTreeMap<String, Table>,
+        vars: &BTreeMap<String, String>,
+    ) {
+        self.templates
+            .extend(templates.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self.vars
+            .extend(vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    /// Resolves inheritance and variables of a `boot_targets` entry.
+    ///
+    /// On error, also returns the field of the entry the error is about,
+    /// if known.
+    pub(super) fn resolve(
+        &self,
+        entry: Table,
+    ) -> Result<TargetConfig, (TemplateError, Option<String>)> {
+        let mut entry = self.inherit(entry, &mut Vec::new()).map_err(|e| {
+            let field = match &e {
+                TemplateError::Append(field) => format!("{field}{APPEND_SUFFIX}"),
+                _ => String::from(EXTENDS),
+            };
+            (e, Some(field))
+        })?;
+        for (key, value) in &mut entry {
+            self.substitute(value).map_err(|e| (e, Some(key.clone())))?;
+        }
+        Value::Table(entry)
+            .try_into()
+            .map_err(|e: toml::de::Error| {
+                let message = String::from(e.message().trim_end());
+                (TemplateError::Invalid(message), None)
+            })
+    }
+
+    /// Flattens `table` and the templates it extends. `chain` holds the
+    /// templates being flattened, to detect cycles.
+    fn inherit(&self, mut table: Table, chain: &mut Vec<String>) -> Result<Table, TemplateError> {
+        let Some(extends) = table.remove(EXTENDS) else {
+            return merge(Table::new(), table);
+        };
+        let Value::String(name) = extends else {
+            return Err(TemplateError::InvalidExtends);
+        };
+
+        if chain.contains(&name) {
+            chain.push(name);
+            return Err(TemplateError::Cycle(chain.join(" -> ")));
+        }
+        let template = self
+            .templates
+            .get(&name)
+            .ok_or_else(|| TemplateError::UnknownTemplate(name.clone()))?;
+        chain.push(name);
+        let base = self.inherit(template.clone(), chain)?;
+        chain.pop();
+
+        merge(base, table)
+    }
+
+    /// Replaces `${name}` in every string of `value`.
+    fn substitute(&self, value: &mut Value) -> Result<(), TemplateError> {
+        match value {
+            Value::String(text) => *text = self.expand(text)?,
+            Value::Array(items) => {
+                for item in items {
+                    self.substitute(item)?;
+                }
+            }
+            Value::Table(table) => {
+                for (_, item) in table.iter_mut() {
+                    self.substitute(item)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn expand(&self, text: &str) -> Result<String, TemplateError> {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let end = after.find('}').ok_or(TemplateError::Unterminated)?;
+            let name = &after[..end];
+            let value = self
+                .vars
+                .get(name)
+                .ok_or_else(|| TemplateError::UnknownVar(String::from(name)))?;
+            out.push_str(value);
+            rest = &after[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// Applies the fields of `child` on top of `base`.
+fn merge(mut base: Table, child: Table) -> Result<Table, TemplateError> {
+    let (appends, fields): (Vec<_>, Vec<_>) = child
+        .into_iter()
+        .partition(|(key, _)| key.ends_with(APPEND_SUFFIX));
+    base.extend(fields);
+
+    for (key, addition) in appends {
+        let field = &key[..key.len() - APPEND_SUFFIX.len()];
+        match (base.get_mut(field), addition) {
+            (None, addition) => {
+                base.insert(String::from(field), addition);
+            }
+            (Some(Value::String(value)), Value::String(addition)) => {
+                if !value.is_empty() && !addition.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(&addition);
+            }
+            (Some(Value::Array(values)), Value::Array(addition)) => values.extend(addition),
+            _ => return Err(TemplateError::Append(String::from(field))),
+        }
+    }
+    Ok(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn definitions(source: &str) -> Definitions {
+        let table: Table = toml::from_str(source).unwrap();
+        let section = |key: &str| {
+            table
+                .get(key)
+                .and_then(Value::as_table)
+                .cloned()
+                .unwrap_or_default()
+        };
+        let mut definitions = Definitions::default();
+        let templates = section("templates")
+            .into_iter()
+            .map(|(k, v)| (k, v.as_table().cloned().unwrap()))
+            .collect();
+        let vars = section("vars")
+            .into_iter()
+            .map(|(k, v)| (k, String::from(v.as_str().unwrap())))
+            .collect();
+        definitions.extend(&templates, &vars);
+        definitions
+    }
+
+    fn entry(source: &str) -> Table {
+        toml::from_str(source).unwrap()
+    }
+
+    const DEFINITIONS: &str = r#"
+[vars]
+root = "root=UUID=0a3407de"
+
+[templates.base]
+type = "linux"
+kernel = "boot():\\vmlinuz"
+initrd = ["boot():\\ucode.img"]
+cmdline = "${root} rw"
+
+[templates.quiet]
+extends = "base"
+cmdline_append = "quiet"
+
+[templates.loop_a]
+extends = "loop_b"
+
+[templates.loop_b]
+extends = "loop_a"
+"#;
+
+    #[test]
+    fn inherits_and_appends() {
+        let defs = definitions(DEFINITIONS);
+        let target = defs
+            .resolve(entry(
+                "extends = \"quiet\"\nlabel = \"Arch\"\ncmdline_append = \"splash\"\ninitrd_append = [\"boot():\\\\initrd.img\"]\n",
+            ))
+            .unwrap();
+        let TargetConfig::Linux {
+            label,
+            kernel,
+            initrd,
+            cmdline,
+            ..
+        } = target
+        else {
+            panic!("expected a linux entry");
+        };
+        assert_eq!(label, "Arch");
+        assert_eq!(kernel, "boot():\\vmlinuz");
+        assert_eq!(initrd, vec!["boot():\\ucode.img", "boot():\\initrd.img"]);
+        assert_eq!(cmdline, "root=UUID=0a3407de rw quiet splash");
+    }
+
+    #[test]
+    fn own_fields_override_template() {
+        let defs = definitions(DEFINITIONS);
+        let target = defs
+            .resolve(entry(
+                "extends = \"base\"\nlabel = \"LTS\"\ncmdline = \"single\"\n",
+            ))
+            .unwrap();
+        assert!(matches!(target, TargetConfig::Linux { cmdline, .. } if cmdline == "single"));
+    }
+
+    #[test]
+    fn reports_errors() {
+        let defs = definitions(DEFINITIONS);
+        let error = |source: &str| defs.resolve(entry(source)).unwrap_err();
+
+        assert_eq!(
+            error("extends = \"missing\"\nlabel = \"A\"\n"),
+            (
+                TemplateError::UnknownTemplate(String::from("missing")),
+                Some(String::from(EXTENDS))
+            )
+        );
+        assert_eq!(
+            error("extends = \"loop_a\"\nlabel = \"A\"\n").0,
+            TemplateError::Cycle(String::from("loop_a -> loop_b -> loop_a"))
+        );
+        assert_eq!(
+            error("extends = \"base\"\nlabel = \"${nope}\"\n"),
+            (
+                TemplateError::UnknownVar(String::from("nope")),
+                Some(String::from("label"))
+            )
+        );
+        assert_eq!(
+            error("extends = \"base\"\nlabel = \"A\"\ninitrd_append = \"x\"\n").0,
+            TemplateError::Append(String::from("initrd"))
+        );
+        assert!(matches!(
+            error("type = \"generic\"\nlabel = \"A\"\n").0,
+            TemplateError::Invalid(_)
+        ));
+    }
+}
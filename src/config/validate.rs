@@ -0,0 +1,553 @@
+//! Validation of `plex.toml`.
+//!
+//! [`validate`] checks a parsed [`Config`] for problems that would only show
+//! up when an entry is booted, and reports each of them as a [`Diagnostic`]
+//! pointing at the offending entry and field in the source. Checks against
+//! the actual disks are delegated to an [`Environment`], so the same code
+//! runs at boot and against a mounted ESP.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::OnceCell;
+use core::ops::Range;
+use core::str::FromStr;
+use serde::Serialize;
+use toml::de::{DeTable, DeValue};
+use toml::Spanned;
+use uefi::CString16;
+
+use crate::config::condition::{When, ARCHES};
+use crate::config::machine::MachineProfile;
+use crate::config::{Config, TargetConfig};
+use crate::path::{PartitionError, PartitionReference, PathReference};
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The entry or setting cannot work as configured.
+    Error,
+    /// Probably a mistake, but plex can cope with it.
+    Warning,
+}
+
+impl core::fmt::Display for Severity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in `plex.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// What is wrong.
+    pub message: String,
+    /// Byte range of the offending value in the source, if known.
+    pub span: Option<Range<usize>>,
+    /// One-based line and column of the start of `span`.
+    pub position: Option<(usize, usize)>,
+    /// Index of the offending entry in `boot_targets`.
+    pub entry: Option<usize>,
+    /// Name of the offending field, e.g. `executable`.
+    pub field: Option<String>,
+    /// Path of the included file or drop-in the problem is in, `None` for
+    /// the main configuration file.
+    pub file: Option<String>,
+}
+
+impl Diagnostic {
+    /// An error without location.
+    #[must_use]
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            span: None,
+            position: None,
+            entry: None,
+            field: None,
+            file: None,
+        }
+    }
+
+    /// A warning without location.
+    #[must_use]
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(message)
+        }
+    }
+
+    /// Converts a TOML syntax or type error.
+    #[must_use]
+    pub fn from_toml(error: &toml::de::Error, source: &str) -> Self {
+        Self::error(error.message().trim_end()).at(source, error.span())
+    }
+
+    /// Points the diagnostic at a byte range of `source`.
+    #[must_use]
+    pub fn at(mut self, source: &str, span: Option<Range<usize>>) -> Self {
+        self.position = span.as_ref().map(|span| line_column(source, span.start));
+        self.span = span;
+        self
+    }
+
+    /// The setting the diagnostic is about, e.g. `boot_targets[2].kernel`.
+    #[must_use]
+    pub fn subject(&self) -> Option<String> {
+        match (self.entry, &self.field) {
+            (Some(entry), Some(field)) => Some(format!("boot_targets[{entry}].{field}")),
+            (Some(entry), None) => Some(format!("boot_targets[{entry}]")),
+            (None, Some(field)) => Some(field.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// Attributes the diagnostic to a file other than the main one.
+    #[must_use]
+    pub fn in_file(mut self, path: impl Into<String>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// Attributes the diagnostic to a field of a `boot_targets` entry.
+    #[must_use]
+    pub fn in_entry(mut self, entry: usize, field: impl Into<String>) -> Self {
+        self.entry = Some(entry);
+        self.field = Some(field.into());
+        self
+    }
+}
+
+impl core::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.severity)?;
+        match (&self.file, self.position) {
+            (Some(file), Some((line, column))) => write!(f, " at {file}:{line}:{column}")?,
+            (Some(file), None) => write!(f, " in {file}")?,
+            (None, Some((line, column))) => write!(f, " at {line}:{column}")?,
+            (None, None) => {}
+        }
+        if let Some(subject) = self.subject() {
+            write!(f, " in {subject}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Converts a byte offset into a one-based line and column.
+///
+/// # Example
+/// ```
+/// use plex_boot::config::validate::line_column;
+/// assert_eq!(line_column("a = 1\nb = 2\n", 8), (2, 3));
+/// ```
+#[must_use]
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = source.get(..offset).unwrap_or(source);
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// The disks the configuration refers to.
+pub trait Environment {
+    /// Whether the partition exists.
+    fn has_partition(&self, location: &PartitionReference) -> bool;
+
+    /// How many partitions match, more than one makes a label ambiguous.
+    /// Defaults to one if the partition exists.
+    fn count_partitions(&self, location: &PartitionReference) -> usize {
+        usize::from(self.has_partition(location))
+    }
+
+    /// Whether the file exists. Only called if its partition exists.
+    fn has_file(&self, path: &PathReference) -> bool;
+}
+
+/// Checks `config`, parsed from `source`, and returns every problem found.
+///
+/// Paths are only checked against the disks if an `env` is given.
+#[must_use]
+pub fn validate(config: &Config, source: &str, env: Option<&dyn Environment>) -> Vec<Diagnostic> {
+    let spans = SourceMap::new(source);
+    check_targets(
+        config.boot_targets.iter().enumerate(),
+        &spans,
+        env,
+        &mut Vec::new(),
+    )
+    .into_iter()
+    .chain(check_machines(config.machines.iter().enumerate(), &spans))
+    .collect()
+}
+
+/// Checks the `machine` blocks of a single file, located with `spans`.
+pub(super) fn check_machines<'m>(
+    machines: impl IntoIterator<Item = (usize, &'m MachineProfile)>,
+    spans: &SourceMap,
+) -> Vec<Diagnostic> {
+    machines
+        .into_iter()
+        .filter(|(_, machine)| !machine.has_criteria())
+        .map(|(idx, _)| {
+            let diagnostic = Diagnostic::warning(
+                "machine profile matches no machine, set manufacturer, product, serial or uuid",
+            )
+            .at(spans.source, spans.element("machine", idx));
+            Diagnostic {
+                field: Some(format!("machine[{idx}]")),
+                ..diagnostic
+            }
+        })
+        .collect()
+}
+
+/// Checks the `boot_targets` of a single file, located with `spans`, each
+/// given with its index in the file.
+///
+/// `labels` holds the labels seen in previously checked files, so
+/// duplicates are found across files.
+pub(super) fn check_targets<'t>(
+    targets: impl IntoIterator<Item = (usize, &'t TargetConfig)>,
+    spans: &SourceMap,
+    env: Option<&dyn Environment>,
+    labels: &mut Vec<String>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for (idx, target) in targets {
+        let mut report = |diagnostic: Diagnostic, field: &str, element: Option<usize>| {
+            diagnostics.push(
+                diagnostic
+                    .at(spans.source, spans.field(idx, field, element))
+                    .in_entry(idx, field),
+            );
+        };
+
+        let label = target.label();
+        if labels.iter().any(|l| l == label) {
+            report(
+                Diagnostic::warning(format!("duplicate label \"{label}\"")),
+                "label",
+                None,
+            );
+        }
+        labels.push(String::from(label));
+
+        match target {
+            TargetConfig::Generic {
+                executable,
+                options,
+                ..
+            } => {
+                check_path(executable, env, |d| report(d, "executable", None));
+                check_ucs2(options, |d| report(d, "options", None));
+            }
+            TargetConfig::Linux {
+                kernel,
+                initrd,
+                cmdline,
+                ..
+            } => {
+                check_path(kernel, env, |d| report(d, "kernel", None));
+                for (i, path) in initrd.iter().enumerate() {
+                    check_path(path, env, |d| report(d, "initrd", Some(i)));
+                }
+                check_ucs2(cmdline, |d| report(d, "cmdline", None));
+            }
+            TargetConfig::FirmwareSetup(_)
+            | TargetConfig::Reboot(_)
+            | TargetConfig::Shutdown(_) => {}
+        }
+
+        check_when(target.when(), |d| report(d, "when", None));
+    }
+
+    diagnostics
+}
+
+fn check_path(path: &str, env: Option<&dyn Environment>, mut report: impl FnMut(Diagnostic)) {
+    let reference = match PathReference::parse(path) {
+        Ok(reference) => reference,
+        Err(e) => {
+            report(Diagnostic::error(format!(
+                "invalid path reference \"{path}\": {e}"
+            )));
+            return;
+        }
+    };
+    if CString16::try_from(reference.path.as_str()).is_err() {
+        report(Diagnostic::error(format!(
+            "path \"{path}\" contains characters UEFI cannot represent"
+        )));
+        return;
+    }
+
+    let Some(env) = env else {
+        return;
+    };
+    // `any()` matches whichever partition has the file.
+    let count = if reference.location == PartitionReference::Any {
+        1
+    } else {
+        env.count_partitions(&reference.location)
+    };
+    let error = match count {
+        0 => PartitionError::NotFound(reference.location),
+        1 if env.has_file(&reference) => return,
+        1 => {
+            report(Diagnostic::error(format!("{path} does not exist")));
+            return;
+        }
+        count => PartitionError::Ambiguous {
+            reference: reference.location,
+            count,
+        },
+    };
+    report(Diagnostic::error(format!("{error}")));
+}
+
+fn check_when(when: &When, mut report: impl FnMut(Diagnostic)) {
+    for arch in when
+        .arch
+        .iter()
+        .filter(|arch| !ARCHES.contains(&arch.as_str()))
+    {
+        report(Diagnostic::warning(format!(
+            "unknown architecture \"{arch}\", expected one of {}",
+            ARCHES.join(", ")
+        )));
+    }
+    if let Some(guid) = when
+        .partition
+        .as_ref()
+        .filter(|guid| uefi::Guid::from_str(guid).is_err())
+    {
+        report(Diagnostic::error(format!(
+            "invalid partition GUID \"{guid}\""
+        )));
+    }
+}
+
+fn check_ucs2(text: &str, mut report: impl FnMut(Diagnostic)) {
+    if CString16::try_from(text).is_ok() {
+        return;
+    }
+    let offending = text
+        .chars()
+        .find(|&c| c == '\0' || u32::from(c) > 0xFFFF)
+        .map_or_else(String::new, |c| format!(" {c:?}"));
+    report(Diagnostic::error(format!(
+        "contains a character UEFI cannot represent{offending}, only UCS-2 is supported"
+    )));
+}
+
+/// Locates values in the source of a single file, for diagnostics.
+///
+/// The source is parsed with spans the first time a value is looked up, and
+/// only once however many diagnostics a file has.
+pub(super) struct SourceMap<'a> {
+    /// The source values are located in.
+    pub(super) source: &'a str,
+    document: OnceCell<Option<Spanned<DeTable<'a>>>>,
+}
+
+impl<'a> SourceMap<'a> {
+    /// Locates values in `source`, which is not parsed until needed.
+    pub(super) const fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            document: OnceCell::new(),
+        }
+    }
+
+    fn document(&self) -> Option<&DeTable<'a>> {
+        self.document
+            .get_or_init(|| DeTable::parse(self.source).ok())
+            .as_ref()
+            .map(Spanned::get_ref)
+    }
+
+    /// The span of `boot_targets[entry].field`, or of its element if
+    /// `element` is given. Falls back to the span of the entry.
+    fn field(&self, entry: usize, field: &str, element: Option<usize>) -> Option<Range<usize>> {
+        let entry = array(self.document()?, "boot_targets")?.get(entry)?;
+        let DeValue::Table(table) = entry.get_ref() else {
+            return Some(entry.span());
+        };
+        let Some(value) = get(table, field) else {
+            return Some(entry.span());
+        };
+        match (value.get_ref(), element) {
+            (DeValue::Array(items), Some(idx)) => items.get(idx).map(Spanned::span),
+            _ => Some(value.span()),
+        }
+        .filter(|span| span.end <= self.source.len())
+    }
+
+    /// The span of `boot_targets[entry].field`, if the entry sets the field.
+    pub(super) fn entry_field(&self, entry: usize, field: &str) -> Option<Range<usize>> {
+        let entry = array(self.document()?, "boot_targets")?.get(entry)?;
+        let DeValue::Table(table) = entry.get_ref() else {
+            return None;
+        };
+        get(table, field).map(Spanned::span)
+    }
+
+    /// The span of element `index` of the top-level array `key`.
+    pub(super) fn element(&self, key: &str, index: usize) -> Option<Range<usize>> {
+        array(self.document()?, key)?.get(index).map(Spanned::span)
+    }
+}
+
+fn get<'t, 'i>(table: &'t DeTable<'i>, key: &str) -> Option<&'t Spanned<DeValue<'i>>> {
+    table
+        .iter()
+        .find(|(k, _)| k.get_ref() == key)
+        .map(|(_, v)| v)
+}
+
+fn array<'t, 'i>(table: &'t DeTable<'i>, key: &str) -> Option<&'t [Spanned<DeValue<'i>>]> {
+    match get(table, key)?.get_ref() {
+        DeValue::Array(items) => Some(items),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A machine with one GPT partition holding a single kernel, and two
+    /// partitions named `root`.
+    struct Disk;
+
+    impl Environment for Disk {
+        fn has_partition(&self, location: &PartitionReference) -> bool {
+            self.count_partitions(location) > 0
+        }
+
+        fn count_partitions(&self, location: &PartitionReference) -> usize {
+            match location {
+                PartitionReference::Boot => 1,
+                PartitionReference::Label(label) if label == "root" => 2,
+                _ => 0,
+            }
+        }
+
+        fn has_file(&self, path: &PathReference) -> bool {
+            path.path == "\\vmlinuz"
+        }
+    }
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        let config = Config::parse(source).unwrap();
+        validate(&config, source, Some(&Disk))
+    }
+
+    #[test]
+    fn reports_parse_errors_with_position() {
+        let source = "timeout = 5\n[[boot_targets]]\ntype = \"generic\"\nlabel = \n";
+        let Err(crate::config::ConfigError::Parse(diagnostic)) = Config::parse(source) else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(diagnostic.position, Some((4, 9)));
+    }
+
+    #[test]
+    fn reports_entry_and_field() {
+        let source = r#"
+[[boot_targets]]
+type = "generic"
+label = "Arch"
+executable = "\\vmlinuz"
+
+[[boot_targets]]
+type = "linux"
+label = "Arch"
+kernel = "boot():\\vmlinuz"
+initrd = ["boot():\\initrd.img", "guid(550e8400-e29b-41d4-a716-446655440000):\\ucode.img"]
+cmdline = "quiet \U0001F600"
+"#;
+        let found: Vec<_> = check(source)
+            .iter()
+            .map(|d| (d.severity, d.entry, d.field.clone(), d.position))
+            .collect();
+        let field = |f: &str| Some(String::from(f));
+        assert_eq!(
+            found,
+            [
+                (Severity::Error, Some(0), field("executable"), Some((5, 14))),
+                (Severity::Warning, Some(1), field("label"), Some((9, 9))),
+                (Severity::Error, Some(1), field("initrd"), Some((11, 11))),
+                (Severity::Error, Some(1), field("initrd"), Some((11, 34))),
+                (Severity::Error, Some(1), field("cmdline"), Some((12, 11))),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_ambiguous_labels() {
+        let source = "[[boot_targets]]\ntype = \"generic\"\nlabel = \"Arch\"\n\
+                      executable = \"label(root):\\\\vmlinuz\"\n";
+        let messages: Vec<_> = check(source).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            ["2 partitions match label(root), rename all but one or use guid(...) instead"]
+        );
+    }
+
+    #[test]
+    fn checks_any_paths_by_file() {
+        let entry = |path: &str| {
+            format!(
+                "[[boot_targets]]\ntype = \"generic\"\nlabel = \"Arch\"\nexecutable = \"{path}\"\n"
+            )
+        };
+        assert_eq!(check(&entry("any():\\\\vmlinuz")), []);
+        let messages: Vec<_> = check(&entry("any():\\\\missing"))
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(messages, ["any():\\missing does not exist"]);
+    }
+
+    #[test]
+    fn reports_machines_without_criteria() {
+        let source =
+            "[[machine]]\nmanufacturer = \"QEMU\"\n\n[[machine]]\nname = \"Any\"\ntimeout = 1\n";
+        let found: Vec<_> = check(source)
+            .iter()
+            .map(|d| (d.severity, d.subject(), d.position))
+            .collect();
+        assert_eq!(
+            found,
+            [(
+                Severity::Warning,
+                Some(String::from("machine[1]")),
+                Some((4, 1))
+            )]
+        );
+    }
+
+    #[test]
+    fn accepts_valid_config() {
+        let source = "[[boot_targets]]\ntype = \"generic\"\nlabel = \"Arch\"\nexecutable = \"boot():\\\\vmlinuz\"\n";
+        assert_eq!(check(source), []);
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        let source = include_str!("../../plex.toml.example");
+        let config = Config::parse(source).unwrap();
+        assert_eq!(validate(&config, source, None), []);
+    }
+}
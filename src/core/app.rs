@@ -1,8 +1,9 @@
-use crate::AppError;
 use crate::core::bootables::DisplayOptions;
-use crate::core::display::GopDisplay;
+use crate::core::display::Display;
+use crate::core::input::InputSource;
 use crate::path::DiskManager;
-use uefi::proto::console::text::Input;
+use crate::AppError;
+use alloc::string::String;
 
 /// Outcome for a blocking app run.
 #[must_use]
@@ -21,13 +22,16 @@ pub enum AppResult {
 /// Borrowed UI and system resources for a running app.
 pub struct AppCtx<'a> {
     /// Display buffer for drawing. Caller retains ownership.
-    pub display: &'a mut GopDisplay<'a>,
+    pub display: &'a mut Display<'a>,
     /// Input source for key events. Caller retains ownership.
-    pub input: &'a mut Input,
+    pub input: &'a mut dyn InputSource,
     /// Disk access helpers scoped to the caller's lifetime.
     pub disk_manager: &'a DiskManager,
     /// Image handle for UEFI service calls.
     pub handle: uefi::Handle,
+    /// Whether booted entries are recorded as the last booted entry, only
+    /// needed for `default = "@saved"`.
+    pub remember_last_booted: bool,
 }
 
 /// Blocking app entry point.
@@ -41,4 +45,51 @@ pub trait App {
 pub trait DisplayEntry {
     /// Returns the options used to display this entry on the screen.
     fn display_options(&self) -> DisplayOptions;
+
+    /// Returns an identifier for this entry that is stable across reboots,
+    /// used to remember the last booted entry. Defaults to the label.
+    fn id(&self) -> String {
+        self.display_options().label
+    }
+
+    /// Returns the command line passed to this entry, or `None` if it has
+    /// none that can be edited.
+    fn cmdline(&self) -> Option<String> {
+        None
+    }
+
+    /// Replaces the command line for this entry until the next reboot.
+    ///
+    /// # Errors
+    /// Returns an error if the command line cannot be passed to the entry.
+    fn set_cmdline(&mut self, _cmdline: &str) -> Result<(), AppError> {
+        Err(AppError::NotImplemented("editing this entry"))
+    }
+
+    /// Arranges for this entry to be booted once on the next start, without
+    /// changing the default. The caller then resets the machine.
+    ///
+    /// # Errors
+    /// Returns an error if the entry cannot be booted this way or the
+    /// firmware refuses to store it.
+    fn boot_next(&self) -> Result<(), AppError> {
+        Err(AppError::NotImplemented("rebooting into this entry"))
+    }
+
+    /// The entries of a group, which the boot menu opens as a nested menu
+    /// instead of running the entry. `None` for entries that are not groups.
+    fn entries(&self) -> Option<&[Self]>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Mutable access to the entries of a group, see [`Self::entries`].
+    fn entries_mut(&mut self) -> Option<&mut [Self]>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
@@ -5,37 +5,140 @@
 
 use crate::core::app::AppResult;
 use crate::core::app::{App, AppCtx, DisplayEntry};
+use crate::core::initrd::{append_initrd, InitrdMedia};
+use crate::core::nvram;
 use crate::error::AppError;
-use crate::path::{DiskManager, PathReference};
-use alloc::borrow::ToOwned as _;
+use crate::path::{DiskManager, PathRefParseError, PathReference};
+use alloc::boxed::Box;
+use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use uefi::boot::LoadImageSource;
 use uefi::cstr16;
-use uefi::proto::device_path::PoolDevicePath;
+use uefi::fs::PathBuf;
+use uefi::proto::device_path::DevicePath;
 use uefi::proto::loaded_image::LoadedImage;
 use uefi::proto::BootPolicy;
-use uefi::CString16;
+use uefi::runtime::{self, ResetType};
+use uefi::{CString16, Status};
 
 #[derive(Debug)]
 /// Represents any bootable target that can be executed by the bootloader.
 pub enum BootTarget {
     /// A generic EFI executable boot target.
     Generic(GenericBootTarget),
+    /// A Linux kernel booted via its EFI stub, with initrds served by Plex.
+    Linux(LinuxBootTarget),
+    /// A boot option from the firmware's own boot menu.
+    Firmware(FirmwareBootTarget),
+    /// Entries shown behind a single item that opens a nested menu, see
+    /// [`fold_groups`].
+    Group(GroupTarget),
+    /// Reboots into the firmware setup, only offered if the firmware
+    /// supports it, see [`nvram::boot_to_firmware_ui_supported`].
+    FirmwareSetup(ActionTarget),
+    /// Reboots the machine.
+    Reboot(ActionTarget),
+    /// Turns the machine off.
+    Shutdown(ActionTarget),
+}
+
+/// An image loaded and ready to be started, together with anything that has
+/// to stay alive until it returns.
+struct PreparedImage {
+    image: uefi::Handle,
+    _initrd: Option<InitrdMedia>,
 }
 
 /// note: this is a two-way implementation, to allow decisions in the
 /// future whether we want to model all targets as enum or use dyn dispatch.
 impl BootTarget {
-    fn boot(&self, handle: uefi::Handle, dm: &DiskManager) -> Result<(), AppError> {
+    /// The group the target is folded into, see [`fold_groups`].
+    #[must_use]
+    pub fn group(&self) -> Option<&str> {
+        match self {
+            Self::Generic(target) => target.group.as_deref(),
+            Self::Linux(target) => target.group.as_deref(),
+            Self::FirmwareSetup(target) | Self::Reboot(target) | Self::Shutdown(target) => {
+                target.group.as_deref()
+            }
+            Self::Firmware(_) | Self::Group(_) => None,
+        }
+    }
+
+    /// The device path of the image this target boots.
+    ///
+    /// # Errors
+    /// Returns an error if the configured path is not a valid `PathReference`
+    /// or its partition cannot be found.
+    pub fn device_path(&self, dm: &DiskManager) -> Result<Box<DevicePath>, AppError> {
+        let reference = match self {
+            Self::Generic(target) => target.executable()?,
+            Self::Linux(target) => PathReference::parse(&target.kernel)?,
+            Self::Firmware(target) => return Ok(target.device_path.to_boxed()),
+            Self::Group(_) => return Err(GroupTarget::NOT_BOOTABLE),
+            Self::FirmwareSetup(_) | Self::Reboot(_) | Self::Shutdown(_) => {
+                return Err(ActionTarget::NO_IMAGE);
+            }
+        };
+        Ok(dm.resolve_path(&reference)?.to_boxed())
+    }
+
+    fn load(&self, handle: uefi::Handle, dm: &DiskManager) -> Result<PreparedImage, AppError> {
         match self {
-            Self::Generic(target) => target.boot(handle, dm),
+            Self::Generic(target) => Ok(PreparedImage {
+                image: target.load(handle, dm)?,
+                _initrd: None,
+            }),
+            Self::Linux(target) => target.load(handle, dm),
+            Self::Firmware(target) => Ok(PreparedImage {
+                image: target.load(handle)?,
+                _initrd: None,
+            }),
+            Self::Group(_) => Err(GroupTarget::NOT_BOOTABLE),
+            Self::FirmwareSetup(_) | Self::Reboot(_) | Self::Shutdown(_) => {
+                Err(ActionTarget::NO_IMAGE)
+            }
         }
     }
+
+    /// Loads and starts the target. With `remember`, it is recorded as the
+    /// last booted entry for `default = "@saved"`, which a one-shot boot must
+    /// not change. Returns once the started image exits. Actions reset the
+    /// machine instead and do not return.
+    ///
+    /// # Errors
+    /// Returns an error if the image cannot be loaded or fails to start.
+    pub fn boot(
+        &self,
+        handle: uefi::Handle,
+        dm: &DiskManager,
+        remember: bool,
+    ) -> Result<(), AppError> {
+        match self {
+            Self::FirmwareSetup(_) => {
+                nvram::request_firmware_ui()?;
+                runtime::reset(ResetType::COLD, Status::SUCCESS, None)
+            }
+            Self::Reboot(_) => runtime::reset(ResetType::COLD, Status::SUCCESS, None),
+            Self::Shutdown(_) => runtime::reset(ResetType::SHUTDOWN, Status::SUCCESS, None),
+            _ => {}
+        }
+        let prepared = self.load(handle, dm)?;
+
+        // The image may never return control, so remember the entry before
+        // starting it. Failing to do so should not prevent booting.
+        if remember && let Err(e) = nvram::store_last_booted(&self.id()) {
+            log::warn!("failed to remember last booted entry: {e}");
+        }
+
+        Ok(uefi::boot::start_image(prepared.image)?)
+    }
 }
 
 impl App for BootTarget {
     fn run(&mut self, ctx: &mut AppCtx) -> AppResult {
-        match self.boot(ctx.handle, ctx.disk_manager) {
+        match self.boot(ctx.handle, ctx.disk_manager, ctx.remember_last_booted) {
             Ok(()) => AppResult::Booted,
             Err(e) => AppResult::Error(e),
         }
@@ -46,6 +149,79 @@ impl DisplayEntry for BootTarget {
     fn display_options(&self) -> DisplayOptions {
         match self {
             Self::Generic(target) => target.display_options(),
+            Self::Linux(target) => DisplayOptions {
+                label: target.label.clone(),
+            },
+            Self::Firmware(target) => DisplayOptions {
+                label: target.label.clone(),
+            },
+            Self::Group(target) => DisplayOptions {
+                label: target.label.clone(),
+            },
+            Self::FirmwareSetup(target) | Self::Reboot(target) | Self::Shutdown(target) => {
+                DisplayOptions {
+                    label: target.label.clone(),
+                }
+            }
+        }
+    }
+
+    fn id(&self) -> String {
+        match self {
+            Self::Generic(target) => target.id.clone(),
+            Self::Linux(target) => target.id.clone(),
+            Self::Firmware(target) => format!("Boot{:04X}", target.number),
+            Self::Group(target) => target.label.clone(),
+            Self::FirmwareSetup(target) | Self::Reboot(target) | Self::Shutdown(target) => {
+                target.id.clone()
+            }
+        }
+    }
+
+    fn cmdline(&self) -> Option<String> {
+        match self {
+            Self::Generic(target) => Some(target.options.to_string()),
+            Self::Linux(target) => Some(target.cmdline.to_string()),
+            _ => None,
+        }
+    }
+
+    fn set_cmdline(&mut self, cmdline: &str) -> Result<(), AppError> {
+        let cmdline = CString16::try_from(cmdline)?;
+        match self {
+            Self::Generic(target) => target.options = cmdline,
+            Self::Linux(target) => target.cmdline = cmdline,
+            Self::Firmware(_) => return Err(AppError::NotImplemented("editing firmware entries")),
+            Self::Group(_) => return Err(AppError::NotImplemented("editing groups")),
+            Self::FirmwareSetup(_) | Self::Reboot(_) | Self::Shutdown(_) => {
+                return Err(AppError::NotImplemented("editing actions"));
+            }
+        }
+        Ok(())
+    }
+
+    fn boot_next(&self) -> Result<(), AppError> {
+        match self {
+            Self::Generic(_) | Self::Linux(_) => nvram::store_one_shot(&self.id()),
+            Self::Firmware(target) => nvram::set_boot_next(target.number),
+            Self::Group(_) => Err(GroupTarget::NOT_BOOTABLE),
+            Self::FirmwareSetup(_) | Self::Reboot(_) | Self::Shutdown(_) => {
+                Err(AppError::NotImplemented("rebooting into actions"))
+            }
+        }
+    }
+
+    fn entries(&self) -> Option<&[Self]> {
+        match self {
+            Self::Group(target) => Some(&target.entries),
+            _ => None,
+        }
+    }
+
+    fn entries_mut(&mut self) -> Option<&mut [Self]> {
+        match self {
+            Self::Group(target) => Some(&mut target.entries),
+            _ => None,
         }
     }
 }
@@ -62,6 +238,8 @@ pub struct DisplayOptions {
 /// is a very common default. Windows is also easily chain-loaded.
 #[derive(Debug)]
 pub struct GenericBootTarget {
+    /// Stable identifier used to remember this entry across reboots.
+    id: String,
     /// Display label for the boot menu
     label: String,
     /// Path to executable. current limitation is that this path is relative
@@ -69,26 +247,52 @@ pub struct GenericBootTarget {
     executable: CString16,
     /// Command options to be passed to `LoadedImage::SetLoadOptions`.
     options: CString16,
+    /// Group the entry is folded into, see [`fold_groups`].
+    group: Option<String>,
 }
 
 impl GenericBootTarget {
     /// Creates a new `GenericBootTarget` from the provided label, executable path, and options.
+    ///
+    /// The label doubles as the entry's stable ID, see [`Self::with_id`].
+    ///
+    /// # Errors
+    /// Returns an error if the executable path or the options cannot be
+    /// passed to UEFI, which only supports UCS-2.
     pub fn new(
         label: impl AsRef<str>,
         executable: impl AsRef<str>,
         options: impl AsRef<str>,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, AppError> {
+        Ok(Self {
+            id: label.as_ref().to_string(),
             label: label.as_ref().to_string(),
-            executable: CString16::try_from(executable.as_ref())
-                .unwrap_or_else(|_| cstr16!("failed to parse").to_owned()),
-            options: CString16::try_from(options.as_ref())
-                .unwrap_or_else(|_| cstr16!("failed to parse").to_owned()),
-        }
+            executable: CString16::try_from(executable.as_ref())?,
+            options: CString16::try_from(options.as_ref())?,
+            group: None,
+        })
     }
 
-    fn boot(&self, handle: uefi::Handle, dm: &DiskManager) -> Result<(), AppError> {
-        let pathref = PathReference::parse(self.executable.to_string().as_str())?;
+    /// Overrides the stable ID used to remember this entry across reboots.
+    #[must_use]
+    pub fn with_id(mut self, id: impl AsRef<str>) -> Self {
+        self.id = id.as_ref().to_string();
+        self
+    }
+
+    /// Puts the entry into the group named `group`, see [`fold_groups`].
+    #[must_use]
+    pub fn with_group(mut self, group: impl AsRef<str>) -> Self {
+        self.group = Some(group.as_ref().to_string());
+        self
+    }
+
+    fn executable(&self) -> Result<PathReference, PathRefParseError> {
+        PathReference::parse(self.executable.to_string().as_str())
+    }
+
+    fn load(&self, handle: uefi::Handle, dm: &DiskManager) -> Result<uefi::Handle, AppError> {
+        let pathref = self.executable()?;
         let img_path = dm.resolve_path(&pathref)?;
 
         log::debug!(
@@ -102,18 +306,9 @@ impl GenericBootTarget {
         };
 
         let loaded_image_handle = uefi::boot::load_image(handle, src)?;
-        let mut loaded_img =
-            uefi::boot::open_protocol_exclusive::<LoadedImage>(loaded_image_handle)?;
-
-        unsafe {
-            loaded_img.set_load_options(
-                self.options.as_ptr().cast::<u8>(),
-                u32::try_from(self.options.num_bytes())
-                    .map_err(|_| AppError::Generic("load options length overflow"))?,
-            );
-        }
+        set_load_options(loaded_image_handle, self.options.as_bytes())?;
 
-        Ok(uefi::boot::start_image(loaded_image_handle)?)
+        Ok(loaded_image_handle)
     }
 
     fn display_options(&self) -> DisplayOptions {
@@ -123,10 +318,333 @@ impl GenericBootTarget {
     }
 }
 
-fn path_to_string(path: &PoolDevicePath) -> CString16 {
+/// A Linux kernel with an EFI stub, booted with initrds assembled by Plex.
+///
+/// Unlike passing `initrd=` in the options of a [`GenericBootTarget`], the
+/// initrds may live on any partition and are concatenated in order. They are
+/// handed to the kernel through the `LoadFile2` protocol, see
+/// [`crate::core::initrd`], which requires Linux 5.8 or newer.
+#[derive(Debug)]
+pub struct LinuxBootTarget {
+    /// Stable identifier used to remember this entry across reboots.
+    id: String,
+    /// Display label for the boot menu
+    label: String,
+    /// `PathReference` of the kernel image.
+    kernel: String,
+    /// `PathReference`s of the initrd images, in load order.
+    initrd: Vec<String>,
+    /// Kernel command line.
+    cmdline: CString16,
+    /// Group the entry is folded into, see [`fold_groups`].
+    group: Option<String>,
+}
+
+impl LinuxBootTarget {
+    /// Creates a new `LinuxBootTarget` from the provided label, kernel and
+    /// initrd paths, and command line.
+    ///
+    /// The label doubles as the entry's stable ID, see [`Self::with_id`].
+    ///
+    /// # Errors
+    /// Returns an error if the command line cannot be passed to UEFI, which
+    /// only supports UCS-2.
+    pub fn new(
+        label: impl AsRef<str>,
+        kernel: impl AsRef<str>,
+        initrd: Vec<String>,
+        cmdline: impl AsRef<str>,
+    ) -> Result<Self, AppError> {
+        Ok(Self {
+            id: label.as_ref().to_string(),
+            label: label.as_ref().to_string(),
+            kernel: kernel.as_ref().to_string(),
+            initrd,
+            cmdline: CString16::try_from(cmdline.as_ref())?,
+            group: None,
+        })
+    }
+
+    /// Overrides the stable ID used to remember this entry across reboots.
+    #[must_use]
+    pub fn with_id(mut self, id: impl AsRef<str>) -> Self {
+        self.id = id.as_ref().to_string();
+        self
+    }
+
+    /// Puts the entry into the group named `group`, see [`fold_groups`].
+    #[must_use]
+    pub fn with_group(mut self, group: impl AsRef<str>) -> Self {
+        self.group = Some(group.as_ref().to_string());
+        self
+    }
+
+    fn load(&self, handle: uefi::Handle, dm: &DiskManager) -> Result<PreparedImage, AppError> {
+        // Read the initrds first, so a missing file is reported before the
+        // kernel is loaded.
+        let initrd = if self.initrd.is_empty() {
+            None
+        } else {
+            let mut data = Vec::new();
+            for path in &self.initrd {
+                let file = read_file(dm, &PathReference::parse(path)?)?;
+                log::debug!("Read initrd {path} ({} bytes)", file.len());
+                append_initrd(&mut data, &file);
+            }
+            Some(data)
+        };
+
+        let kernel_path = dm.resolve_path(&PathReference::parse(&self.kernel)?)?;
+        log::debug!(
+            "Loading kernel from resolved path: {}",
+            path_to_string(&kernel_path)
+        );
+
+        let src = LoadImageSource::FromDevicePath {
+            device_path: &kernel_path,
+            boot_policy: BootPolicy::default(),
+        };
+        // Installed before the kernel is loaded, so a failure leaves no image
+        // behind. Dropping the media uninstalls it again.
+        let initrd = initrd.map(InitrdMedia::install).transpose()?;
+        let image = uefi::boot::load_image(handle, src)?;
+        if let Err(e) = set_load_options(image, self.cmdline.as_bytes()) {
+            let _ = uefi::boot::unload_image(image);
+            return Err(e);
+        }
+
+        Ok(PreparedImage {
+            image,
+            _initrd: initrd,
+        })
+    }
+}
+
+/// An entry of the firmware boot menu, a `Boot####` load option.
+///
+/// It is started directly from the option's device path, see
+/// [`crate::core::resolver::firmware`].
+#[derive(Debug)]
+pub struct FirmwareBootTarget {
+    /// The `####` of the `Boot####` variable.
+    number: u16,
+    /// Description of the load option.
+    label: String,
+    /// Device path of the image to boot.
+    device_path: Box<DevicePath>,
+    /// Optional data of the load option, passed to the image as its load
+    /// options like the firmware boot manager does.
+    optional_data: Vec<u8>,
+}
+
+impl FirmwareBootTarget {
+    /// Creates a target for the load option `Boot####` with the given number.
+    #[must_use]
+    pub const fn new(number: u16, label: String, device_path: Box<DevicePath>) -> Self {
+        Self {
+            number,
+            label,
+            device_path,
+            optional_data: Vec::new(),
+        }
+    }
+
+    /// Passes `data` to the image as its load options, see
+    /// [`crate::core::resolver::firmware::LoadOption::optional_data`].
+    #[must_use]
+    pub fn with_optional_data(mut self, data: Vec<u8>) -> Self {
+        self.optional_data = data;
+        self
+    }
+
+    fn load(&self, handle: uefi::Handle) -> Result<uefi::Handle, AppError> {
+        log::debug!(
+            "Loading Boot{:04X} from {}",
+            self.number,
+            path_to_string(&self.device_path)
+        );
+
+        // Options may point at a whole device rather than a file, boot
+        // selection lets the firmware look for the removable media path.
+        let src = LoadImageSource::FromDevicePath {
+            device_path: &self.device_path,
+            boot_policy: BootPolicy::BootSelection,
+        };
+        let image = uefi::boot::load_image(handle, src)?;
+        if !self.optional_data.is_empty() {
+            set_load_options(image, &self.optional_data)?;
+        }
+        Ok(image)
+    }
+}
+
+/// A built-in action offered as a menu entry, such as rebooting. Which one
+/// is given by the [`BootTarget`] variant holding it.
+#[derive(Debug)]
+pub struct ActionTarget {
+    /// Stable identifier used to remember this entry across reboots.
+    id: String,
+    /// Display label for the boot menu.
+    label: String,
+    /// Group the entry is folded into, see [`fold_groups`].
+    group: Option<String>,
+}
+
+impl ActionTarget {
+    const NO_IMAGE: AppError = AppError::Generic("an action has no image to load");
+
+    /// Creates an action labelled `label`, which doubles as its stable ID.
+    pub fn new(label: impl AsRef<str>) -> Self {
+        Self {
+            id: label.as_ref().to_string(),
+            label: label.as_ref().to_string(),
+            group: None,
+        }
+    }
+
+    /// Overrides the stable ID used to remember this entry across reboots.
+    #[must_use]
+    pub fn with_id(mut self, id: impl AsRef<str>) -> Self {
+        self.id = id.as_ref().to_string();
+        self
+    }
+
+    /// Puts the entry into the group named `group`, see [`fold_groups`].
+    #[must_use]
+    pub fn with_group(mut self, group: impl AsRef<str>) -> Self {
+        self.group = Some(group.as_ref().to_string());
+        self
+    }
+}
+
+/// Entries shown behind a single item of the boot menu, e.g. every kernel
+/// of one distribution. Selecting it opens a nested menu of the entries.
+#[derive(Debug)]
+pub struct GroupTarget {
+    /// Display label for the boot menu, the name of the group.
+    label: String,
+    /// The grouped entries, in menu order.
+    entries: Vec<BootTarget>,
+}
+
+impl GroupTarget {
+    const NOT_BOOTABLE: AppError = AppError::Generic("a group cannot be booted, open it instead");
+
+    /// Creates a group named `label` holding `entries`.
+    #[must_use]
+    pub const fn new(label: String, entries: Vec<BootTarget>) -> Self {
+        Self { label, entries }
+    }
+}
+
+/// Folds targets that name the same group into a [`GroupTarget`], placed
+/// where the first of them was. A group with a single entry is left as that
+/// entry.
+#[must_use]
+pub fn fold_groups(targets: Vec<BootTarget>) -> Vec<BootTarget> {
+    let mut folded: Vec<BootTarget> = Vec::with_capacity(targets.len());
+    // Groups found so far, with their position in `folded`.
+    let mut groups: Vec<(String, usize)> = Vec::new();
+
+    for target in targets {
+        let Some(name) = target.group().map(ToString::to_string) else {
+            folded.push(target);
+            continue;
+        };
+        let Some(&(_, idx)) = groups.iter().find(|(group, _)| *group == name) else {
+            groups.push((name, folded.len()));
+            folded.push(target);
+            continue;
+        };
+        // The second entry of a group turns the first one into the group.
+        if !matches!(folded[idx], BootTarget::Group(_)) {
+            let group = BootTarget::Group(GroupTarget::new(name, Vec::new()));
+            let first = core::mem::replace(&mut folded[idx], group);
+            if let BootTarget::Group(group) = &mut folded[idx] {
+                group.entries.push(first);
+            }
+        }
+        if let BootTarget::Group(group) = &mut folded[idx] {
+            group.entries.push(target);
+        }
+    }
+
+    folded
+}
+
+/// Reads a whole file from the partition a `PathReference` points to.
+fn read_file(dm: &DiskManager, reference: &PathReference) -> Result<Vec<u8>, AppError> {
+    let mut fs = dm.locate(reference)?.file_system()?;
+    Ok(fs.read(PathBuf::from(CString16::try_from(reference.path.as_str())?))?)
+}
+
+/// Passes `options` to the loaded `image`, usually a NUL terminated UCS-2
+/// command line. They must stay alive until the image is started.
+fn set_load_options(image: uefi::Handle, options: &[u8]) -> Result<(), AppError> {
+    let mut loaded_img = uefi::boot::open_protocol_exclusive::<LoadedImage>(image)?;
+
+    unsafe {
+        loaded_img.set_load_options(
+            options.as_ptr(),
+            u32::try_from(options.len())
+                .map_err(|_| AppError::Generic("load options length overflow"))?,
+        );
+    }
+
+    Ok(())
+}
+
+fn path_to_string(path: &DevicePath) -> CString16 {
     path.to_string(
         uefi::proto::device_path::text::DisplayOnly(true),
         uefi::proto::device_path::text::AllowShortcuts(true),
     )
     .unwrap_or_else(|_| cstr16!("failed to parse.").into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(label: &str, group: Option<&str>) -> BootTarget {
+        let target = GenericBootTarget::new(label, "boot():\\vmlinuz", "").unwrap();
+        BootTarget::Generic(match group {
+            Some(group) => target.with_group(group),
+            None => target,
+        })
+    }
+
+    /// The labels of `targets`, with the entries of groups in brackets.
+    fn labels(targets: &[BootTarget]) -> Vec<String> {
+        targets
+            .iter()
+            .map(|target| {
+                let label = target.display_options().label;
+                target.entries().map_or_else(
+                    || label.clone(),
+                    |entries| format!("{label} {:?}", labels(entries)),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn folds_groups() {
+        let folded = fold_groups(alloc::vec![
+            entry("Windows", None),
+            entry("Arch (6.9.7)", Some("Arch")),
+            entry("Fedora (6.8)", Some("Fedora")),
+            entry("Shell", None),
+            entry("Arch (6.9.6)", Some("Arch")),
+        ]);
+        assert_eq!(
+            labels(&folded),
+            [
+                "Windows",
+                "Arch [\"Arch (6.9.7)\", \"Arch (6.9.6)\"]",
+                "Fedora (6.8)",
+                "Shell",
+            ]
+        );
+    }
+}
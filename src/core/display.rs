@@ -1,12 +1,129 @@
-//! Module implementing `embedded_graphics_core::DrawTarget`
-//! for the Rust UEFI crate. It is actually just a buffer,
+//! Display backends the UI draws on.
+//!
+//! Graphical themes draw on a [`Framebuffer`] through [`Canvas`], which
+//! implements `embedded_graphics_core::DrawTarget`. [`GopDisplay`] is the
+//! framebuffer for the Rust UEFI crate. It is actually just a buffer,
 //! and the push to uefi is done via blit during `flush()`.
+//! [`MemoryFramebuffer`] keeps the pixels in memory, so themes can be
+//! rendered and checked in host tests.
+//!
+//! [`TextDisplay`] is a character grid over a [`TextConsole`] such as
+//! `SimpleTextOutput`, for machines without GOP and for serial consoles.
+use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::convert::Infallible;
 use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+use serde::Deserialize;
 use uefi::proto::console::gop::{BltOp, BltPixel, BltRegion, GraphicsOutput};
+use uefi::proto::console::text::{Color, Output};
+use uefi::{CStr16, CString16};
 
-/// An `embedded-graphics` draw target using the UEFI Graphics Output Protocol (Gop).
+use crate::error::AppError;
+
+/// Which kind of display the UI uses, set by `console` in `plex.toml`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsoleMode {
+    /// Graphics if the firmware provides GOP, text otherwise.
+    #[default]
+    #[serde(alias = "graphics")]
+    Auto,
+    /// Always use the text console, e.g. for serial lines.
+    Text,
+}
+
+/// The display the UI is drawn on.
+pub enum Display<'a> {
+    /// A graphical framebuffer, drawn by the configured theme.
+    Graphics(&'a mut dyn Framebuffer),
+    /// A text console, drawn with box-drawing characters.
+    Text(TextDisplay<'a>),
+}
+
+/// A buffer of RGB pixels that is presented with `flush()`.
+pub trait Framebuffer {
+    /// Width and height in pixels.
+    fn resolution(&self) -> (usize, usize);
+
+    /// Set a single pixel, which is always within the resolution.
+    fn set_pixel(&mut self, x: usize, y: usize, color: Rgb888);
+
+    /// Set all pixels to `color`.
+    fn fill(&mut self, color: Rgb888);
+
+    /// Present the buffer.
+    ///
+    /// # Errors
+    /// Returns any error from the underlying device.
+    fn flush(&mut self) -> Result<(), AppError>;
+}
+
+/// Adapts a [`Framebuffer`] to `embedded-graphics`, so themes can draw on
+/// any of them.
+pub struct Canvas<'a> {
+    framebuffer: &'a mut dyn Framebuffer,
+}
+
+impl<'a> Canvas<'a> {
+    /// Wraps a framebuffer for drawing.
+    pub fn new(framebuffer: &'a mut dyn Framebuffer) -> Self {
+        Self { framebuffer }
+    }
+
+    /// Fill the whole framebuffer with a single color.
+    pub fn clear(&mut self, color: Rgb888) {
+        self.framebuffer.fill(color);
+    }
+
+    /// Present the drawn image.
+    ///
+    /// # Errors
+    /// Returns any error from the underlying framebuffer.
+    pub fn flush(&mut self) -> Result<(), AppError> {
+        self.framebuffer.flush()
+    }
+}
+
+impl DrawTarget for Canvas<'_> {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (width, height) = self.framebuffer.resolution();
+        for Pixel(coord, color) in pixels {
+            // Bounds check - discard out of bounds pixels per DrawTarget requirements
+            let (Ok(x), Ok(y)) = (usize::try_from(coord.x), usize::try_from(coord.y)) else {
+                continue;
+            };
+
+            if x < width && y < height {
+                self.framebuffer.set_pixel(x, y, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.framebuffer.fill(color);
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Canvas<'_> {
+    fn size(&self) -> Size {
+        let (width, height) = self.framebuffer.resolution();
+        Size::new(
+            u32::try_from(width).unwrap_or(u32::MAX),
+            u32::try_from(height).unwrap_or(u32::MAX),
+        )
+    }
+}
+
+/// A [`Framebuffer`] using the UEFI Graphics Output Protocol (Gop).
 ///
 /// This maintains an internal buffer where drawing operations happen. To display
 /// the drawn image, you must call `flush()` which blits the entire buffer to the screen.
@@ -28,62 +145,325 @@ impl<'a> GopDisplay<'a> {
             gop,
         }
     }
+}
+
+impl Framebuffer for GopDisplay<'_> {
+    fn resolution(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, color: Rgb888) {
+        let pixel = &mut self.buffer[y * self.width + x];
+        pixel.red = color.r();
+        pixel.green = color.g();
+        pixel.blue = color.b();
+    }
+
+    fn fill(&mut self, color: Rgb888) {
+        let pixel = BltPixel::new(color.r(), color.g(), color.b());
+        self.buffer.fill(pixel);
+    }
 
     /// Blit the entire buffer to the framebuffer.
-    ///
-    /// # Errors
-    /// Returns any error from the underlying GOP blit call.
-    pub fn flush(&mut self) -> Result<(), uefi::Error> {
-        self.gop.blt(BltOp::BufferToVideo {
+    fn flush(&mut self) -> Result<(), AppError> {
+        Ok(self.gop.blt(BltOp::BufferToVideo {
             buffer: &self.buffer,
             src: BltRegion::Full,
             dest: (0, 0),
             dims: (self.width, self.height),
-        })
+        })?)
     }
+}
 
-    /// Clear the buffer to a specific color.
-    pub fn clear(&mut self, color: Rgb888) {
-        let pixel = BltPixel::new(color.r(), color.g(), color.b());
-        self.buffer.fill(pixel);
+/// A framebuffer kept in memory, e.g. to render themes in host tests.
+///
+/// `flush()` only counts how often the image was presented.
+pub struct MemoryFramebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb888>,
+    flushes: usize,
+}
+
+impl MemoryFramebuffer {
+    /// Create a black framebuffer of the given resolution.
+    #[must_use]
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Rgb888::BLACK; width * height],
+            flushes: 0,
+        }
+    }
+
+    /// All pixels, row by row.
+    #[must_use]
+    pub fn pixels(&self) -> &[Rgb888] {
+        &self.pixels
+    }
+
+    /// How often `flush()` was called.
+    #[must_use]
+    pub const fn flushes(&self) -> usize {
+        self.flushes
     }
 }
 
-impl DrawTarget for GopDisplay<'_> {
-    type Color = Rgb888;
-    type Error = core::convert::Infallible;
+impl Framebuffer for MemoryFramebuffer {
+    fn resolution(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
 
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for Pixel(coord, color) in pixels {
-            // Bounds check - discard out of bounds pixels per DrawTarget requirements
-            let (x, y) = match (coord.x, coord.y) {
-                (x, y) if x >= 0 && y >= 0 => (
-                    usize::try_from(x).unwrap_or(usize::MAX),
-                    usize::try_from(y).unwrap_or(usize::MAX),
-                ),
-                _ => continue,
-            };
+    fn set_pixel(&mut self, x: usize, y: usize, color: Rgb888) {
+        self.pixels[y * self.width + x] = color;
+    }
 
-            if x < self.width && y < self.height {
-                let index = y * self.width + x;
-                let pixel = &mut self.buffer[index];
-                pixel.red = color.r();
-                pixel.green = color.g();
-                pixel.blue = color.b();
-            }
+    fn fill(&mut self, color: Rgb888) {
+        self.pixels.fill(color);
+    }
+
+    fn flush(&mut self) -> Result<(), AppError> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+/// Semantic colors of text drawn on a [`TextDisplay`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextStyle {
+    /// Regular text.
+    #[default]
+    Normal,
+    /// Highlighted text, e.g. the selected menu entry.
+    Selected,
+    /// Less important text, e.g. key hints.
+    Dim,
+    /// Titles and frames.
+    Accent,
+    /// Error messages.
+    Error,
+}
+
+impl TextStyle {
+    /// Foreground and background color on the UEFI console.
+    const fn colors(self) -> (Color, Color) {
+        match self {
+            Self::Normal => (Color::LightGray, Color::Black),
+            Self::Selected => (Color::Black, Color::LightGray),
+            Self::Dim => (Color::DarkGray, Color::Black),
+            Self::Accent => (Color::LightCyan, Color::Black),
+            Self::Error => (Color::LightRed, Color::Black),
         }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    c: char,
+    style: TextStyle,
+}
+
+impl Cell {
+    const BLANK: Self = Self {
+        c: ' ',
+        style: TextStyle::Normal,
+    };
+}
+
+/// A character console, such as the UEFI `SimpleTextOutput` protocol.
+pub trait TextConsole {
+    /// Number of columns and rows.
+    ///
+    /// # Errors
+    /// Returns an error if the console has no valid text mode.
+    fn size(&self) -> Result<(usize, usize), AppError>;
+
+    /// Clear the screen and hide the cursor.
+    ///
+    /// # Errors
+    /// Returns any error from the underlying console.
+    fn reset(&mut self) -> Result<(), AppError>;
+
+    /// Write `text` at the given position.
+    ///
+    /// # Errors
+    /// Returns any error from the underlying console.
+    fn write_at(
+        &mut self,
+        column: usize,
+        row: usize,
+        text: &CStr16,
+        style: TextStyle,
+    ) -> Result<(), AppError>;
+}
+
+impl TextConsole for Output {
+    fn size(&self) -> Result<(usize, usize), AppError> {
+        let mode = self
+            .current_mode()?
+            .ok_or(AppError::Generic("text console has no mode set"))?;
+        Ok((mode.columns(), mode.rows()))
+    }
+
+    fn reset(&mut self) -> Result<(), AppError> {
+        self.set_color(Color::LightGray, Color::Black)?;
+        self.clear()?;
+        // Not all consoles can hide the cursor, it is only cosmetic.
+        let _ = self.enable_cursor(false);
         Ok(())
     }
+
+    fn write_at(
+        &mut self,
+        column: usize,
+        row: usize,
+        text: &CStr16,
+        style: TextStyle,
+    ) -> Result<(), AppError> {
+        let (fg, bg) = style.colors();
+        self.set_cursor_position(column, row)?;
+        self.set_color(fg, bg)?;
+        Ok(self.output_string_lossy(text)?)
+    }
 }
 
-impl OriginDimensions for GopDisplay<'_> {
-    fn size(&self) -> Size {
-        Size::new(
-            u32::try_from(self.width).unwrap_or(u32::MAX),
-            u32::try_from(self.height).unwrap_or(u32::MAX),
-        )
+/// A character grid drawn to a text console.
+///
+/// Like [`GopDisplay`], drawing happens on an internal buffer and
+/// `flush()` writes it out. Only rows that changed since the last flush are
+/// written, which keeps redraws cheap on slow serial lines.
+pub struct TextDisplay<'a> {
+    columns: usize,
+    rows: usize,
+    cells: Vec<Cell>,
+    /// What is currently on the screen, `None` before the first flush.
+    shown: Option<Vec<Cell>>,
+    console: &'a mut dyn TextConsole,
+}
+
+impl<'a> TextDisplay<'a> {
+    /// Create a new `TextDisplay` matching the size of the console.
+    ///
+    /// # Errors
+    /// Returns an error if the console has no valid text mode.
+    pub fn new(console: &'a mut dyn TextConsole) -> Result<Self, AppError> {
+        let (columns, rows) = console.size()?;
+        // Writing the bottom right cell scrolls some consoles, so keep the
+        // last column free.
+        let columns = columns.saturating_sub(1);
+        Ok(Self {
+            columns,
+            rows,
+            cells: vec![Cell::BLANK; columns * rows],
+            shown: None,
+            console,
+        })
+    }
+
+    /// The usable size of the console as `(columns, rows)`.
+    #[must_use]
+    pub const fn size(&self) -> (usize, usize) {
+        (self.columns, self.rows)
+    }
+
+    /// Clear the buffer.
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::BLANK);
+    }
+
+    /// Write `text` starting at the given cell, clipped to the row.
+    pub fn put_str(&mut self, column: usize, row: usize, text: &str, style: TextStyle) {
+        if row >= self.rows {
+            return;
+        }
+        let start = row * self.columns;
+        for (col, c) in (column..self.columns).zip(text.chars()) {
+            // The console only understands UCS-2.
+            let c = if uefi::Char16::try_from(c).is_ok() {
+                c
+            } else {
+                '?'
+            };
+            self.cells[start + col] = Cell { c, style };
+        }
+    }
+
+    /// Fill a rectangle with `c`, clipped to the screen.
+    pub fn fill(
+        &mut self,
+        column: usize,
+        row: usize,
+        width: usize,
+        height: usize,
+        c: char,
+        style: TextStyle,
+    ) {
+        for y in row..(row + height).min(self.rows) {
+            for x in column..(column + width).min(self.columns) {
+                self.cells[y * self.columns + x] = Cell { c, style };
+            }
+        }
+    }
+
+    /// Draw a frame with box-drawing characters and clear its inside.
+    pub fn draw_box(
+        &mut self,
+        column: usize,
+        row: usize,
+        width: usize,
+        height: usize,
+        style: TextStyle,
+    ) {
+        if width < 2 || height < 2 {
+            return;
+        }
+        let right = column + width - 1;
+        let bottom = row + height - 1;
+
+        self.fill(column, row, width, height, ' ', TextStyle::Normal);
+        self.fill(column + 1, row, width - 2, 1, '\u{2500}', style);
+        self.fill(column + 1, bottom, width - 2, 1, '\u{2500}', style);
+        self.fill(column, row + 1, 1, height - 2, '\u{2502}', style);
+        self.fill(right, row + 1, 1, height - 2, '\u{2502}', style);
+        self.fill(column, row, 1, 1, '\u{250c}', style);
+        self.fill(right, row, 1, 1, '\u{2510}', style);
+        self.fill(column, bottom, 1, 1, '\u{2514}', style);
+        self.fill(right, bottom, 1, 1, '\u{2518}', style);
+    }
+
+    /// Write the changed rows of the buffer to the console.
+    ///
+    /// # Errors
+    /// Returns any error from the underlying console.
+    pub fn flush(&mut self) -> Result<(), AppError> {
+        if self.shown.is_none() {
+            self.console.reset()?;
+        }
+
+        for row in 0..self.rows {
+            let range = row * self.columns..(row + 1) * self.columns;
+            let line = &self.cells[range.clone()];
+            if self
+                .shown
+                .as_ref()
+                .is_some_and(|shown| shown[range.clone()] == *line)
+            {
+                continue;
+            }
+
+            // Write runs of equally styled cells with a single call each.
+            let mut column = 0;
+            for run in line.chunk_by(|a, b| a.style == b.style) {
+                let text: String = run.iter().map(|cell| cell.c).collect();
+                let text = CString16::try_from(text.as_str())
+                    .map_err(|_| AppError::Generic("text is not representable in UCS-2"))?;
+                self.console.write_at(column, row, &text, run[0].style)?;
+                column += run.len();
+            }
+        }
+
+        self.shown = Some(self.cells.clone());
+        Ok(())
     }
 }
@@ -0,0 +1,179 @@
+//! Initrd delivery through the Linux `LoadFile2` protocol.
+//!
+//! Since v5.8 the Linux EFI stub asks the firmware for its initrd instead of
+//! reading `initrd=` files itself. It locates a handle carrying a vendor
+//! media device path with [`LINUX_EFI_INITRD_MEDIA_GUID`] and calls
+//! `LoadFile2` on it. Serving the initrd from memory this way means it can
+//! be assembled from several files on any partition.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use uefi::proto::device_path::build::{self, DevicePathBuilder};
+use uefi::{guid, Guid, Handle, Identify, Status};
+use uefi_raw::protocol::device_path::DevicePathProtocol;
+use uefi_raw::protocol::media::LoadFile2Protocol;
+use uefi_raw::Boolean;
+
+use crate::error::AppError;
+
+/// Vendor media GUID of the device path the Linux EFI stub loads its initrd from.
+pub const LINUX_EFI_INITRD_MEDIA_GUID: Guid = guid!("5568e427-68fc-4f3d-ac74-ca555231cc68");
+
+/// The `LoadFile2` interface together with the data it serves.
+///
+/// `protocol` must stay the first field, the firmware hands the interface
+/// pointer back to [`load_file`], which casts it to the whole struct.
+#[repr(C)]
+struct InitrdLoader {
+    protocol: LoadFile2Protocol,
+    data: Vec<u8>,
+}
+
+/// An initrd installed for the next image to pick up.
+///
+/// The protocols are uninstalled when this is dropped, so it must be kept
+/// alive until `start_image` returns.
+pub struct InitrdMedia {
+    handle: Handle,
+    loader: Box<InitrdLoader>,
+    device_path: Vec<u8>,
+}
+
+impl InitrdMedia {
+    /// Installs the initrd device path and `LoadFile2` protocol on a new
+    /// handle, serving `data`.
+    ///
+    /// # Errors
+    /// Returns an error if the device path cannot be built or the protocols
+    /// cannot be installed, e.g. because another initrd is already installed.
+    pub fn install(data: Vec<u8>) -> Result<Self, AppError> {
+        let mut device_path = Vec::new();
+        let path_ptr = DevicePathBuilder::with_vec(&mut device_path)
+            .push(&build::media::Vendor {
+                vendor_guid: LINUX_EFI_INITRD_MEDIA_GUID,
+                vendor_defined_data: &[],
+            })?
+            .finalize()?
+            .as_ffi_ptr();
+
+        let loader = Box::new(InitrdLoader {
+            protocol: LoadFile2Protocol { load_file },
+            data,
+        });
+        let loader_ptr: *const InitrdLoader = &raw const *loader;
+
+        // Both interfaces point into heap allocations owned by the returned
+        // value, which uninstalls them before they are freed.
+        let handle = unsafe {
+            uefi::boot::install_protocol_interface(
+                None,
+                &uefi::proto::device_path::DevicePath::GUID,
+                path_ptr.cast(),
+            )?
+        };
+        if let Err(e) = unsafe {
+            uefi::boot::install_protocol_interface(
+                Some(handle),
+                &LoadFile2Protocol::GUID,
+                loader_ptr.cast(),
+            )
+        } {
+            let _ = unsafe {
+                uefi::boot::uninstall_protocol_interface(
+                    handle,
+                    &uefi::proto::device_path::DevicePath::GUID,
+                    path_ptr.cast(),
+                )
+            };
+            return Err(e.into());
+        }
+
+        Ok(Self {
+            handle,
+            loader,
+            device_path,
+        })
+    }
+}
+
+impl Drop for InitrdMedia {
+    fn drop(&mut self) {
+        let loader_ptr: *const InitrdLoader = &raw const *self.loader;
+        let results = unsafe {
+            [
+                uefi::boot::uninstall_protocol_interface(
+                    self.handle,
+                    &LoadFile2Protocol::GUID,
+                    loader_ptr.cast(),
+                ),
+                uefi::boot::uninstall_protocol_interface(
+                    self.handle,
+                    &uefi::proto::device_path::DevicePath::GUID,
+                    self.device_path.as_ptr().cast(),
+                ),
+            ]
+        };
+        for result in results {
+            if let Err(e) = result {
+                log::warn!("failed to uninstall initrd protocols: {e}");
+            }
+        }
+    }
+}
+
+/// Appends `file` to an initrd being assembled from several files.
+///
+/// Each file starts on a 4 byte boundary, as the kernel expects for
+/// concatenated cpio archives.
+pub fn append_initrd(initrd: &mut Vec<u8>, file: &[u8]) {
+    initrd.resize(initrd.len().next_multiple_of(4), 0);
+    initrd.extend_from_slice(file);
+}
+
+unsafe extern "efiapi" fn load_file(
+    this: *mut LoadFile2Protocol,
+    _file_path: *const DevicePathProtocol,
+    boot_policy: Boolean,
+    buffer_size: *mut usize,
+    buffer: *mut c_void,
+) -> Status {
+    if this.is_null() || buffer_size.is_null() {
+        return Status::INVALID_PARAMETER;
+    }
+    // LoadFile2 is never used to load boot options.
+    if bool::from(boot_policy) {
+        return Status::UNSUPPORTED;
+    }
+
+    let data = unsafe { &(*this.cast::<InitrdLoader>()).data };
+    let size = unsafe { &mut *buffer_size };
+    if buffer.is_null() || *size < data.len() {
+        *size = data.len();
+        return Status::BUFFER_TOO_SMALL;
+    }
+
+    unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), buffer.cast::<u8>(), data.len()) };
+    *size = data.len();
+    Status::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_concatenated_files() {
+        let mut initrd = Vec::new();
+        append_initrd(&mut initrd, b"ucode");
+        assert_eq!(initrd, b"ucode");
+
+        append_initrd(&mut initrd, b"cpio");
+        assert_eq!(initrd, b"ucode\0\0\0cpio");
+
+        // Files ending on a boundary are not padded.
+        append_initrd(&mut initrd, b"");
+        append_initrd(&mut initrd, b"x");
+        assert_eq!(initrd, b"ucode\0\0\0cpiox");
+    }
+}
@@ -0,0 +1,353 @@
+//! Keyboard input for the UI.
+//!
+//! Apps read keys through [`InputSource`], so they do not depend on the UEFI
+//! input protocol directly. [`UefiInput`] reads from the firmware console,
+//! tests can script key presses instead.
+//!
+//! [`reveal_requested`] checks for held keys before any UI exists, for
+//! `menu = "hidden"`.
+
+use core::time::Duration;
+use uefi::boot::{EventType, ScopedProtocol, TimerTrigger, Tpl};
+use uefi::proto::console::text::{Input, Key};
+use uefi::proto::unsafe_protocol;
+use uefi::{Event, Handle, Status};
+use uefi_raw::protocol::console::InputKey;
+use uefi_raw::Boolean;
+
+use crate::error::AppError;
+
+/// Something that happened while waiting for input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    /// A key was pressed.
+    Key(Key),
+    /// A second passed without a key press, see [`InputSource::next_event`].
+    Tick,
+}
+
+/// A source of key presses.
+pub trait InputSource {
+    /// Blocks until the next key press.
+    ///
+    /// With `ticks` set, [`InputEvent::Tick`] is also returned once a second
+    /// while no key is pressed, e.g. to drive a countdown.
+    ///
+    /// # Errors
+    /// Returns an error if the input device fails.
+    fn next_event(&mut self, ticks: bool) -> Result<InputEvent, AppError>;
+
+    /// Blocks until the next key press, ignoring ticks.
+    ///
+    /// # Errors
+    /// Returns an error if the input device fails.
+    fn next_key(&mut self) -> Result<Key, AppError> {
+        loop {
+            if let InputEvent::Key(key) = self.next_event(false)? {
+                return Ok(key);
+            }
+        }
+    }
+
+    /// Whether Ctrl was held with the last key press, to tell e.g.
+    /// Ctrl+Enter from Enter. Sources that cannot tell return `false`.
+    fn control_held(&mut self) -> bool {
+        false
+    }
+}
+
+/// Reads keys from the UEFI console, through `SimpleTextInputEx` if the
+/// firmware has it and `SimpleTextInput` otherwise.
+pub struct UefiInput<'a> {
+    input: &'a mut Input,
+    /// Only exists while ticks are requested, so a stale tick is not
+    /// reported when they are requested again later.
+    timer: Option<TickTimer>,
+    /// The same console, which reports each key together with the state of
+    /// the modifier keys. Used instead of `input` if available.
+    input_ex: Option<ScopedProtocol<TextInputEx>>,
+    /// Whether Ctrl was held with the last key read.
+    control: bool,
+}
+
+impl<'a> UefiInput<'a> {
+    /// Wraps a UEFI input protocol.
+    pub fn new(input: &'a mut Input) -> Self {
+        let mut input_ex = open_stdin_ex();
+        if let Some(input_ex) = input_ex.as_mut() {
+            // The modifier state is only reliable once it is exposed.
+            input_ex.expose_modifiers();
+        }
+        Self {
+            input,
+            timer: None,
+            input_ex,
+            control: false,
+        }
+    }
+
+    /// Reads the next key if one is waiting, remembering whether Ctrl was
+    /// held with it.
+    fn read_key(&mut self) -> Result<Option<Key>, AppError> {
+        let Some(input_ex) = self.input_ex.as_mut() else {
+            self.control = false;
+            return Ok(self.input.read_key()?);
+        };
+        // With the modifier keys exposed, pressing one alone reads as a key
+        // stroke without a key.
+        match input_ex.read_key()? {
+            Some(data) if data.key != InputKey::default() => {
+                self.control = data.control();
+                Ok(Some(Key::from(data.key)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl InputSource for UefiInput<'_> {
+    fn control_held(&mut self) -> bool {
+        self.control
+    }
+
+    fn next_event(&mut self, ticks: bool) -> Result<InputEvent, AppError> {
+        if !ticks {
+            self.timer = None;
+        } else if self.timer.is_none() {
+            self.timer = Some(TickTimer::new()?);
+        }
+
+        loop {
+            let key_event = match self.input_ex.as_ref().and_then(|i| i.key_event()) {
+                Some(event) => event,
+                // unchecked because Option::<NonNull>::None.unwrap_unchecked() == 0
+                // due to the niche optimization with valid size and alignment.
+                None => unsafe { self.input.wait_for_key_event().unwrap_unchecked() },
+            };
+
+            let fired = match &self.timer {
+                Some(timer) => {
+                    let mut events = [key_event, unsafe { timer.event.unsafe_clone() }];
+                    uefi::boot::wait_for_event(&mut events)
+                }
+                None => uefi::boot::wait_for_event(&mut [key_event]),
+            }
+            .map_err(|_| uefi::Error::from(uefi::Status::INVALID_PARAMETER))?;
+
+            if fired == 1 {
+                return Ok(InputEvent::Tick);
+            }
+
+            // The key event may be signaled without a key being available.
+            if let Some(key) = self.read_key()? {
+                return Ok(InputEvent::Key(key));
+            }
+        }
+    }
+}
+
+/// A periodic one second timer used to generate ticks.
+///
+/// The underlying event is closed when the timer is dropped.
+struct TickTimer {
+    event: Event,
+}
+
+impl TickTimer {
+    /// Timer period in 100ns units.
+    const PERIOD: u64 = 10_000_000;
+
+    fn new() -> Result<Self, AppError> {
+        let event =
+            unsafe { uefi::boot::create_event(EventType::TIMER, Tpl::CALLBACK, None, None)? };
+        uefi::boot::set_timer(&event, TimerTrigger::Periodic(Self::PERIOD))?;
+        Ok(Self { event })
+    }
+}
+
+impl Drop for TickTimer {
+    fn drop(&mut self) {
+        let event = unsafe { self.event.unsafe_clone() };
+        if let Err(e) = uefi::boot::close_event(event) {
+            log::warn!("failed to close tick timer: {e:?}");
+        }
+    }
+}
+
+/// The `EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL`, which unlike `SimpleTextInput`
+/// also reports the state of the modifier keys.
+#[repr(C)]
+#[unsafe_protocol("dd9e7534-7762-4698-8c14-f58517a625aa")]
+struct TextInputEx {
+    reset: unsafe extern "efiapi" fn(this: *mut Self, extended_verification: Boolean) -> Status,
+    read_key_stroke_ex:
+        unsafe extern "efiapi" fn(this: *mut Self, key_data: *mut KeyData) -> Status,
+    wait_for_key_ex: uefi_raw::Event,
+    set_state: unsafe extern "efiapi" fn(this: *mut Self, toggle_state: *const u8) -> Status,
+    register_key_notify: *const core::ffi::c_void,
+    unregister_key_notify: *const core::ffi::c_void,
+}
+
+impl TextInputEx {
+    /// Asks the firmware to also report modifier keys pressed on their own.
+    /// Not every firmware supports this, Shift is then only seen together
+    /// with another key.
+    fn expose_modifiers(&mut self) {
+        let state = KeyData::TOGGLE_STATE_VALID | KeyData::KEY_STATE_EXPOSED;
+        let status = unsafe { (self.set_state)(self, &raw const state) };
+        if status.is_error() {
+            log::debug!("cannot expose modifier keys: {status:?}");
+        }
+    }
+
+    /// The event signaled when a key stroke is waiting.
+    fn key_event(&self) -> Option<Event> {
+        unsafe { Event::from_ptr(self.wait_for_key_ex) }
+    }
+
+    /// Reads the next key stroke. Without one, the key is left empty but
+    /// the modifier state is still filled in.
+    fn read(&mut self) -> Option<KeyData> {
+        let mut data = KeyData::default();
+        match unsafe { (self.read_key_stroke_ex)(self, &raw mut data) } {
+            Status::SUCCESS | Status::NOT_READY => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Reads the next key stroke, `None` if there is none.
+    ///
+    /// # Errors
+    /// Returns an error if the input device fails.
+    fn read_key(&mut self) -> Result<Option<KeyData>, AppError> {
+        let mut data = KeyData::default();
+        match unsafe { (self.read_key_stroke_ex)(self, &raw mut data) } {
+            Status::SUCCESS => Ok(Some(data)),
+            Status::NOT_READY => Ok(None),
+            status => Err(uefi::Error::from(status).into()),
+        }
+    }
+}
+
+/// The `EFI_KEY_DATA` returned by [`TextInputEx`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+struct KeyData {
+    key: InputKey,
+    shift_state: u32,
+    toggle_state: u8,
+}
+
+impl KeyData {
+    const SHIFT_STATE_VALID: u32 = 0x8000_0000;
+    const RIGHT_SHIFT_PRESSED: u32 = 0x0000_0001;
+    const LEFT_SHIFT_PRESSED: u32 = 0x0000_0002;
+    const RIGHT_CONTROL_PRESSED: u32 = 0x0000_0004;
+    const LEFT_CONTROL_PRESSED: u32 = 0x0000_0008;
+    const TOGGLE_STATE_VALID: u8 = 0x80;
+    const KEY_STATE_EXPOSED: u8 = 0x40;
+
+    /// Whether any of the `keys` in the shift state is held.
+    const fn holds(&self, keys: u32) -> bool {
+        self.shift_state & Self::SHIFT_STATE_VALID != 0 && self.shift_state & keys != 0
+    }
+
+    /// Whether Space or either Shift key is held.
+    const fn reveals(&self) -> bool {
+        self.key.unicode_char == b' ' as u16
+            || self.holds(Self::RIGHT_SHIFT_PRESSED | Self::LEFT_SHIFT_PRESSED)
+    }
+
+    /// Whether either Ctrl key is held.
+    const fn control(&self) -> bool {
+        self.holds(Self::RIGHT_CONTROL_PRESSED | Self::LEFT_CONTROL_PRESSED)
+    }
+}
+
+/// Opens `SimpleTextInputEx` on the console input, if the firmware has it.
+fn open_stdin_ex() -> Option<ScopedProtocol<TextInputEx>> {
+    let stdin = uefi::table::system_table_raw()
+        .and_then(|table| unsafe { Handle::from_ptr(table.as_ref().stdin_handle) })?;
+    crate::path::open_protocol_get::<TextInputEx>(stdin)
+        .inspect_err(|e| log::debug!("no SimpleTextInputEx on the console: {e}"))
+        .ok()
+}
+
+/// Interval at which the keyboard is polled during the reveal window.
+const REVEAL_POLL: Duration = Duration::from_millis(10);
+
+/// Whether Space or Shift is held during the next `window`, asking for
+/// the boot menu to be shown although it is hidden.
+///
+/// Only the console input protocols are used, so nothing is drawn and the
+/// firmware logo stays on the screen. Firmware without
+/// `SimpleTextInputEx` only sees Space.
+#[must_use]
+pub fn reveal_requested(window: Duration) -> bool {
+    let polls = window.as_millis().div_ceil(REVEAL_POLL.as_millis()).max(1);
+
+    if let Some(mut input) = open_stdin_ex() {
+        input.expose_modifiers();
+        for poll in 0..=polls {
+            // Drain buffered keys, e.g. repeats of a key held since power on.
+            while let Some(data) = input.read() {
+                if data.reveals() {
+                    return true;
+                }
+                if data.key == InputKey::default() {
+                    break;
+                }
+            }
+            if poll < polls {
+                uefi::boot::stall(REVEAL_POLL);
+            }
+        }
+        return false;
+    }
+
+    uefi::system::with_stdin(|input| {
+        for poll in 0..=polls {
+            while let Ok(Some(key)) = input.read_key() {
+                if matches!(key, Key::Printable(c) if c == ' ') {
+                    return true;
+                }
+            }
+            if poll < polls {
+                uefi::boot::stall(REVEAL_POLL);
+            }
+        }
+        false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_modifier_keys() {
+        let key = |unicode_char: u8, shift_state: u32| KeyData {
+            key: InputKey {
+                scan_code: 0,
+                unicode_char: u16::from(unicode_char),
+            },
+            shift_state,
+            toggle_state: 0,
+        };
+
+        assert!(key(b' ', 0).reveals());
+        assert!(!key(b'a', 0).reveals());
+        assert!(!key(0, 0).reveals());
+        assert!(key(0, KeyData::SHIFT_STATE_VALID | KeyData::LEFT_SHIFT_PRESSED).reveals());
+        assert!(key(0, KeyData::SHIFT_STATE_VALID | KeyData::RIGHT_SHIFT_PRESSED).reveals());
+        // Control alone does not reveal, and the state is ignored unless valid.
+        let control = key(
+            b'\r',
+            KeyData::SHIFT_STATE_VALID | KeyData::LEFT_CONTROL_PRESSED,
+        );
+        assert!(!control.reveals());
+        assert!(control.control());
+        assert!(!key(0, KeyData::LEFT_SHIFT_PRESSED).reveals());
+        assert!(!key(b'\r', KeyData::RIGHT_CONTROL_PRESSED).control());
+    }
+}
@@ -7,4 +7,9 @@
 pub mod app;
 pub mod bootables;
 pub mod display;
+pub mod initrd;
+pub mod input;
+pub mod nvram;
 pub mod resolver;
+pub mod smbios;
+pub mod system;
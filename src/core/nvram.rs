@@ -0,0 +1,256 @@
+//! EFI variables plex reads and writes.
+//!
+//! Plex keeps its own state in non-volatile variables under
+//! [`PLEX_VENDOR`]: the stable ID of the last booted entry, which lets
+//! `default = "@saved"` preselect it on the next start, and the entry to
+//! boot once on the next start. Variables of the firmware boot manager,
+//! such as `BootNext` and `OsIndications`, are accessed through here as
+//! well.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use uefi::runtime::{self, VariableAttributes, VariableVendor};
+use uefi::{cstr16, guid, CStr16, CString16};
+
+use crate::error::AppError;
+
+/// Vendor GUID under which plex stores its EFI variables.
+pub const PLEX_VENDOR: VariableVendor =
+    VariableVendor(guid!("477f99d8-02d2-484a-b2b7-ee46ea9b8768"));
+
+/// Name of the variable holding the ID of the last booted entry.
+const LAST_BOOTED: &CStr16 = cstr16!("PlexLastBooted");
+/// Name of the variable holding the ID of the entry to boot once.
+const ONE_SHOT: &CStr16 = cstr16!("PlexOneShot");
+/// Name of the variable selecting the `Boot####` option for the next start.
+const BOOT_NEXT: &CStr16 = cstr16!("BootNext");
+/// Name of the variable listing the boot options in menu order.
+const BOOT_ORDER: &CStr16 = cstr16!("BootOrder");
+/// Name of the variable holding the number of the option booted this time.
+const BOOT_CURRENT: &CStr16 = cstr16!("BootCurrent");
+/// Name of the variable telling whether Secure Boot is enforced.
+const SECURE_BOOT: &CStr16 = cstr16!("SecureBoot");
+/// Name of the variable holding requests of the OS to the firmware.
+const OS_INDICATIONS: &CStr16 = cstr16!("OsIndications");
+/// Name of the variable listing the `OsIndications` the firmware supports.
+const OS_INDICATIONS_SUPPORTED: &CStr16 = cstr16!("OsIndicationsSupported");
+
+/// `EFI_OS_INDICATIONS_BOOT_TO_FW_UI`, asks the firmware to stop in its
+/// setup on the next start.
+pub const BOOT_TO_FW_UI: u64 = 0x0000_0000_0000_0001;
+
+/// Attributes of variables that survive a reboot.
+const PERSISTENT: VariableAttributes = VariableAttributes::NON_VOLATILE
+    .union(VariableAttributes::BOOTSERVICE_ACCESS)
+    .union(VariableAttributes::RUNTIME_ACCESS);
+
+/// Reads a variable holding UTF-8 text.
+///
+/// Missing or unreadable variables are not an error, there is simply
+/// nothing to restore.
+#[must_use]
+pub fn get_string(name: &CStr16, vendor: &VariableVendor) -> Option<String> {
+    match runtime::get_variable_boxed(name, vendor) {
+        Ok((data, _)) => decode_string(data.into_vec()).or_else(|| {
+            log::warn!("{name} is not valid UTF-8, ignoring");
+            None
+        }),
+        Err(e) if e.status() == uefi::Status::NOT_FOUND => None,
+        Err(e) => {
+            log::warn!("failed to read {name}: {e:?}");
+            None
+        }
+    }
+}
+
+/// Decodes the contents of a variable written by [`set_string`].
+fn decode_string(data: Vec<u8>) -> Option<String> {
+    String::from_utf8(data).ok()
+}
+
+/// Writes `value` as UTF-8 to a variable that survives a reboot.
+///
+/// # Errors
+/// Returns an error if the firmware refuses to write the variable.
+pub fn set_string(name: &CStr16, vendor: &VariableVendor, value: &str) -> Result<(), AppError> {
+    runtime::set_variable(name, vendor, PERSISTENT, value.as_bytes())?;
+    Ok(())
+}
+
+/// Returns the ID of the last booted entry, if one was recorded.
+#[must_use]
+pub fn load_last_booted() -> Option<String> {
+    get_string(LAST_BOOTED, &PLEX_VENDOR)
+}
+
+/// Records `id` as the last booted entry. The variable is only written if
+/// it holds a different ID, as every write wears the flash.
+///
+/// # Errors
+/// Returns an error if the firmware refuses to write the variable.
+pub fn store_last_booted(id: &str) -> Result<(), AppError> {
+    if load_last_booted().as_deref() == Some(id) {
+        return Ok(());
+    }
+    set_string(LAST_BOOTED, &PLEX_VENDOR, id)
+}
+
+/// Returns the ID of the entry to boot once and forgets it, so the entry
+/// is not booted again if it fails or the machine restarts.
+#[must_use]
+pub fn take_one_shot() -> Option<String> {
+    let id = get_string(ONE_SHOT, &PLEX_VENDOR)?;
+    if let Err(e) = runtime::delete_variable(ONE_SHOT, &PLEX_VENDOR) {
+        // Booting the entry again on every start would be worse than not
+        // booting it at all.
+        log::warn!("failed to clear {ONE_SHOT}, ignoring it: {e:?}");
+        return None;
+    }
+    Some(id)
+}
+
+/// Makes plex boot the entry with the stable ID `id` on the next start,
+/// once, without showing the menu.
+///
+/// # Errors
+/// Returns an error if the firmware refuses to write the variable.
+pub fn store_one_shot(id: &str) -> Result<(), AppError> {
+    set_string(ONE_SHOT, &PLEX_VENDOR, id)
+}
+
+/// Makes the firmware boot the option `Boot####` with the given number on
+/// the next start, once, instead of following `BootOrder`.
+///
+/// # Errors
+/// Returns an error if the firmware refuses to write the variable.
+pub fn set_boot_next(number: u16) -> Result<(), AppError> {
+    runtime::set_variable(
+        BOOT_NEXT,
+        &VariableVendor::GLOBAL_VARIABLE,
+        PERSISTENT,
+        &number.to_le_bytes(),
+    )?;
+    Ok(())
+}
+
+/// Reads a variable of the firmware boot manager, defined by the UEFI
+/// specification under `EFI_GLOBAL_VARIABLE`.
+///
+/// # Errors
+/// Returns an error if the variable is missing or cannot be read.
+pub fn get_global(name: &CStr16) -> Result<Box<[u8]>, AppError> {
+    Ok(runtime::get_variable_boxed(name, &VariableVendor::GLOBAL_VARIABLE)?.0)
+}
+
+/// Returns the numbers of the `Boot####` options in `BootOrder`, in the
+/// order of the firmware boot menu.
+///
+/// # Errors
+/// Returns an error if `BootOrder` is missing or cannot be read.
+pub fn boot_order() -> Result<Vec<u16>, AppError> {
+    Ok(decode_u16_list(&get_global(BOOT_ORDER)?))
+}
+
+/// Returns the number of the `Boot####` option the firmware started this
+/// time, if it recorded one.
+#[must_use]
+pub fn boot_current() -> Option<u16> {
+    decode_u16_list(&get_global(BOOT_CURRENT).ok()?)
+        .first()
+        .copied()
+}
+
+/// Reads the load option `Boot####` with the given number, see
+/// [`crate::core::resolver::firmware::LoadOption::parse`].
+///
+/// # Errors
+/// Returns an error if the option is missing or cannot be read.
+pub fn boot_option(number: u16) -> Result<Box<[u8]>, AppError> {
+    get_global(&CString16::try_from(format!("Boot{number:04X}").as_str())?)
+}
+
+/// Whether the `SecureBoot` variable is set, i.e. Secure Boot is enforced.
+#[must_use]
+pub fn secure_boot_enabled() -> bool {
+    get_global(SECURE_BOOT).is_ok_and(|data| data.first() == Some(&1))
+}
+
+/// Decodes a variable holding a list of little endian `u16`, such as
+/// `BootOrder`. A trailing odd byte is ignored.
+fn decode_u16_list(data: &[u8]) -> Vec<u16> {
+    data.as_chunks::<2>()
+        .0
+        .iter()
+        .map(|&n| u16::from_le_bytes(n))
+        .collect()
+}
+
+/// Decodes a variable holding a little endian `u64`, `0` if it is shorter.
+fn decode_u64(data: &[u8]) -> u64 {
+    data.first_chunk::<8>()
+        .map_or(0, |n| u64::from_le_bytes(*n))
+}
+
+/// Reads a variable holding a little endian `u64`, `0` if it is missing.
+fn get_u64(name: &CStr16) -> u64 {
+    get_global(name).map_or(0, |data| decode_u64(&data))
+}
+
+/// Whether the firmware can be asked to stop in its setup, see
+/// [`request_firmware_ui`].
+#[must_use]
+pub fn boot_to_firmware_ui_supported() -> bool {
+    get_u64(OS_INDICATIONS_SUPPORTED) & BOOT_TO_FW_UI != 0
+}
+
+/// Asks the firmware to stop in its setup on the next start, keeping any
+/// other indications already set.
+///
+/// # Errors
+/// Returns an error if the firmware does not support this or refuses to
+/// write the variable.
+pub fn request_firmware_ui() -> Result<(), AppError> {
+    if !boot_to_firmware_ui_supported() {
+        return Err(AppError::NotImplemented(
+            "booting to the firmware setup on this firmware",
+        ));
+    }
+    let indications = get_u64(OS_INDICATIONS) | BOOT_TO_FW_UI;
+    runtime::set_variable(
+        OS_INDICATIONS,
+        &VariableVendor::GLOBAL_VARIABLE,
+        PERSISTENT,
+        &indications.to_le_bytes(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_strings() {
+        let id = "Arch Linux (6.9.7) \u{1F600}";
+        assert_eq!(decode_string(id.as_bytes().to_vec()).as_deref(), Some(id));
+        assert_eq!(decode_string(Vec::new()).as_deref(), Some(""));
+        assert_eq!(decode_string(alloc::vec![0xFF, 0xFE]), None);
+    }
+
+    #[test]
+    fn decodes_numbers() {
+        let order: Vec<u8> = [0x0003_u16, 0x0001, 0x1000]
+            .iter()
+            .flat_map(|n| n.to_le_bytes())
+            .collect();
+        assert_eq!(decode_u16_list(&order), [0x0003, 0x0001, 0x1000]);
+        assert_eq!(decode_u16_list(&[0x01, 0x00, 0x02]), [0x0001]);
+        assert_eq!(decode_u16_list(&[]), []);
+
+        let indications = (BOOT_TO_FW_UI | 0x40).to_le_bytes();
+        assert_eq!(decode_u64(&indications), BOOT_TO_FW_UI | 0x40);
+        assert_eq!(decode_u64(&indications[..4]), 0);
+    }
+}
@@ -0,0 +1,403 @@
+//! Boot Loader Specification Type #1 entry discovery.
+//!
+//! Reads `/loader/entries/*.conf` from the EFI system partition and from any
+//! Extended Boot Loader (XBOOTLDR) partitions, as described in
+//! <https://uapi-group.org/specifications/specs/boot_loader_specification/>.
+//! Kernels and initrds are loaded from the partition the entry was found on.
+
+use core::cmp::Ordering;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::Deserialize;
+use uefi::fs::PathBuf;
+use uefi::{CStr16, CString16};
+
+use crate::core::bootables::{BootTarget, GenericBootTarget};
+use crate::core::resolver::{require_disk_manager, ResolverCtx};
+use crate::error::AppError;
+use crate::helpers::text::strip_suffix_ignore_case;
+use crate::path::{Partition, PartitionReference, PathReference};
+
+/// Options of the `bls` resolver in `plex.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct BlsOptions {
+    /// Directory holding Type #1 entries, relative to the partition root.
+    pub directory: String,
+    /// Whether to also look for entries on XBOOTLDR partitions, not just
+    /// on the ESP.
+    pub xbootldr: bool,
+}
+
+impl Default for BlsOptions {
+    fn default() -> Self {
+        Self {
+            directory: String::from("\\loader\\entries"),
+            xbootldr: true,
+        }
+    }
+}
+
+/// A single Type #1 boot loader entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlsEntry {
+    /// File name of the entry without the `.conf` suffix.
+    pub id: String,
+    /// Human readable name of the entry.
+    pub title: Option<String>,
+    /// Version of the entry, usually the kernel version.
+    pub version: Option<String>,
+    /// Machine ID of the OS installation the entry belongs to.
+    pub machine_id: Option<String>,
+    /// Key used to order entries before falling back to the version.
+    pub sort_key: Option<String>,
+    /// Path of the Linux kernel EFI image.
+    pub linux: Option<String>,
+    /// Paths of the initrd images, in load order.
+    pub initrd: Vec<String>,
+    /// Kernel command line fragments, in the order they appeared.
+    pub options: Vec<String>,
+}
+
+impl BlsEntry {
+    /// Parses the contents of an entry file.
+    ///
+    /// Unknown keys and comments are ignored. `initrd` and `options` may be
+    /// given more than once, all other keys keep their last value.
+    ///
+    /// # Example
+    /// ```
+    /// use plex_boot::core::resolver::bls::BlsEntry;
+    /// let entry = BlsEntry::parse(
+    ///     "arch-6.9.1",
+    ///     "title Arch Linux\nversion 6.9.1\nlinux /vmlinuz-linux\n\
+    ///      initrd /initramfs-linux.img\noptions root=/dev/sda2 rw\n",
+    /// );
+    /// assert_eq!(entry.label(), "Arch Linux (6.9.1)");
+    /// assert_eq!(entry.linux.as_deref(), Some("/vmlinuz-linux"));
+    /// ```
+    #[must_use]
+    pub fn parse(id: &str, contents: &str) -> Self {
+        let mut entry = Self {
+            id: id.to_string(),
+            ..Self::default()
+        };
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(|c: char| c.is_ascii_whitespace())
+                .map_or((line, ""), |(k, v)| (k, v.trim()));
+            let value = value.to_string();
+
+            match key {
+                "title" => entry.title = Some(value),
+                "version" => entry.version = Some(value),
+                "machine-id" => entry.machine_id = Some(value),
+                "sort-key" => entry.sort_key = Some(value),
+                "linux" => entry.linux = Some(value),
+                "initrd" => entry.initrd.push(value),
+                "options" => entry.options.push(value),
+                _ => log::trace!("bls entry {id}: ignoring key {key}"),
+            }
+        }
+
+        entry
+    }
+
+    /// The label shown in the boot menu, the title followed by the version.
+    #[must_use]
+    pub fn label(&self) -> String {
+        let title = self.title.as_deref().unwrap_or(&self.id);
+        self.version.as_ref().map_or_else(
+            || title.to_string(),
+            |version| format!("{title} ({version})"),
+        )
+    }
+
+    /// The kernel command line, with an `initrd=` argument per initrd so
+    /// the EFI stub loads them from the kernel's partition.
+    #[must_use]
+    pub fn cmdline(&self) -> String {
+        let mut args: Vec<String> = self.options.clone();
+        args.extend(
+            self.initrd
+                .iter()
+                .map(|initrd| format!("initrd={}", to_uefi_path(initrd))),
+        );
+        args.join(" ")
+    }
+
+    /// Orders entries as recommended by the specification: entries with a
+    /// `sort-key` come first, then by `sort-key` and `machine-id`, newest
+    /// version first.
+    #[must_use]
+    pub fn compare(&self, other: &Self) -> Ordering {
+        match (&self.sort_key, &other.sort_key) {
+            (Some(a), Some(b)) => a
+                .cmp(b)
+                .then_with(|| self.machine_id.cmp(&other.machine_id))
+                .then_with(|| {
+                    version_cmp(
+                        other.version.as_deref().unwrap_or_default(),
+                        self.version.as_deref().unwrap_or_default(),
+                    )
+                }),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+        .then_with(|| version_cmp(&other.id, &self.id))
+    }
+
+    fn into_boot_target(self, location: &PartitionReference) -> Option<BootTarget> {
+        let Some(linux) = &self.linux else {
+            log::warn!("bls entry {} has no linux key, skipping", self.id);
+            return None;
+        };
+
+        let executable = PathReference {
+            location: location.clone(),
+            path: to_uefi_path(linux),
+        };
+
+        let target = match GenericBootTarget::new(self.label(), executable.to_uri(), self.cmdline())
+        {
+            Ok(target) => target.with_id(&self.id),
+            Err(e) => {
+                log::warn!("skipping bls entry {}: {e}", self.id);
+                return None;
+            }
+        };
+        // Entries of one OS share the title and differ in the version.
+        Some(BootTarget::Generic(match &self.title {
+            Some(title) => target.with_group(title),
+            None => target,
+        }))
+    }
+}
+
+/// Discovers Type #1 entries on all ESP and, if enabled, XBOOTLDR partitions.
+pub(super) fn resolve(ctx: &ResolverCtx, opts: &BlsOptions) -> Result<Vec<BootTarget>, AppError> {
+    let dm = require_disk_manager(ctx)?;
+    let dir = CString16::try_from(opts.directory.as_str())?;
+    let mut entries = Vec::new();
+
+    for partition in dm
+        .partitions()
+        .iter()
+        .filter(|p| p.is_esp() || (opts.xbootldr && p.is_xbootldr()))
+    {
+        let Some(location) = partition.reference() else {
+            log::warn!("skipping bls entries on unaddressable partition");
+            continue;
+        };
+
+        match read_entries(partition, &dir) {
+            Ok(found) => entries.extend(found.into_iter().map(|e| (location.clone(), e))),
+            Err(e) => log::debug!("no bls entries on {}: {e}", location.to_uri_prefix()),
+        }
+    }
+
+    entries.sort_by(|(_, a), (_, b)| a.compare(b));
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|(location, entry)| entry.into_boot_target(&location))
+        .collect())
+}
+
+fn read_entries(partition: &Partition, dir: &CStr16) -> Result<Vec<BlsEntry>, AppError> {
+    let mut fs = partition.file_system()?;
+    let mut entries = Vec::new();
+
+    for info in fs.read_dir(dir)? {
+        let Ok(info) = info else {
+            continue;
+        };
+        if !info.is_regular_file() {
+            continue;
+        }
+
+        let name = info.file_name().to_string();
+        let Some(id) = strip_suffix_ignore_case(&name, ".conf") else {
+            continue;
+        };
+
+        let path = format!("{dir}\\{name}");
+        let contents = match fs.read_to_string(PathBuf::from(CString16::try_from(path.as_str())?)) {
+            Ok(contents) => contents,
+            Err(e) => {
+                log::warn!("failed to read bls entry {path}: {e}");
+                continue;
+            }
+        };
+
+        entries.push(BlsEntry::parse(id, &contents));
+    }
+
+    Ok(entries)
+}
+
+/// Converts a specification path (`/vmlinuz`) into a UEFI path (`\vmlinuz`).
+fn to_uefi_path(path: &str) -> String {
+    let path = path.replace('/', "\\");
+    if path.starts_with('\\') {
+        path
+    } else {
+        format!("\\{path}")
+    }
+}
+
+/// Compares two version strings, treating runs of digits as numbers so
+/// that `6.10` sorts after `6.9`.
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.as_bytes();
+    let mut b = b.as_bytes();
+
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_len = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_len = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (a_num, a_rest) = a.split_at(a_len);
+                let (b_num, b_rest) = b.split_at(b_len);
+
+                // Compare numerically without overflowing on long runs.
+                let a_num = trim_leading_zeros(a_num);
+                let b_num = trim_leading_zeros(b_num);
+                let ord = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+
+                a = a_rest;
+                b = b_rest;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+    &digits[zeros..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        id: &str,
+        sort_key: Option<&str>,
+        machine_id: Option<&str>,
+        version: &str,
+    ) -> BlsEntry {
+        BlsEntry {
+            id: id.to_string(),
+            sort_key: sort_key.map(String::from),
+            machine_id: machine_id.map(String::from),
+            version: Some(version.to_string()),
+            ..BlsEntry::default()
+        }
+    }
+
+    #[test]
+    fn parses_entries() {
+        let entry = BlsEntry::parse(
+            "arch",
+            "# Managed by kernel-install\n\
+             title   Arch Linux\n\
+             \n\
+             version 6.9.1\n\
+             machine-id 0a3407de014b458bb5c1848e92a327a3\n\
+             sort-key arch\n\
+             linux /vmlinuz-linux\n\
+             initrd /intel-ucode.img\n\
+             initrd /initramfs-linux.img\n\
+             options root=/dev/sda2\n\
+             options rw quiet\n\
+             devicetree /dtb\n\
+             title Arch Linux (renamed)\n",
+        );
+        assert_eq!(entry.id, "arch");
+        assert_eq!(entry.title.as_deref(), Some("Arch Linux (renamed)"));
+        assert_eq!(entry.version.as_deref(), Some("6.9.1"));
+        assert_eq!(
+            entry.machine_id.as_deref(),
+            Some("0a3407de014b458bb5c1848e92a327a3")
+        );
+        assert_eq!(entry.sort_key.as_deref(), Some("arch"));
+        assert_eq!(entry.linux.as_deref(), Some("/vmlinuz-linux"));
+        assert_eq!(entry.initrd, ["/intel-ucode.img", "/initramfs-linux.img"]);
+        assert_eq!(entry.options, ["root=/dev/sda2", "rw quiet"]);
+        assert_eq!(entry.label(), "Arch Linux (renamed) (6.9.1)");
+        assert_eq!(BlsEntry::parse("bare", "").label(), "bare");
+    }
+
+    #[test]
+    fn generates_cmdline() {
+        let entry = BlsEntry::parse(
+            "arch",
+            "options root=/dev/sda2 rw\ninitrd /intel-ucode.img\ninitrd arch/initramfs-linux.img\n",
+        );
+        assert_eq!(
+            entry.cmdline(),
+            "root=/dev/sda2 rw initrd=\\intel-ucode.img initrd=\\arch\\initramfs-linux.img"
+        );
+        assert_eq!(BlsEntry::parse("empty", "").cmdline(), "");
+    }
+
+    #[test]
+    fn orders_entries() {
+        let mut entries = [
+            entry("no-key-6.9", None, None, "6.9"),
+            entry("fedora-6.9", Some("fedora"), Some("b"), "6.9"),
+            entry("arch-6.9", Some("arch"), Some("a"), "6.9"),
+            entry("fedora-6.10", Some("fedora"), Some("b"), "6.10"),
+            entry("fedora-other", Some("fedora"), Some("a"), "5.0"),
+            entry("no-key-6.10", None, None, "6.10"),
+        ];
+        entries.sort_by(BlsEntry::compare);
+        let ids: Vec<_> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "arch-6.9",
+                "fedora-other",
+                "fedora-6.10",
+                "fedora-6.9",
+                "no-key-6.10",
+                "no-key-6.9",
+            ]
+        );
+    }
+
+    #[test]
+    fn compares_versions() {
+        assert_eq!(version_cmp("6.10", "6.9"), Ordering::Greater);
+        assert_eq!(version_cmp("6.9.1", "6.9"), Ordering::Greater);
+        assert_eq!(version_cmp("6.09", "6.9"), Ordering::Equal);
+        assert_eq!(version_cmp("6.9-rc1", "6.9-rc2"), Ordering::Less);
+        assert_eq!(
+            version_cmp("99999999999999999999999", "100000000000000000000000"),
+            Ordering::Less
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Discovery of EFI executables installed on the ESP.
+//!
+//! Lists `\EFI\*\*.efi` on every EFI system partition, which is where
+//! operating systems install their boot loaders, e.g.
+//! `\EFI\ubuntu\shimx64.efi`. Well-known loaders installed one level
+//! deeper, such as `\EFI\Microsoft\Boot\bootmgfw.efi`, are looked for
+//! explicitly, see [`KNOWN_LOADERS`]. Also picks up the removable media path
+//! `\EFI\BOOT\BOOT{arch}.EFI` on any partition, which is how USB sticks and
+//! installers are booted.
+//!
+//! These paths hold many tools that are not meant to be booted directly, so
+//! the resolver is not part of [`super::Resolver::defaults`]. It is part of
+//! the fallback menu shown when `plex.toml` cannot be loaded.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::Deserialize;
+use uefi::fs::PathBuf;
+use uefi::proto::device_path::text::{AllowShortcuts, DisplayOnly};
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::{CStr16, CString16, Handle};
+
+use crate::core::bootables::{BootTarget, GenericBootTarget};
+use crate::core::resolver::{require_disk_manager, ResolverCtx};
+use crate::error::AppError;
+use crate::helpers::text::strip_suffix_ignore_case;
+use crate::path::{open_protocol_get, Partition, PartitionReference, PathReference};
+
+/// Options of the `efi` resolver in `plex.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct EfiOptions {
+    /// Directory whose subdirectories are scanned for executables,
+    /// relative to the ESP root.
+    pub directory: String,
+    /// Whether to look for the removable media path on every partition.
+    pub removable: bool,
+}
+
+impl Default for EfiOptions {
+    fn default() -> Self {
+        Self {
+            directory: String::from("\\EFI"),
+            removable: true,
+        }
+    }
+}
+
+/// Subdirectory of [`EfiOptions::directory`] holding the removable media path.
+const REMOVABLE_DIR: &str = "BOOT";
+
+/// File name of the removable media path for the architecture we run on.
+#[cfg(target_arch = "x86_64")]
+const REMOVABLE_FILE: &str = "BOOTX64.EFI";
+#[cfg(target_arch = "x86")]
+const REMOVABLE_FILE: &str = "BOOTIA32.EFI";
+#[cfg(target_arch = "aarch64")]
+const REMOVABLE_FILE: &str = "BOOTAA64.EFI";
+#[cfg(target_arch = "riscv64")]
+const REMOVABLE_FILE: &str = "BOOTRISCV64.EFI";
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "x86",
+    target_arch = "aarch64",
+    target_arch = "riscv64"
+)))]
+const REMOVABLE_FILE: &str = "BOOT.EFI";
+
+/// Helpers installed next to shim, which are only started by shim itself.
+const SHIM_HELPERS: &[&str] = &[
+    "mmx64.efi",
+    "mmia32.efi",
+    "mmaa64.efi",
+    "fbx64.efi",
+    "fbia32.efi",
+    "fbaa64.efi",
+];
+
+/// Well-known loaders as `(directory, path below it, label)`. Those in a
+/// subdirectory of `directory` are only found because they are listed here.
+const KNOWN_LOADERS: &[(&str, &str, &str)] =
+    &[("Microsoft", "Boot\\bootmgfw.efi", "Windows Boot Manager")];
+
+/// Discovers executables on all ESPs and removable media paths.
+pub(super) fn resolve(ctx: &ResolverCtx, opts: &EfiOptions) -> Result<Vec<BootTarget>, AppError> {
+    let dm = require_disk_manager(ctx)?;
+    let own_image = own_image(ctx.image_handle);
+    let mut targets = Vec::new();
+
+    for partition in dm.partitions() {
+        let Some(location) = partition.reference() else {
+            continue;
+        };
+        let is_own_image = |path: &str| {
+            own_image.as_ref().is_some_and(|(handle, own)| {
+                *handle == partition.handle && own.eq_ignore_ascii_case(path)
+            })
+        };
+
+        if partition.is_esp() {
+            match list_loaders(partition, &opts.directory) {
+                Ok(loaders) => {
+                    for (dir, file_name) in loaders {
+                        let path = format!("{}\\{dir}\\{file_name}", opts.directory);
+                        if !is_own_image(&path) {
+                            push_target(
+                                &mut targets,
+                                &location,
+                                path,
+                                loader_label(&dir, &file_name),
+                            );
+                        }
+                    }
+                }
+                Err(e) => log::debug!("no loaders on {}: {e}", location.to_uri_prefix()),
+            }
+        }
+
+        if opts.removable {
+            let path = format!("{}\\{REMOVABLE_DIR}\\{REMOVABLE_FILE}", opts.directory);
+            let exists =
+                CString16::try_from(path.as_str()).is_ok_and(|p| partition.open_file(&p).is_ok());
+            if exists && !is_own_image(&path) {
+                let label = format!("Default loader on {}", location.to_uri_prefix());
+                push_target(&mut targets, &location, path, label.trim_end_matches(':'));
+            }
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Lists `(directory, path below it)` pairs of the executables one level
+/// below `root`, skipping the removable media directory and shim helpers,
+/// followed by the [`KNOWN_LOADERS`] nested deeper in the same directory.
+fn list_loaders(partition: &Partition, root: &str) -> Result<Vec<(String, String)>, AppError> {
+    let mut fs = partition.file_system()?;
+    let root = PathBuf::from(CString16::try_from(root)?);
+
+    let mut dirs: Vec<String> = fs
+        .read_dir(&root)?
+        .filter_map(Result::ok)
+        .filter(|info| info.is_directory())
+        .map(|info| info.file_name().to_string())
+        .filter(|name| name != "." && name != ".." && !name.eq_ignore_ascii_case(REMOVABLE_DIR))
+        .collect();
+    // Directory order is arbitrary, keep the menu stable between boots.
+    dirs.sort();
+
+    let mut loaders = Vec::new();
+    for dir in dirs {
+        let path = PathBuf::from(CString16::try_from(format!("{root}\\{dir}").as_str())?);
+        let Ok(entries) = fs.read_dir(&path) else {
+            continue;
+        };
+        let mut files: Vec<String> = entries
+            .filter_map(Result::ok)
+            .filter(|info| info.is_regular_file())
+            .map(|info| info.file_name().to_string())
+            .filter(|name| is_loader(name))
+            .collect();
+        files.sort();
+        for nested in nested_loaders(&dir) {
+            let path = CString16::try_from(format!("{root}\\{dir}\\{nested}").as_str())?;
+            if fs
+                .metadata(PathBuf::from(path))
+                .is_ok_and(|info| info.is_regular_file())
+            {
+                files.push(String::from(nested));
+            }
+        }
+        loaders.extend(files.into_iter().map(|file| (dir.clone(), file)));
+    }
+    Ok(loaders)
+}
+
+/// Whether a file directly below a loader directory is listed.
+fn is_loader(file_name: &str) -> bool {
+    strip_suffix_ignore_case(file_name, ".efi").is_some()
+        && !SHIM_HELPERS
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case(file_name))
+}
+
+/// The [`KNOWN_LOADERS`] in a subdirectory of `dir`, as paths below it.
+fn nested_loaders(dir: &str) -> impl Iterator<Item = &'static str> + '_ {
+    KNOWN_LOADERS
+        .iter()
+        .filter(move |(known, path, _)| known.eq_ignore_ascii_case(dir) && path.contains('\\'))
+        .map(|(_, path, _)| *path)
+}
+
+/// A readable label for [`KNOWN_LOADERS`], `directory (path)` otherwise.
+fn loader_label(dir: &str, path: &str) -> String {
+    KNOWN_LOADERS
+        .iter()
+        .find(|(known, known_path, _)| {
+            known.eq_ignore_ascii_case(dir) && known_path.eq_ignore_ascii_case(path)
+        })
+        .map_or_else(
+            || format!("{dir} ({path})"),
+            |(_, _, label)| String::from(*label),
+        )
+}
+
+/// Adds an entry booting `path` on `location`, skipping paths UEFI cannot
+/// open.
+fn push_target(
+    targets: &mut Vec<BootTarget>,
+    location: &PartitionReference,
+    path: String,
+    label: impl AsRef<str>,
+) {
+    let executable = PathReference {
+        location: location.clone(),
+        path,
+    };
+    let id = format!("efi:{}", executable.to_uri());
+    match GenericBootTarget::new(label, executable.to_uri(), "") {
+        Ok(target) => targets.push(BootTarget::Generic(target.with_id(id))),
+        Err(e) => log::warn!("skipping {}: {e}", executable.to_uri()),
+    }
+}
+
+/// The partition handle and path plex itself was loaded from, so it is not
+/// listed as an entry.
+fn own_image(image_handle: Handle) -> Option<(Handle, String)> {
+    let image = open_protocol_get::<LoadedImage>(image_handle).ok()?;
+    let device = image.device()?;
+    let path = image
+        .file_path()?
+        .to_string(DisplayOnly(false), AllowShortcuts(false))
+        .ok()?;
+    Some((device, String::from(&*path as &CStr16)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_loaders() {
+        assert!(is_loader("grubx64.efi"));
+        assert!(is_loader("SHIMX64.EFI"));
+        assert!(!is_loader("grub.cfg"));
+        assert!(!is_loader("mmx64.efi"));
+        assert!(!is_loader("FBX64.EFI"));
+
+        assert_eq!(
+            nested_loaders("microsoft").collect::<Vec<_>>(),
+            ["Boot\\bootmgfw.efi"]
+        );
+        assert_eq!(nested_loaders("ubuntu").count(), 0);
+    }
+
+    #[test]
+    fn labels_loaders() {
+        assert_eq!(
+            loader_label("Microsoft", "Boot\\bootmgfw.efi"),
+            "Windows Boot Manager"
+        );
+        assert_eq!(
+            loader_label("MICROSOFT", "BOOT\\BOOTMGFW.EFI"),
+            "Windows Boot Manager"
+        );
+        assert_eq!(
+            loader_label("Microsoft", "bootmgfw.efi"),
+            "Microsoft (bootmgfw.efi)"
+        );
+        assert_eq!(
+            loader_label("ubuntu", "shimx64.efi"),
+            "ubuntu (shimx64.efi)"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Firmware boot options.
+//!
+//! Lists the `Boot####` load options referenced by the `BootOrder` variable,
+//! i.e. the entries of the firmware's own boot menu, such as other boot
+//! loaders, the UEFI shell or network boot. Each option is started from its
+//! device path with its optional data as load options, the way the firmware
+//! boot manager would. The option that started plex itself is left out.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::Deserialize;
+use uefi::proto::device_path::{DevicePath, LoadedImageDevicePath};
+
+use super::device_path_text;
+use crate::core::bootables::{BootTarget, FirmwareBootTarget};
+use crate::core::nvram;
+use crate::core::resolver::ResolverCtx;
+use crate::error::AppError;
+use crate::path::open_protocol_get;
+
+/// Options of the `firmware` resolver in `plex.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct FirmwareOptions {
+    /// Whether to also list options that are not active.
+    pub include_inactive: bool,
+    /// Whether to also list options the firmware hides from its boot menu.
+    pub include_hidden: bool,
+    /// Whether to also list application options, such as firmware tools,
+    /// rather than only boot options.
+    pub include_apps: bool,
+}
+
+/// An `EFI_LOAD_OPTION`, as stored in a `Boot####` variable.
+#[derive(Debug)]
+pub struct LoadOption {
+    /// `LOAD_OPTION_*` attribute bits.
+    pub attributes: u32,
+    /// The label shown in the firmware boot menu.
+    pub description: String,
+    /// The first device path of the option, pointing at the image to boot.
+    pub device_path: Box<DevicePath>,
+    /// Data passed to the image as its load options, e.g. the `WINDOWS`
+    /// marker the Windows boot manager expects.
+    pub optional_data: Vec<u8>,
+}
+
+impl LoadOption {
+    /// The option is shown in the boot menu and may be booted.
+    pub const ACTIVE: u32 = 0x0000_0001;
+    /// The option is not shown in the firmware boot menu.
+    pub const HIDDEN: u32 = 0x0000_0008;
+    /// Mask of the category bits.
+    pub const CATEGORY: u32 = 0x0000_1F00;
+    /// Category of applications that are not booted automatically.
+    pub const CATEGORY_APP: u32 = 0x0000_0100;
+
+    /// Parses the contents of a `Boot####` variable.
+    ///
+    /// # Errors
+    /// Returns an error if the data is truncated or the device path is
+    /// malformed.
+    pub fn parse(data: &[u8]) -> Result<Self, AppError> {
+        const INVALID: AppError = AppError::Generic("malformed load option");
+
+        let (header, rest) = data.split_first_chunk::<6>().ok_or(INVALID)?;
+        let attributes = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let path_len = usize::from(u16::from_le_bytes([header[4], header[5]]));
+
+        // The description is a NUL terminated UCS-2 string.
+        let units: Vec<u16> = rest
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .map(|&unit| u16::from_le_bytes(unit))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        let description_len = (units.len() + 1) * 2;
+        let description = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+
+        let path = rest
+            .get(description_len..description_len + path_len)
+            .ok_or(INVALID)?;
+        let device_path = <&DevicePath>::try_from(path).map_err(|_| INVALID)?;
+        // Whatever follows the device paths is optional data.
+        let optional_data = rest[description_len + path_len..].to_vec();
+
+        Ok(Self {
+            attributes,
+            description,
+            device_path: device_path.to_boxed(),
+            optional_data,
+        })
+    }
+
+    /// Whether the option is active, see [`Self::ACTIVE`].
+    #[must_use]
+    pub const fn is_active(&self) -> bool {
+        self.attributes & Self::ACTIVE != 0
+    }
+
+    /// Whether the option is hidden, see [`Self::HIDDEN`].
+    #[must_use]
+    pub const fn is_hidden(&self) -> bool {
+        self.attributes & Self::HIDDEN != 0
+    }
+
+    /// Whether the option is an application, see [`Self::CATEGORY_APP`].
+    #[must_use]
+    pub const fn is_app(&self) -> bool {
+        self.attributes & Self::CATEGORY == Self::CATEGORY_APP
+    }
+
+    /// Whether `opts` lets the option be listed, and if not, why.
+    const fn excluded_by(&self, opts: &FirmwareOptions) -> Option<&'static str> {
+        if !self.is_active() && !opts.include_inactive {
+            Some("inactive")
+        } else if self.is_hidden() && !opts.include_hidden {
+            Some("hidden")
+        } else if self.is_app() && !opts.include_apps {
+            Some("an application")
+        } else {
+            None
+        }
+    }
+}
+
+/// Lists the boot options in `BootOrder` that `opts` allows.
+pub(super) fn resolve(
+    ctx: &ResolverCtx,
+    opts: &FirmwareOptions,
+) -> Result<Vec<BootTarget>, AppError> {
+    let order = nvram::boot_order()?;
+    let current = nvram::boot_current();
+    let own_path = open_protocol_get::<LoadedImageDevicePath>(ctx.image_handle)
+        .ok()
+        .and_then(|path| device_path_text(&path));
+    let mut targets = Vec::new();
+
+    for number in order {
+        let name = format!("Boot{number:04X}");
+        let option = nvram::boot_option(number).and_then(|data| LoadOption::parse(&data));
+        let mut option = match option {
+            Ok(option) => option,
+            Err(e) => {
+                log::warn!("skipping {name}: {e}");
+                continue;
+            }
+        };
+        if let Some(reason) = option.excluded_by(opts) {
+            log::debug!("skipping {name}, it is {reason}");
+            continue;
+        }
+
+        // Firmware often stores paths starting at the partition, which
+        // `LoadImage` does not always expand itself.
+        if let Some(expanded) = ctx
+            .disk_manager
+            .and_then(|dm| dm.expand_device_path(&option.device_path))
+        {
+            option.device_path = expanded;
+        }
+        let is_own = own_path.as_deref().is_some_and(|own| {
+            device_path_text(&option.device_path)
+                .is_some_and(|path| is_own_option(&path, own, current == Some(number)))
+        });
+        if is_own {
+            log::debug!("skipping {name}, it starts plex");
+            continue;
+        }
+
+        targets.push(BootTarget::Firmware(
+            FirmwareBootTarget::new(number, option.description, option.device_path)
+                .with_optional_data(option.optional_data),
+        ));
+    }
+
+    Ok(targets)
+}
+
+/// Whether the option with the device path `path` starts plex, whose image
+/// is at `own`, both as text. An option naming only the disk or partition
+/// plex is on counts if it is the `current` one, i.e. the firmware booted
+/// it to start plex.
+fn is_own_option(path: &str, own: &str, current: bool) -> bool {
+    path == own
+        || (current
+            && own
+                .strip_prefix(path)
+                .is_some_and(|rest| rest.starts_with('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A device path of a single file path node, `\EFI\X.EFI`.
+    const FILE_PATH: [u8; 30] = [
+        0x04, 0x04, 26, 0, b'\\', 0, b'E', 0, b'F', 0, b'I', 0, b'\\', 0, b'X', 0, b'.', 0, b'E',
+        0, b'F', 0, b'I', 0, 0, 0, 0x7F, 0xFF, 4, 0,
+    ];
+
+    fn load_option(attributes: u32, description: &str, optional_data: &[u8]) -> Vec<u8> {
+        let mut data = attributes.to_le_bytes().to_vec();
+        data.extend_from_slice(&u16::try_from(FILE_PATH.len()).unwrap().to_le_bytes());
+        for unit in description.encode_utf16().chain([0]) {
+            data.extend_from_slice(&unit.to_le_bytes());
+        }
+        data.extend_from_slice(&FILE_PATH);
+        data.extend_from_slice(optional_data);
+        data
+    }
+
+    #[test]
+    fn parses_load_options() {
+        let option = LoadOption::parse(&load_option(
+            LoadOption::ACTIVE,
+            "Windows Boot Manager",
+            b"WINDOWS\0",
+        ))
+        .unwrap();
+        assert_eq!(option.description, "Windows Boot Manager");
+        assert_eq!(option.device_path.as_bytes(), FILE_PATH);
+        assert_eq!(option.optional_data, b"WINDOWS\0");
+        assert!(option.is_active());
+        assert!(!option.is_hidden());
+
+        let option = LoadOption::parse(&load_option(0, "", &[])).unwrap();
+        assert_eq!(option.description, "");
+        assert_eq!(option.optional_data, []);
+        assert!(!option.is_active());
+    }
+
+    #[test]
+    fn rejects_malformed_load_options() {
+        assert!(LoadOption::parse(&[1, 0, 0]).is_err());
+
+        // The device path is cut short.
+        let mut data = load_option(LoadOption::ACTIVE, "Shell", &[]);
+        data.truncate(data.len() - 2);
+        assert!(LoadOption::parse(&data).is_err());
+
+        // The device path is not terminated.
+        let mut data = load_option(LoadOption::ACTIVE, "Shell", &[]);
+        let end = data.len() - 4;
+        data[end] = 0x04;
+        assert!(LoadOption::parse(&data).is_err());
+    }
+
+    #[test]
+    fn filters_by_attributes() {
+        let option = |attributes| LoadOption::parse(&load_option(attributes, "Tool", &[])).unwrap();
+        let defaults = FirmwareOptions::default();
+        let everything = FirmwareOptions {
+            include_inactive: true,
+            include_hidden: true,
+            include_apps: true,
+        };
+
+        assert_eq!(option(LoadOption::ACTIVE).excluded_by(&defaults), None);
+        for attributes in [
+            0,
+            LoadOption::ACTIVE | LoadOption::HIDDEN,
+            LoadOption::ACTIVE | LoadOption::CATEGORY_APP,
+        ] {
+            assert!(option(attributes).excluded_by(&defaults).is_some());
+            assert_eq!(option(attributes).excluded_by(&everything), None);
+        }
+        assert!(
+            toml::from_str::<FirmwareOptions>("include_hidden = true")
+                .unwrap()
+                .include_hidden
+        );
+    }
+
+    #[test]
+    fn recognizes_own_option() {
+        let own = "PCIROOT(0X0)/PCI(0X1,0X1)/HD(1,GPT,ABC)/\\EFI\\PLEX\\PLEX.EFI";
+        let partition = "PCIROOT(0X0)/PCI(0X1,0X1)/HD(1,GPT,ABC)";
+
+        assert!(is_own_option(own, own, false));
+        assert!(is_own_option(partition, own, true));
+        assert!(!is_own_option(partition, own, false));
+        assert!(!is_own_option(
+            "PCIROOT(0X0)/PCI(0X1,0X1)/HD(1,GPT,ABC)/\\EFI\\BOOT\\BOOTX64.EFI",
+            own,
+            true
+        ));
+        assert!(!is_own_option("PCIROOT(0X0)/PCI(0X1,0X1)/HD(1", own, true));
+    }
+}
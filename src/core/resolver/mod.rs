@@ -0,0 +1,197 @@
+//! Resolver pipeline for boot entries.
+//!
+//! Resolvers discover boot targets at runtime, e.g. from files installed
+//! by the distribution, so they do not have to be listed in `plex.toml`.
+//! Which resolvers run, in what order and with which options is configured
+//! by the `[[resolvers]]` section, see [`run_all`].
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::Deserialize;
+use uefi::proto::device_path::text::{AllowShortcuts, DisplayOnly};
+use uefi::proto::device_path::DevicePath;
+
+use crate::core::app::DisplayEntry;
+use crate::core::bootables::BootTarget;
+use crate::error::AppError;
+use crate::path::DiskManager;
+
+pub mod bls;
+pub mod efi;
+pub mod firmware;
+pub mod uki;
+
+/// Runtime context provided to resolvers.
+pub struct ResolverCtx<'a> {
+    /// Disk access helpers for resolution.
+    pub disk_manager: Option<&'a DiskManager>,
+    /// Image handle for UEFI service calls.
+    pub image_handle: uefi::Handle,
+}
+
+/// Plug-in interface for boot entry discovery.
+///
+/// Configured as a `[[resolvers]]` entry in `plex.toml`, selected by its
+/// `name` with the remaining keys as resolver specific options:
+///
+/// ```toml
+/// [[resolvers]]
+/// name = "bls"
+/// xbootldr = false
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum Resolver {
+    /// Boot Loader Specification Type #1 entries, see [`bls`].
+    Bls(bls::BlsOptions),
+    /// Unified Kernel Images in `\EFI\Linux`, see [`uki`].
+    Uki(uki::UkiOptions),
+    /// Boot loaders in `\EFI\*` and the removable media path, see [`efi`].
+    Efi(efi::EfiOptions),
+    /// The firmware's `Boot####` options, see [`firmware`].
+    Firmware(firmware::FirmwareOptions),
+}
+
+impl Resolver {
+    /// The `bls` and `uki` resolvers with their default options, which find
+    /// the kernels distributions install. Discovery is opt-in, so these only
+    /// run if listed in `[[resolvers]]` or as part of [`Self::fallback`].
+    #[must_use]
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::Bls(bls::BlsOptions::default()),
+            Self::Uki(uki::UkiOptions::default()),
+        ]
+    }
+
+    /// The resolvers used when `plex.toml` cannot be loaded, which find
+    /// anything that might be bootable.
+    #[must_use]
+    pub fn fallback() -> Vec<Self> {
+        let mut resolvers = Self::defaults();
+        resolvers.push(Self::Efi(efi::EfiOptions::default()));
+        resolvers.push(Self::Firmware(firmware::FirmwareOptions::default()));
+        resolvers
+    }
+
+    /// A short name identifying the resolver in logs.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Bls(_) => "bls",
+            Self::Uki(_) => "uki",
+            Self::Efi(_) => "efi",
+            Self::Firmware(_) => "firmware",
+        }
+    }
+
+    /// Discovers boot targets.
+    ///
+    /// # Errors
+    /// Returns an error if the resolver cannot run at all. Problems with
+    /// individual entries are logged and the entry is skipped instead.
+    pub fn resolve(&self, ctx: &ResolverCtx) -> Result<Vec<BootTarget>, AppError> {
+        match self {
+            Self::Bls(opts) => bls::resolve(ctx, opts),
+            Self::Uki(opts) => uki::resolve(ctx, opts),
+            Self::Efi(opts) => efi::resolve(ctx, opts),
+            Self::Firmware(opts) => firmware::resolve(ctx, opts),
+        }
+    }
+}
+
+/// Runs `resolvers` in order and appends the discovered targets to `targets`.
+///
+/// A target is dropped if its executable resolves to the same device path
+/// and it has the same command line as one already in `targets`, so entries
+/// from `plex.toml` take precedence over discovered ones, and earlier
+/// resolvers over later ones. Entries booting one kernel with different
+/// options are kept. A resolver that fails is logged as a warning and
+/// skipped.
+pub fn run_all(resolvers: &[Resolver], ctx: &ResolverCtx, targets: &mut Vec<BootTarget>) {
+    let mut seen: Vec<String> = targets
+        .iter()
+        .filter_map(|target| dedup_key(ctx, target))
+        .collect();
+
+    for resolver in resolvers {
+        let found = match resolver.resolve(ctx) {
+            Ok(found) => found,
+            Err(e) => {
+                log::warn!("{} resolver failed: {e}", resolver.name());
+                continue;
+            }
+        };
+
+        let total = found.len();
+        let mut added = 0;
+        for target in found {
+            if let Some(key) = dedup_key(ctx, &target) {
+                if seen.contains(&key) {
+                    log::debug!("{} resolver: skipping duplicate {key}", resolver.name());
+                    continue;
+                }
+                seen.push(key);
+            }
+            targets.push(target);
+            added += 1;
+        }
+
+        log::info!(
+            "{} resolver discovered {total} entries, {added} new",
+            resolver.name()
+        );
+    }
+}
+
+/// Resolves the target's executable to a device path and renders it as
+/// text for comparison, followed by the command line. FAT paths are case
+/// insensitive, so the path is upper cased. Returns `None` if the path
+/// cannot be resolved.
+fn dedup_key(ctx: &ResolverCtx, target: &BootTarget) -> Option<String> {
+    let path = device_path_text(&target.device_path(ctx.disk_manager?).ok()?)?;
+    Some(with_cmdline(path, target.cmdline().as_deref()))
+}
+
+/// Appends `cmdline` to the device path text `path`, see [`dedup_key`].
+/// An empty command line is the same as none, e.g. for firmware entries.
+fn with_cmdline(mut path: String, cmdline: Option<&str>) -> String {
+    if let Some(cmdline) = cmdline.filter(|cmdline| !cmdline.is_empty()) {
+        // Device path text never contains a newline.
+        path.push('\n');
+        path.push_str(cmdline);
+    }
+    path
+}
+
+/// Renders `device_path` as upper cased text, see [`dedup_key`].
+fn device_path_text(device_path: &DevicePath) -> Option<String> {
+    let text = device_path
+        .to_string(DisplayOnly(false), AllowShortcuts(false))
+        .ok()?;
+    Some(String::from(&text).to_ascii_uppercase())
+}
+
+pub(crate) fn require_disk_manager<'a>(ctx: &ResolverCtx<'a>) -> Result<&'a DiskManager, AppError> {
+    ctx.disk_manager
+        .ok_or(AppError::Generic("resolver requires disk access"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedups_by_path_and_cmdline() {
+        let path = || String::from("HD(1,GPT,0A3407DE)/\\VMLINUZ");
+        let key = |cmdline| with_cmdline(path(), cmdline);
+
+        assert_eq!(key(Some("rw quiet")), key(Some("rw quiet")));
+        assert_ne!(key(Some("rw quiet")), key(Some("rw single")));
+        assert_eq!(key(Some("")), key(None));
+        assert_eq!(key(None), path());
+    }
+}
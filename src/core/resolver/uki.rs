@@ -0,0 +1,307 @@
+//! Unified Kernel Image discovery.
+//!
+//! Scans `\EFI\Linux\*.efi` on every partition and labels each image using
+//! the `os-release` data, command line and kernel version embedded in its
+//! `.osrel`, `.cmdline` and `.uname` PE sections.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::Deserialize;
+use uefi::proto::media::file::RegularFile;
+use uefi::{CStr16, CString16};
+
+use crate::core::bootables::{BootTarget, GenericBootTarget};
+use crate::core::resolver::{require_disk_manager, ResolverCtx};
+use crate::error::AppError;
+use crate::helpers::text::strip_suffix_ignore_case;
+use crate::path::{Partition, PartitionReference, PathReference};
+use crate::pe::PeSections;
+
+/// Options of the `uki` resolver in `plex.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct UkiOptions {
+    /// Directory holding UKIs, relative to the partition root.
+    pub directory: String,
+    /// Whether to only look on the ESP instead of every partition.
+    pub esp_only: bool,
+}
+
+impl Default for UkiOptions {
+    fn default() -> Self {
+        Self {
+            directory: String::from("\\EFI\\Linux"),
+            esp_only: false,
+        }
+    }
+}
+
+/// Bytes read up front to find the section table, enough for typical images.
+const HEADER_PROBE_LEN: usize = 1024;
+
+/// Upper bound for the headers and each metadata section, so a corrupt
+/// image cannot make us allocate unbounded memory.
+const MAX_METADATA_LEN: usize = 64 * 1024;
+
+/// Metadata embedded in a Unified Kernel Image.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UkiInfo {
+    /// File name of the image without the `.efi` suffix.
+    pub id: String,
+    /// Key/value pairs of the embedded `os-release` file.
+    pub os_release: Vec<(String, String)>,
+    /// The embedded kernel command line.
+    pub cmdline: Option<String>,
+    /// The embedded kernel version.
+    pub uname: Option<String>,
+}
+
+impl UkiInfo {
+    /// Looks up an `os-release` field, e.g. `PRETTY_NAME`.
+    #[must_use]
+    pub fn os_release_field(&self, key: &str) -> Option<&str> {
+        self.os_release
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The pretty name of the OS, which groups the images of one OS in the
+    /// boot menu.
+    #[must_use]
+    pub fn os_name(&self) -> Option<&str> {
+        self.os_release_field("PRETTY_NAME")
+            .or_else(|| self.os_release_field("NAME"))
+    }
+
+    /// The label shown in the boot menu, the pretty OS name followed by
+    /// the kernel or OS version.
+    ///
+    /// # Example
+    /// ```
+    /// use plex_boot::core::resolver::uki::{parse_os_release, UkiInfo};
+    /// let info = UkiInfo {
+    ///     id: "arch-linux".into(),
+    ///     os_release: parse_os_release("PRETTY_NAME=\"Arch Linux\"\n"),
+    ///     cmdline: None,
+    ///     uname: Some("6.9.1-arch1-1".into()),
+    /// };
+    /// assert_eq!(info.label(), "Arch Linux (6.9.1-arch1-1)");
+    /// ```
+    #[must_use]
+    pub fn label(&self) -> String {
+        let title = self.os_name().unwrap_or(&self.id);
+        let version = self
+            .uname
+            .as_deref()
+            .or_else(|| self.os_release_field("VERSION_ID"))
+            .or_else(|| self.os_release_field("IMAGE_VERSION"));
+
+        version.map_or_else(
+            || title.to_string(),
+            |version| format!("{title} ({version})"),
+        )
+    }
+
+    fn into_boot_target(
+        self,
+        location: &PartitionReference,
+        path: String,
+    ) -> Result<BootTarget, AppError> {
+        let executable = PathReference {
+            location: location.clone(),
+            path,
+        };
+
+        // The stub falls back to the embedded command line, passing it along
+        // unchanged keeps the two in sync while allowing it to be edited.
+        let options = self.cmdline.clone().unwrap_or_default();
+        let target =
+            GenericBootTarget::new(self.label(), executable.to_uri(), options)?.with_id(&self.id);
+        Ok(BootTarget::Generic(match self.os_name() {
+            Some(name) => target.with_group(name),
+            None => target,
+        }))
+    }
+}
+
+/// Parses an `os-release` file into key/value pairs, unquoting values.
+///
+/// Comments, blank lines and malformed lines are skipped.
+#[must_use]
+pub fn parse_os_release(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), unquote(value.trim())))
+        .collect()
+}
+
+fn unquote(value: &str) -> String {
+    let inner = ['"', '\'']
+        .iter()
+        .find_map(|&q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)));
+    let Some(inner) = inner else {
+        return value.to_string();
+    };
+
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Discovers UKIs on all partitions, or only on the ESP if configured.
+pub(super) fn resolve(ctx: &ResolverCtx, opts: &UkiOptions) -> Result<Vec<BootTarget>, AppError> {
+    let dm = require_disk_manager(ctx)?;
+    let dir = CString16::try_from(opts.directory.as_str())?;
+    let mut targets = Vec::new();
+
+    for partition in dm
+        .partitions()
+        .iter()
+        .filter(|p| !opts.esp_only || p.is_esp())
+    {
+        let Some(location) = partition.reference() else {
+            continue;
+        };
+
+        let names = match list_images(partition, &dir) {
+            Ok(names) => names,
+            Err(e) => {
+                log::debug!("no UKIs on {}: {e}", location.to_uri_prefix());
+                continue;
+            }
+        };
+
+        for (file_name, id) in names {
+            let path = format!("{dir}\\{file_name}");
+            match read_uki(partition, &path, id)
+                .and_then(|info| info.into_boot_target(&location, path.clone()))
+            {
+                Ok(target) => targets.push(target),
+                Err(e) => log::warn!("skipping {path}: {e}"),
+            }
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Lists `(file name, id)` pairs of the `.efi` files in `dir`.
+fn list_images(partition: &Partition, dir: &CStr16) -> Result<Vec<(String, String)>, AppError> {
+    let mut fs = partition.file_system()?;
+    let mut names: Vec<(String, String)> = fs
+        .read_dir(dir)?
+        .filter_map(Result::ok)
+        .filter(|info| info.is_regular_file())
+        .filter_map(|info| {
+            let name = info.file_name().to_string();
+            let id = strip_suffix_ignore_case(&name, ".efi")?.to_string();
+            Some((name, id))
+        })
+        .collect();
+
+    // Directory order is arbitrary, keep the menu stable between boots.
+    names.sort();
+    Ok(names)
+}
+
+fn read_uki(partition: &Partition, path: &str, id: String) -> Result<UkiInfo, AppError> {
+    let mut file = partition.open_file(&CString16::try_from(path)?)?;
+
+    let mut headers = read_at(&mut file, 0, HEADER_PROBE_LEN)?;
+    let headers_len = PeSections::headers_len(&headers)?;
+    if headers_len > headers.len() {
+        if headers_len > MAX_METADATA_LEN {
+            return Err(AppError::Generic("PE headers too large"));
+        }
+        headers = read_at(&mut file, 0, headers_len)?;
+    }
+
+    let sections = PeSections::parse(&headers)?;
+    if sections.find(".linux").is_none() {
+        return Err(AppError::Generic("not a unified kernel image"));
+    }
+
+    let mut read_section = |name: &str| -> Result<Option<String>, AppError> {
+        let Some(section) = sections.find(name) else {
+            return Ok(None);
+        };
+        let range = section.file_range();
+        let len = range.len().min(MAX_METADATA_LEN);
+        let data = read_at(&mut file, range.start as u64, len)?;
+        // Sections are NUL padded, and the command line often ends in one.
+        let text = String::from_utf8_lossy(&data);
+        Ok(Some(text.trim_end_matches('\0').trim().to_string()))
+    };
+
+    Ok(UkiInfo {
+        os_release: read_section(".osrel")?
+            .map(|s| parse_os_release(&s))
+            .unwrap_or_default(),
+        cmdline: read_section(".cmdline")?,
+        uname: read_section(".uname")?,
+        id,
+    })
+}
+
+/// Reads up to `len` bytes starting at `offset`, stopping early at EOF.
+fn read_at(file: &mut RegularFile, offset: u64, len: usize) -> Result<Vec<u8>, AppError> {
+    file.set_position(offset)?;
+    let mut buf = vec![0; len];
+    let mut filled = 0;
+    while filled < len {
+        let read = file
+            .read(&mut buf[filled..])
+            .map_err(|e| uefi::Error::from(e.status()))?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    buf.truncate(filled);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_os_release() {
+        let fields = parse_os_release(
+            "# comment\nNAME=\"Fedora Linux\"\nVERSION_ID=40\n\nPRETTY_NAME='Fedora \\'40\\''\nbogus\n",
+        );
+        assert_eq!(
+            fields,
+            [
+                ("NAME".to_string(), "Fedora Linux".to_string()),
+                ("VERSION_ID".to_string(), "40".to_string()),
+                ("PRETTY_NAME".to_string(), "Fedora '40'".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn label_falls_back_to_os_version_and_id() {
+        let mut info = UkiInfo {
+            id: "fedora".to_string(),
+            os_release: parse_os_release("NAME=Fedora\nVERSION_ID=40\n"),
+            ..UkiInfo::default()
+        };
+        assert_eq!(info.label(), "Fedora (40)");
+
+        info.os_release.clear();
+        assert_eq!(info.label(), "fedora");
+    }
+}
@@ -0,0 +1,213 @@
+//! Reading the machine identity from the SMBIOS tables.
+//!
+//! The firmware publishes the SMBIOS structure table through the UEFI
+//! configuration table. Plex only needs the System Information structure
+//! (type 1), which names the manufacturer and model of the machine and
+//! carries its serial number and UUID, to pick a machine profile, see
+//! [`crate::config::machine`].
+
+use alloc::string::String;
+use uefi::table::cfg::ConfigTableEntry;
+use uefi::Guid;
+
+/// Anchor of the 64-bit SMBIOS 3 entry point.
+const SMBIOS3_ANCHOR: &[u8] = b"_SM3_";
+/// Anchor of the 32-bit SMBIOS 2 entry point.
+const SMBIOS_ANCHOR: &[u8] = b"_SM_";
+/// Bytes of either entry point read to locate the structure table.
+const ENTRY_POINT_LEN: usize = 0x1F;
+
+/// Type of the System Information structure.
+const SYSTEM_INFORMATION: u8 = 1;
+/// Type of the structure marking the end of the table.
+const END_OF_TABLE: u8 = 127;
+
+/// The System Information structure, with unset strings left empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Smbios {
+    /// System manufacturer, e.g. `LENOVO`.
+    pub manufacturer: String,
+    /// Product name, e.g. `20XWCTO1WW`.
+    pub product: String,
+    /// Serial number of the system.
+    pub serial: String,
+    /// System UUID, `None` if the firmware leaves it unset.
+    pub uuid: Option<Guid>,
+}
+
+impl Smbios {
+    /// Reads the System Information structure published by the firmware.
+    ///
+    /// Returns `None` if the firmware has no SMBIOS tables or they lack the
+    /// structure.
+    #[must_use]
+    pub fn detect() -> Option<Self> {
+        let entry_point = uefi::system::with_config_table(|tables| {
+            // Prefer the 64-bit entry point, the 32-bit one may be missing
+            // or point to a truncated table on newer firmware.
+            [
+                ConfigTableEntry::SMBIOS3_GUID,
+                ConfigTableEntry::SMBIOS_GUID,
+            ]
+            .iter()
+            .find_map(|guid| tables.iter().find(|t| t.guid == *guid))
+            .map(|t| t.address.cast::<u8>())
+        })?;
+
+        // SAFETY: the firmware identity maps memory while boot services are
+        // active, and both entry points are at least this long.
+        let entry_point = unsafe { core::slice::from_raw_parts(entry_point, ENTRY_POINT_LEN) };
+        let (address, len) = table_location(entry_point)?;
+        let address = usize::try_from(address).ok()?;
+        // SAFETY: the entry point describes the table, which the firmware
+        // keeps mapped as reserved memory.
+        let table = unsafe { core::slice::from_raw_parts(address as *const u8, len) };
+        system_information(table)
+    }
+}
+
+/// Finds the address and maximum length of the structure table from an
+/// SMBIOS 3 or SMBIOS 2 entry point.
+fn table_location(entry_point: &[u8]) -> Option<(u64, usize)> {
+    if entry_point.starts_with(SMBIOS3_ANCHOR) {
+        let len = u32::from_le_bytes(entry_point.get(0x0C..0x10)?.try_into().ok()?);
+        let address = u64::from_le_bytes(entry_point.get(0x10..0x18)?.try_into().ok()?);
+        Some((address, usize::try_from(len).ok()?))
+    } else if entry_point.starts_with(SMBIOS_ANCHOR) {
+        let len = u16::from_le_bytes(entry_point.get(0x16..0x18)?.try_into().ok()?);
+        let address = u32::from_le_bytes(entry_point.get(0x18..0x1C)?.try_into().ok()?);
+        Some((u64::from(address), usize::from(len)))
+    } else {
+        None
+    }
+}
+
+/// Finds and decodes the System Information structure in a structure table.
+fn system_information(mut table: &[u8]) -> Option<Smbios> {
+    while let [kind, len, ..] = *table {
+        let len = usize::from(len);
+        if len < 4 || len > table.len() {
+            return None;
+        }
+        // The formatted area is followed by its strings, each terminated by
+        // a NUL, and another NUL ending the set.
+        let strings_len = table[len..].windows(2).position(|w| w == [0, 0])?;
+        let (formatted, strings) = (&table[..len], &table[len..len + strings_len]);
+
+        match kind {
+            SYSTEM_INFORMATION => return Some(decode_system_information(formatted, strings)),
+            END_OF_TABLE => return None,
+            _ => table = &table[len + strings_len + 2..],
+        }
+    }
+    None
+}
+
+fn decode_system_information(formatted: &[u8], strings: &[u8]) -> Smbios {
+    // Strings are referenced by their 1-based number, 0 means unset.
+    let string = |offset: usize| {
+        let number = usize::from(formatted.get(offset).copied().unwrap_or(0));
+        strings
+            .split(|&b| b == 0)
+            .nth(number.wrapping_sub(1))
+            .map(|s| String::from(String::from_utf8_lossy(s).trim()))
+            .unwrap_or_default()
+    };
+    // All zeroes means the UUID is not present, all ones that it is not set.
+    let uuid = formatted
+        .get(0x08..0x18)
+        .and_then(|bytes| <[u8; 16]>::try_from(bytes).ok())
+        .filter(|bytes| bytes.iter().any(|&b| b != 0) && bytes.iter().any(|&b| b != 0xFF))
+        // Since SMBIOS 2.6 the first three fields are little endian, which
+        // is the byte order of an EFI GUID.
+        .map(Guid::from_bytes);
+
+    Smbios {
+        manufacturer: string(0x04),
+        product: string(0x05),
+        serial: string(0x07),
+        uuid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// A structure of type `kind` with the given formatted area after the
+    /// header, and strings.
+    fn structure(kind: u8, data: &[u8], strings: &[&str]) -> Vec<u8> {
+        let mut out = alloc::vec![kind, u8::try_from(data.len() + 4).unwrap(), 0, 0];
+        out.extend_from_slice(data);
+        for s in strings {
+            out.extend_from_slice(s.as_bytes());
+            out.push(0);
+        }
+        if strings.is_empty() {
+            out.push(0);
+        }
+        out.push(0);
+        out
+    }
+
+    #[test]
+    fn decodes_system_information() {
+        let mut system = alloc::vec![1, 2, 3, 4];
+        system.extend_from_slice(&[
+            0xde, 0x07, 0x34, 0x0a, 0x4b, 0x01, 0x8b, 0x45, 0xb5, 0xc1, 0x84, 0x8e, 0x92, 0xa3,
+            0x27, 0xa3,
+        ]);
+        system.extend_from_slice(&[6, 0, 0]);
+
+        let mut table = structure(0, &[1, 2, 0, 0], &["Vendor", "1.0"]);
+        table.extend(structure(
+            1,
+            &system,
+            &["LENOVO ", "20XWCTO1WW", "ThinkPad X1", "PF2ABCDE"],
+        ));
+        table.extend(structure(END_OF_TABLE, &[], &[]));
+
+        assert_eq!(
+            system_information(&table),
+            Some(Smbios {
+                manufacturer: String::from("LENOVO"),
+                product: String::from("20XWCTO1WW"),
+                serial: String::from("PF2ABCDE"),
+                uuid: Some(uefi::guid!("0a3407de-014b-458b-b5c1-848e92a327a3")),
+            })
+        );
+    }
+
+    #[test]
+    fn handles_missing_and_unset_fields() {
+        let mut system = alloc::vec![1, 0, 0, 0];
+        system.extend_from_slice(&[0xFF; 16]);
+        let table = structure(1, &system, &["QEMU"]);
+        let smbios = system_information(&table).unwrap();
+        assert_eq!(smbios.manufacturer, "QEMU");
+        assert_eq!(smbios.product, "");
+        assert_eq!(smbios.uuid, None);
+
+        assert_eq!(system_information(&structure(END_OF_TABLE, &[], &[])), None);
+        // A structure claiming to be longer than the table.
+        assert_eq!(system_information(&[1, 0x40, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn locates_table() {
+        let mut smbios3 = alloc::vec![0; ENTRY_POINT_LEN];
+        smbios3[..5].copy_from_slice(SMBIOS3_ANCHOR);
+        smbios3[0x0C..0x10].copy_from_slice(&0x1234_u32.to_le_bytes());
+        smbios3[0x10..0x18].copy_from_slice(&0x7F00_0000_u64.to_le_bytes());
+        assert_eq!(table_location(&smbios3), Some((0x7F00_0000, 0x1234)));
+
+        let mut smbios = alloc::vec![0; ENTRY_POINT_LEN];
+        smbios[..4].copy_from_slice(SMBIOS_ANCHOR);
+        smbios[0x16..0x18].copy_from_slice(&0x0456_u16.to_le_bytes());
+        smbios[0x18..0x1C].copy_from_slice(&0x000F_0000_u32.to_le_bytes());
+        assert_eq!(table_location(&smbios), Some((0x000F_0000, 0x0456)));
+
+        assert_eq!(table_location(&[0; ENTRY_POINT_LEN]), None);
+    }
+}
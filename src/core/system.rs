@@ -0,0 +1,61 @@
+//! Facts about the machine plex runs on.
+//!
+//! Collected once at startup to pick the entries and the machine profile
+//! that apply to this machine, see [`crate::config::condition`] and
+//! [`crate::config::machine`].
+
+use alloc::string::String;
+
+use crate::core::nvram;
+use crate::core::smbios::Smbios;
+
+/// The architecture plex was built for, named like Rust's `target_arch`.
+pub const ARCH: &str = if cfg!(target_arch = "x86_64") {
+    "x86_64"
+} else if cfg!(target_arch = "x86") {
+    "x86"
+} else if cfg!(target_arch = "aarch64") {
+    "aarch64"
+} else if cfg!(target_arch = "arm") {
+    "arm"
+} else if cfg!(target_arch = "riscv64") {
+    "riscv64"
+} else if cfg!(target_arch = "loongarch64") {
+    "loongarch64"
+} else {
+    "unknown"
+};
+
+/// What the firmware tells us about the machine.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemInfo {
+    /// CPU architecture, see [`ARCH`].
+    pub arch: &'static str,
+    /// Whether Secure Boot is enabled.
+    pub secure_boot: bool,
+    /// Firmware vendor from the system table, e.g. `EDK II`.
+    pub firmware_vendor: String,
+    /// Vendor specific firmware revision from the system table.
+    pub firmware_revision: u32,
+    /// The System Information structure from the SMBIOS tables, if the
+    /// firmware publishes it.
+    pub smbios: Option<Smbios>,
+    /// Whether the firmware can be asked to stop in its setup on the next
+    /// start.
+    pub firmware_setup: bool,
+}
+
+impl SystemInfo {
+    /// Reads the facts from the firmware.
+    #[must_use]
+    pub fn detect() -> Self {
+        Self {
+            arch: ARCH,
+            secure_boot: nvram::secure_boot_enabled(),
+            firmware_vendor: String::from(uefi::system::firmware_vendor()),
+            firmware_revision: uefi::system::firmware_revision(),
+            smbios: Smbios::detect(),
+            firmware_setup: nvram::boot_to_firmware_ui_supported(),
+        }
+    }
+}
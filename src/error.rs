@@ -15,6 +15,10 @@ pub enum AppError {
     #[error(transparent)]
     PathRef(#[from] crate::path::PathRefParseError),
     #[error(transparent)]
+    Partition(#[from] crate::path::PartitionError),
+    #[error(transparent)]
+    Pe(#[from] crate::pe::PeError),
+    #[error(transparent)]
     Builder(#[from] uefi::proto::device_path::build::BuildError),
     #[error(transparent)]
     Path(#[from] uefi::proto::device_path::DevicePathUtilitiesError),
@@ -1,4 +1,5 @@
 pub mod logger;
+pub mod text;
 
 #[cfg(feature = "panic_handler")]
 pub mod panic_handler;
@@ -0,0 +1,22 @@
+/// Strips an ASCII suffix regardless of case, FAT file names are case
+/// insensitive.
+pub(crate) fn strip_suffix_ignore_case<'a>(name: &'a str, suffix: &str) -> Option<&'a str> {
+    let idx = name.len().checked_sub(suffix.len())?;
+    (name.is_char_boundary(idx) && name[idx..].eq_ignore_ascii_case(suffix)).then(|| &name[..idx])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_suffixes_regardless_of_case() {
+        assert_eq!(strip_suffix_ignore_case("arch.conf", ".conf"), Some("arch"));
+        assert_eq!(strip_suffix_ignore_case("LINUX.EFI", ".efi"), Some("LINUX"));
+        assert_eq!(strip_suffix_ignore_case(".efi", ".efi"), Some(""));
+        assert_eq!(strip_suffix_ignore_case("efi", ".efi"), None);
+        assert_eq!(strip_suffix_ignore_case("linux.efi.bak", ".efi"), None);
+        // A suffix that would split a multi-byte character never matches.
+        assert_eq!(strip_suffix_ignore_case("ké", "xx"), None);
+    }
+}
@@ -27,6 +27,7 @@ pub mod core;
 pub mod error;
 pub mod helpers;
 pub mod path;
+pub mod pe;
 pub mod ui;
 
 pub use error::AppError;
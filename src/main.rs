@@ -8,13 +8,22 @@
 //! boot menu.
 
 extern crate alloc;
+use alloc::vec;
+use core::time::Duration;
 use log::info;
-use plex_boot::config::Config;
+use plex_boot::config::loader::ImageFiles;
+use plex_boot::config::validate::Diagnostic;
+use plex_boot::config::{Config, DefaultEntry};
 use plex_boot::core::app::{App, AppCtx, AppResult};
-use plex_boot::core::bootables::BootTarget;
-use plex_boot::core::display::GopDisplay;
+use plex_boot::core::bootables::{self, BootTarget};
+use plex_boot::core::display::{ConsoleMode, Display, GopDisplay, TextDisplay};
+use plex_boot::core::input::{self, UefiInput};
+use plex_boot::core::nvram;
+use plex_boot::core::resolver::{self, ResolverCtx};
+use plex_boot::core::system::SystemInfo;
 use plex_boot::path::DiskManager;
 use plex_boot::ui;
+use plex_boot::ui::boot_menu::{BootMenu, MenuMode};
 use uefi::{prelude::*, proto::console::gop::GraphicsOutput};
 
 #[entry]
@@ -22,46 +31,153 @@ fn main() -> Status {
     uefi::helpers::init().unwrap();
     info!("Initialized UEFI helpers successfully.");
 
+    let handle = boot::image_handle();
+    let mut disk_manager = DiskManager::new(handle).unwrap();
+
     const CONFIG_PATH: &str = "\\plex.toml";
-    let config = match Config::load_from_file(CONFIG_PATH) {
-        Ok(cfg) => cfg,
-        Err(e) => {
-            log::error!("Failed to load config from {}: {:?}", CONFIG_PATH, e);
-            return Status::LOAD_ERROR;
-        }
-    };
+    // A broken config must not leave the machine unbootable, so fall back
+    // to discovered entries and tell the user once the UI is up.
+    let (mut config, diagnostics) =
+        match Config::load(&ImageFiles, CONFIG_PATH, Some(&disk_manager)) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                log::error!(
+                    "Failed to load config from {CONFIG_PATH}: {e}, using discovered entries"
+                );
+                (Config::fallback(), vec![Diagnostic::from(e)])
+            }
+        };
+    for diagnostic in &diagnostics {
+        log::warn!("{CONFIG_PATH}: {diagnostic}");
+    }
+    disk_manager.set_search_order(config.search_order.clone());
+
+    let system = SystemInfo::detect();
+    config.retain_matching(&system, &disk_manager);
+    let machine = config.select_machine(&system);
+    let profile = machine.as_ref().map(|m| m.display_name());
 
     info!(
         "Loaded {} boot targets from config",
         config.boot_targets.len()
     );
 
-    let handle = boot::image_handle();
-    let disk_manager = DiskManager::new(handle).unwrap();
-
     let theme = config.theme;
+    let console = config.console;
+    let timeout = config.timeout;
+    let default = config.default.clone();
+    // Without "@saved" the last booted entry is never read, so it is not
+    // written either.
+    let remember = default == DefaultEntry::Saved;
+    let menu_mode = config.menu;
+    let reveal_window = Duration::from_millis(config.reveal_window);
+    let resolvers = config.resolvers.clone();
     let mut boot_targets = config.into_boot_targets();
 
-    let gop_handle = boot::get_handle_for_protocol::<GraphicsOutput>().unwrap();
-    let mut gop = boot::open_protocol_exclusive::<GraphicsOutput>(gop_handle).unwrap();
+    let resolver_ctx = ResolverCtx {
+        disk_manager: Some(&disk_manager),
+        image_handle: handle,
+    };
+    resolver::run_all(&resolvers, &resolver_ctx, &mut boot_targets);
+    if let Some(machine) = &machine {
+        machine.apply(&mut boot_targets);
+    }
+    let mut boot_targets = bootables::fold_groups(boot_targets);
+
+    // An entry picked with Ctrl+Enter before the reboot is booted once, without
+    // becoming the last booted entry, and a hidden menu boots the default
+    // entry. Both happen before any graphics are set up, so the firmware logo
+    // stays on screen. The menu is still shown, without a countdown, if the
+    // entry is missing or fails to boot.
+    let direct = match nvram::take_one_shot() {
+        Some(id) => Some((DefaultEntry::Id(id), false)),
+        None if menu_mode == MenuMode::Hidden => {
+            if input::reveal_requested(reveal_window) {
+                info!("menu revealed");
+                None
+            } else {
+                Some((default.clone(), remember))
+            }
+        }
+        None => None,
+    };
+    let mut boot_error = None;
+    if let Some((entry, remember)) = direct {
+        match BootMenu::default_target(&mut boot_targets, &entry) {
+            Some(target) => match target.boot(handle, &disk_manager, remember) {
+                Ok(()) => return Status::SUCCESS,
+                Err(e) => boot_error = Some(e),
+            },
+            None => log::warn!("entry {entry:?} not found, showing the menu"),
+        }
+    }
+    let timeout = if boot_error.is_some() { None } else { timeout };
+
+    let mut gop = match console {
+        ConsoleMode::Text => None,
+        ConsoleMode::Auto => boot::get_handle_for_protocol::<GraphicsOutput>()
+            .and_then(boot::open_protocol_exclusive::<GraphicsOutput>)
+            .inspect_err(|e| log::warn!("no usable GOP, using the text console: {e}"))
+            .ok(),
+    };
+    let mut gop_display = gop.as_deref_mut().map(GopDisplay::new);
 
     // Using with_stdin ensures we choose the multiplexed EFI input device,
     // otherwise we might pick up a specific device that is not the main keyboard
-    // and miss inputs.
+    // and miss inputs. Likewise with_stdout reaches every console, including
+    // serial lines.
     uefi::system::with_stdin(|input| {
-        let mut display = GopDisplay::new(&mut gop);
-        let mut app_ctx = AppCtx {
-            display: &mut display,
-            input,
-            disk_manager: &disk_manager,
-            handle,
-        };
-        let mut menu =
-            ui::boot_menu::BootMenu::<BootTarget>::new(boot_targets.as_mut_slice(), theme);
-        if let AppResult::Error(ref err) = menu.run(&mut app_ctx) {
-            let mut overlay = ui::overlay::ErrorOverlay::new(err, theme);
-            let _ = overlay.run(&mut app_ctx);
-        }
+        uefi::system::with_stdout(|output| {
+            let mut display = match gop_display.as_mut() {
+                Some(framebuffer) => Display::Graphics(framebuffer),
+                None => match TextDisplay::new(output) {
+                    Ok(display) => {
+                        // Log messages would scroll the menu off the console.
+                        log::set_max_level(log::LevelFilter::Off);
+                        Display::Text(display)
+                    }
+                    Err(e) => {
+                        log::error!("no usable console: {e}");
+                        return;
+                    }
+                },
+            };
+            let mut input = UefiInput::new(input);
+            let mut app_ctx = AppCtx {
+                display: &mut display,
+                input: &mut input,
+                disk_manager: &disk_manager,
+                handle,
+                remember_last_booted: remember,
+            };
+            let mut menu =
+                BootMenu::<BootTarget>::new(boot_targets.as_mut_slice(), theme, timeout, &default)
+                    .with_system_info(&system, profile.as_deref());
+            if let Some(err) = &boot_error {
+                let _ = menu.draw(&mut app_ctx);
+                let _ = ui::overlay::ErrorOverlay::new(err, theme).run(&mut app_ctx);
+            }
+            if !diagnostics.is_empty() {
+                // The overlay uses up the timeout, so a warning does not keep
+                // an unattended machine from booting. It only waits for the
+                // user if there is nothing to boot.
+                let countdown = if menu.targets().is_empty() {
+                    None
+                } else {
+                    timeout
+                };
+                let mut overlay = ui::diagnostics::DiagnosticsOverlay::new(&diagnostics, theme)
+                    .with_countdown(countdown);
+                // Show the menu behind the overlay, it is what the user gets next.
+                let _ = menu.draw(&mut app_ctx);
+                let _ = overlay.run(&mut app_ctx);
+                menu.set_countdown(overlay.countdown());
+            }
+            if let AppResult::Error(ref err) = menu.run(&mut app_ctx) {
+                let mut overlay = ui::overlay::ErrorOverlay::new(err, theme);
+                let _ = overlay.run(&mut app_ctx);
+            }
+        })
     });
 
     Status::SUCCESS
@@ -0,0 +1,75 @@
+//! Parsing of FAT and exFAT boot sectors.
+
+/// Offset of the boot sector signature, `55 AA`.
+const SIGNATURE: usize = 510;
+/// Offset of the OEM name, `EXFAT   ` on exFAT.
+const OEM_NAME: usize = 3;
+/// Offset of `BPB_FATSz16`, the FAT size on FAT12/16, `0` on FAT32.
+const FAT_SIZE_16: usize = 22;
+/// Offsets of the extended boot signature on FAT12/16 and on FAT32. The
+/// volume serial directly follows it.
+const BOOT_SIG_16: usize = 38;
+const BOOT_SIG_32: usize = 66;
+/// Offset of `VolumeSerialNumber` on exFAT.
+const EXFAT_SERIAL: usize = 100;
+
+/// Returns the volume serial number of the FAT12/16/32 or exFAT file
+/// system whose boot sector is `sector`, or `None` if it is not one or
+/// has no serial.
+pub(super) fn volume_serial(sector: &[u8]) -> Option<u32> {
+    let u32_at = |offset: usize| {
+        sector
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    if sector.get(SIGNATURE..SIGNATURE + 2)? != [0x55, 0xAA] {
+        return None;
+    }
+    if sector.get(OEM_NAME..OEM_NAME + 8)? == b"EXFAT   " {
+        return u32_at(EXFAT_SERIAL);
+    }
+    let fat32 = sector.get(FAT_SIZE_16..FAT_SIZE_16 + 2)? == [0, 0];
+    let boot_sig = if fat32 { BOOT_SIG_32 } else { BOOT_SIG_16 };
+    // Only the extended boot signature 0x29 guarantees a serial is present.
+    if *sector.get(boot_sig)? != 0x29 {
+        return None;
+    }
+    u32_at(boot_sig + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boot_sector(patch: &[(usize, &[u8])]) -> [u8; 512] {
+        let mut sector = [0; 512];
+        sector[SIGNATURE..].copy_from_slice(&[0x55, 0xAA]);
+        for (offset, bytes) in patch {
+            sector[*offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        sector
+    }
+
+    #[test]
+    fn reads_volume_serials() {
+        let serial: &[u8] = &[0xCD, 0xAB, 0x34, 0x12];
+        let fat16 = boot_sector(&[(FAT_SIZE_16, &[0x20, 0]), (38, &[0x29]), (39, serial)]);
+        assert_eq!(volume_serial(&fat16), Some(0x1234_ABCD));
+        let fat32 = boot_sector(&[(66, &[0x29]), (67, serial)]);
+        assert_eq!(volume_serial(&fat32), Some(0x1234_ABCD));
+        let exfat = boot_sector(&[(OEM_NAME, b"EXFAT   "), (EXFAT_SERIAL, serial)]);
+        assert_eq!(volume_serial(&exfat), Some(0x1234_ABCD));
+    }
+
+    #[test]
+    fn rejects_other_sectors() {
+        // No extended boot signature, the serial field holds boot code.
+        assert_eq!(volume_serial(&boot_sector(&[(67, &[1, 2, 3, 4])])), None);
+
+        let mut unsigned = boot_sector(&[(66, &[0x29])]);
+        unsigned[SIGNATURE] = 0;
+        assert_eq!(volume_serial(&unsigned), None);
+        assert_eq!(volume_serial(&[0x55, 0xAA]), None);
+    }
+}
@@ -1,188 +1,71 @@
 //! Utilities for reading block devices and locating files
 //! specified in config.
-use core::str::FromStr;
-
-use alloc::format;
-use alloc::string::String;
-use alloc::string::ToString;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::cell::RefCell;
 use log::error;
 use uefi::boot::OpenProtocolParams;
+use uefi::fs::FileSystem;
+use uefi::proto::device_path::build::DevicePathBuilder;
+use uefi::proto::device_path::media::{HardDrive, PartitionSignature};
 use uefi::proto::device_path::{DevicePath, PoolDevicePath};
 use uefi::proto::loaded_image::LoadedImage;
-use uefi::proto::media::partition::{GptPartitionEntry, MbrPartitionRecord};
+use uefi::proto::media::block::BlockIO;
+use uefi::proto::media::file::{File, FileAttribute, FileMode, FileSystemVolumeLabel, RegularFile};
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::proto::media::partition::{GptPartitionEntry, GptPartitionType, MbrPartitionRecord};
 use uefi::proto::ProtocolPointer;
-use uefi::{CString16, Handle, Identify};
-
-/// URI-style path reference for locating files across partitions
-///
-/// Supports two addressing modes:
-/// - `boot():/path` - The partition where bootloader was loaded from
-/// - `guid:PARTUUID:/path` - Partition identified by GPT PARTUUID
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct PathReference {
-    /// Which partition contains the file
-    pub location: PartitionReference,
-    /// Absolute path within that partition (must start with /)
-    pub path: String,
-}
+use uefi::{guid, CStr16, CString16, Handle, Identify};
 
-/// A reference to a specific partition.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum PartitionReference {
-    /// The partition where the bootloader EFI executable was loaded from
-    ///
-    /// For UEFI systems: This is determined by examining the `LoadedImage`
-    /// protocol's `DeviceHandle`, which tells us which partition the firmware
-    /// loaded us from.
-    ///
-    /// Syntax: `boot():`
-    /// Example: `boot():/vmlinuz-linux`
-    Boot,
-
-    /// Partition identified by GPT Partition GUID (PARTUUID)
-    ///
-    /// This is the unique identifier from the GPT partition table entry,
-    /// NOT the filesystem UUID. Each partition in a GPT table has a unique
-    /// GUID assigned when the partition is created.
-    ///
-    /// To find the PARTUUID on Linux:
-    /// ```bash
-    /// blkid /dev/nvme0n1p2
-    /// # Shows: PARTUUID="550e8400-e29b-41d4-a716-446655440000"
-    /// ```
-    ///
-    /// Or inspect GPT directly:
-    /// ```bash
-    /// sgdisk -i 2 /dev/nvme0n1
-    /// # Shows partition unique GUID
-    /// ```
-    ///
-    /// Syntax: `guid(XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX)/path`
-    Guid(uefi::Guid),
-}
-
-impl PathReference {
-    /// Parse a URI-style path reference
-    ///
-    /// # Rules
-    /// - Resource and path separated by `:`
-    ///
-    /// # Examples
-    /// ```
-    /// use plex_boot::path::PathReference;
-    /// PathReference::parse("boot():/vmlinuz-linux").unwrap();
-    /// PathReference::parse("boot():/EFI/BOOT/BOOTX64.EFI").unwrap();
-    /// PathReference::parse("guid(550e8400-e29b-41d4-a716-446655440000):/vmlinuz").unwrap();
-    /// ```
-    ///
-    /// # Errors
-    /// # Errors
-    /// Returns a `PathRefParseError` if the URI does not conform to the rules.
-    pub fn parse(s: &str) -> Result<Self, PathRefParseError> {
-        let (resource, path) = s
-            .split_once(':')
-            .ok_or(PathRefParseError::MissingDelimiter)?;
-
-        let location = PartitionReference::parse(resource)?;
-
-        Ok(Self {
-            location,
-            path: path.to_string(),
-        })
-    }
+use crate::config::validate::Environment;
 
-    /// Convert back to canonical URI string
-    ///
-    /// # Example
-    /// ```
-    /// use plex_boot::path::PathReference;
-    /// let uri = PathReference::parse("boot():/vmlinuz-linux").unwrap().to_uri();
-    /// assert_eq!(uri, "boot():/vmlinuz-linux");
-    /// ```
-    #[must_use]
-    pub fn to_uri(&self) -> String {
-        format!("{}{}", self.location.to_uri_prefix(), self.path)
-    }
-}
+mod fat;
+mod reference;
 
-impl PartitionReference {
-    /// Parse just the partition reference portion (before the final `:`)
-    ///
-    /// # Examples
-    /// ```
-    /// use plex_boot::path::PartitionReference;
-    /// PartitionReference::parse("boot()").unwrap();
-    /// PartitionReference::parse("guid(550e8400-e29b-41d4-a716-446655440000)").unwrap();
-    /// ```
-    ///
-    /// # Errors
-    /// Returns a `PathRefParseError` if the partition reference is invalid.
-    pub fn parse(s: &str) -> Result<Self, PathRefParseError> {
-        let Some(lparen) = s.find('(') else {
-            return Err(PathRefParseError::InvalidSyntax);
-        };
+pub use reference::{PartitionReference, PathRefParseError, PathReference};
 
-        let scheme = &s[..lparen];
-        let arg = s[lparen + 1..]
-            .strip_suffix(')')
-            .ok_or(PathRefParseError::MissingDelimiter)?;
-
-        match scheme {
-            "boot" => Ok(Self::Boot),
-            "guid" => Ok(Self::Guid(
-                uefi::Guid::from_str(arg).map_err(|_| PathRefParseError::InvalidGuid)?,
-            )),
-            _ => Err(PathRefParseError::UnknownResource(scheme.to_string())),
-        }
-    }
-    /// Convert to URI prefix (everything before the path).
-    ///
-    /// # Example
-    /// ```
-    /// use plex_boot::path::PartitionReference;
-    /// assert_eq!(PartitionReference::Boot.to_uri_prefix(), "boot():");
-    /// ```
-    #[must_use]
-    pub fn to_uri_prefix(&self) -> String {
-        match self {
-            Self::Boot => String::from("boot():"),
-            Self::Guid(guid) => format!("guid({guid}):"),
-        }
-    }
-}
+/// GPT partition type of an Extended Boot Loader partition (XBOOTLDR), as
+/// defined by the Boot Loader Specification.
+pub const XBOOTLDR_PARTITION: GptPartitionType =
+    GptPartitionType(guid!("bc13c2ff-59e6-4262-a352-b275fd6f7172"));
 
-/// Errors that can occur when parsing a `PathReference`.
-#[derive(Debug, Clone, PartialEq, Eq, thiserror_no_std::Error)]
-pub enum PathRefParseError {
-    /// No `:` separator found between resource and path
-    #[error("Missing Delimiter")]
-    MissingDelimiter,
+/// Errors finding the partition a [`PartitionReference`] points to.
+#[derive(Debug, thiserror_no_std::Error)]
+pub enum PartitionError {
+    /// No discovered partition matches.
+    #[error("no partition matches {0}")]
+    NotFound(PartitionReference),
 
-    /// Path component doesn't start with `/` or is empty
-    #[error("Invalid Path")]
-    InvalidPath,
+    /// No partition contains the path searched with `any()`.
+    #[error("no partition contains {0}")]
+    FileNotFound(String),
 
-    #[error("Unknown Resource: {0}")]
-    /// Unknown resource type (not "boot" or "guid")
-    UnknownResource(String),
+    /// A label or volume serial is used by several partitions, so it is
+    /// unclear which one is meant.
+    #[error("{count} partitions match {reference}, rename all but one or use guid(...) instead")]
+    Ambiguous {
+        /// The reference matching more than one partition.
+        reference: PartitionReference,
+        /// How many partitions match.
+        count: usize,
+    },
 
-    /// GUID format invalid
-    ///
-    /// Valid format: "xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx"
-    /// Must have exactly 36 chars (32 hex + 4 hyphens)
-    #[error("Invalid Guid")]
-    InvalidGuid,
-
-    /// `boot()` syntax error (something in the parens)
-    #[error("Invalid Syntax")]
-    InvalidSyntax,
+    /// The partition was found, but accessing it failed.
+    #[error(transparent)]
+    Uefi(#[from] uefi::Error),
 }
 
 /// Manages partition discovery and path resolution
 pub struct DiskManager {
     /// All discovered partitions with their metadata
     partitions: Vec<Partition>,
+    /// Partitions `any()` searches first, see [`Self::set_search_order`]
+    search_order: Vec<PartitionReference>,
+    /// Index of the partition each path searched with `any()` was found
+    /// on, `None` if no partition has it
+    found: RefCell<BTreeMap<String, Option<usize>>>,
 }
 
 impl DiskManager {
@@ -197,7 +80,9 @@ impl DiskManager {
     /// 1. Call `LocateHandleBuffer` for `BlockIO` protocol
     /// 2. Filter to only logical partitions (`media.is_logical_partition()`)
     /// 3. For each partition, extract PARTUUID from device path
-    /// 4. Store mapping of PARTUUID -> Handle
+    /// 4. Group partitions by their parent disk device path, see
+    ///    [`DiskPosition`]
+    /// 5. Store mapping of PARTUUID -> Handle
     ///
     /// # Errors
     /// Returns error if:
@@ -207,6 +92,7 @@ impl DiskManager {
     pub fn new(boot_handle: Handle) -> uefi::Result<Self> {
         use uefi::proto::media::partition::PartitionInfo;
         let mut partitions = Vec::new();
+        let mut disks = Vec::new();
 
         let boot_device_handle = open_protocol_get::<LoadedImage>(boot_handle)?.device();
         let partition_handles = uefi::boot::locate_handle_buffer(
@@ -216,10 +102,14 @@ impl DiskManager {
         for handle in partition_handles.iter() {
             match uefi::boot::open_protocol_exclusive::<PartitionInfo>(*handle) {
                 Ok(partition_info) => {
+                    let position = open_protocol_get::<DevicePath>(*handle)
+                        .ok()
+                        .and_then(|path| disk_position(&path, &mut disks));
                     partitions.push(Partition {
                         handle: *handle,
                         mbr_partition_info: partition_info.mbr_partition_record().copied(),
                         gpt_partition_info: partition_info.gpt_partition_entry().copied(),
+                        position,
                         is_system: partition_info.is_system(),
                         is_boot: boot_device_handle == Some(*handle),
                     });
@@ -230,9 +120,87 @@ impl DiskManager {
             }
         }
 
-        Ok(Self { partitions })
+        Ok(Self {
+            partitions,
+            search_order: alloc::vec![PartitionReference::Boot],
+            found: RefCell::default(),
+        })
+    }
+
+    /// Sets the order in which `any()` searches partitions: those matching
+    /// the first reference, then those matching the second and so on, then
+    /// all others in the order the firmware lists them. Defaults to the
+    /// boot partition first.
+    pub fn set_search_order(&mut self, order: Vec<PartitionReference>) {
+        self.search_order = order;
+        self.found.get_mut().clear();
+    }
+
+    /// Returns all discovered partitions.
+    #[must_use]
+    pub fn partitions(&self) -> &[Partition] {
+        &self.partitions
+    }
+
+    /// Finds the partition a `PartitionReference` points to.
+    ///
+    /// # Errors
+    /// - [`PartitionError::NotFound`] if no discovered partition matches
+    /// - [`PartitionError::Ambiguous`] if a reference that is not unique,
+    ///   see [`PartitionReference::is_unique`], matches several partitions
+    pub fn find_partition(
+        &self,
+        location: &PartitionReference,
+    ) -> Result<&Partition, PartitionError> {
+        let mut matching = self.partitions.iter().filter(|part| location.matches(part));
+        let partition = matching
+            .next()
+            .ok_or_else(|| PartitionError::NotFound(location.clone()))?;
+        if !location.is_unique() {
+            let others = matching.count();
+            if others > 0 {
+                return Err(PartitionError::Ambiguous {
+                    reference: location.clone(),
+                    count: others + 1,
+                });
+            }
+        }
+        Ok(partition)
+    }
+
+    /// Finds the partition holding the file `path` points to. Unlike
+    /// [`Self::find_partition`], this also resolves `any()` by opening the
+    /// file on each partition in the search order. The result is cached, so
+    /// every partition is searched at most once per path.
+    ///
+    /// # Errors
+    /// - [`PartitionError::FileNotFound`] if no partition contains an
+    ///   `any()` path
+    /// - otherwise as [`Self::find_partition`]
+    pub fn locate(&self, path: &PathReference) -> Result<&Partition, PartitionError> {
+        if path.location != PartitionReference::Any {
+            return self.find_partition(&path.location);
+        }
+
+        let cached = self.found.borrow().get(&path.path).copied();
+        cached
+            .map_or_else(|| self.search(&path.path), Ok)?
+            .map(|idx| &self.partitions[idx])
+            .ok_or_else(|| PartitionError::FileNotFound(path.path.clone()))
+    }
+
+    /// Opens `path` on each partition in the search order and remembers
+    /// the index of the first one that has it, see [`Self::locate`].
+    fn search(&self, path: &str) -> Result<Option<usize>, PartitionError> {
+        let name = CString16::try_from(path)
+            .map_err(|_| uefi::Error::new(uefi::Status::INVALID_PARAMETER, ()))?;
+        let found = search_order(&self.partitions, &self.search_order)
+            .into_iter()
+            .find(|&idx| self.partitions[idx].open_file(&name).is_ok());
+        self.found.borrow_mut().insert(String::from(path), found);
+        Ok(found)
     }
-    //
+
     /// Resolve a partition reference to a UEFI handle
     ///
     /// # Arguments
@@ -244,33 +212,146 @@ impl DiskManager {
     /// # Behavior
     /// - Boot: Returns cached `boot_handle` immediately (O(1))
     /// - Guid: Linear search through partitions for matching GUID (O(n))
+    /// - Label: Searches every partition, so duplicates are detected (O(n))
+    /// - `FsLabel`/`FsUuid`: Like labels, but reads every file system (O(n))
+    /// - Any: Opens the file on each partition until found, see [`Self::locate`]
     ///
     /// # Errors
-    /// - `NOT_FOUND` if GUID doesn't match any discovered partition
-    pub fn resolve_path(&self, reference: &PathReference) -> uefi::Result<PoolDevicePath> {
-        match self
+    /// Returns an error if the partition cannot be found, see
+    /// [`Self::find_partition`], or the path cannot be represented.
+    pub fn resolve_path(
+        &self,
+        reference: &PathReference,
+    ) -> Result<PoolDevicePath, PartitionError> {
+        let partition = self.locate(reference)?;
+        let device_path = open_protocol_get::<DevicePath>(partition.handle)?;
+        let mut v = Vec::new();
+        let root_to_executable =
+            uefi::proto::device_path::build::DevicePathBuilder::with_vec(&mut v)
+                .push(&uefi::proto::device_path::build::media::FilePath {
+                    path_name: &CString16::try_from(reference.path.as_str())
+                        .map_err(|_| uefi::Error::new(uefi::Status::NOT_FOUND, ()))?,
+                })
+                .map_err(|_| uefi::Error::new(uefi::Status::NOT_FOUND, ()))?
+                .finalize()
+                .map_err(|_| uefi::Error::new(uefi::Status::NOT_FOUND, ()))?;
+        Ok(device_path
+            .append_path(root_to_executable)
+            .map_err(|_| uefi::Error::new(uefi::Status::NOT_FOUND, ()))?)
+    }
+
+    /// Expands a short-form device path, which starts at the `HD()` node of
+    /// a partition as load options often do, to the full path of that
+    /// partition followed by the rest of `path`.
+    ///
+    /// Returns `None` if `path` does not start with a `HD()` node or no
+    /// discovered partition matches it.
+    #[must_use]
+    pub fn expand_device_path(&self, path: &DevicePath) -> Option<Box<DevicePath>> {
+        let partition_paths: Vec<_> = self
             .partitions
             .iter()
-            .find(|part| reference.location.matches(part))
-        {
-            Some(partition) => {
-                let device_path = open_protocol_get::<DevicePath>(partition.handle)?;
-                let mut v = Vec::new();
-                let root_to_executable =
-                    uefi::proto::device_path::build::DevicePathBuilder::with_vec(&mut v)
-                        .push(&uefi::proto::device_path::build::media::FilePath {
-                            path_name: &CString16::try_from(reference.path.as_str())
-                                .map_err(|_| uefi::Error::new(uefi::Status::NOT_FOUND, ()))?,
-                        })
-                        .map_err(|_| uefi::Error::new(uefi::Status::NOT_FOUND, ()))?
-                        .finalize()
-                        .map_err(|_| uefi::Error::new(uefi::Status::NOT_FOUND, ()))?;
-                Ok(device_path
-                    .append_path(root_to_executable)
-                    .map_err(|_| uefi::Error::new(uefi::Status::NOT_FOUND, ()))?)
+            .filter_map(|p| open_protocol_get::<DevicePath>(p.handle).ok())
+            .collect();
+        expand_hard_drive(path, partition_paths.iter().map(|p| &**p))
+    }
+}
+
+/// Returns the indices of `partitions` in the order `any()` searches them,
+/// see [`DiskManager::set_search_order`].
+fn search_order(partitions: &[Partition], order: &[PartitionReference]) -> Vec<usize> {
+    let mut indices: Vec<usize> = Vec::with_capacity(partitions.len());
+    let mut push = |matches: &dyn Fn(&Partition) -> bool| {
+        for (idx, partition) in partitions.iter().enumerate() {
+            if !indices.contains(&idx) && matches(partition) {
+                indices.push(idx);
             }
-            None => Err(uefi::Error::new(uefi::Status::NOT_FOUND, ())),
         }
+    };
+    for reference in order {
+        push(&|partition| reference.matches(partition));
+    }
+    push(&|_| true);
+    indices
+}
+
+/// Finds the disk the partition at `path` is on in `disks`, adding it if it
+/// is new, and the number of the partition from its `HD()` node.
+///
+/// Returns `None` if `path` does not end in a `HD()` node.
+fn disk_position(path: &DevicePath, disks: &mut Vec<Box<DevicePath>>) -> Option<DiskPosition> {
+    let nodes: Vec<_> = path.node_iter().collect();
+    let (last, parent) = nodes.split_last()?;
+    let hd = <&HardDrive>::try_from(*last).ok()?;
+
+    let mut buf = Vec::new();
+    let mut builder = DevicePathBuilder::with_vec(&mut buf);
+    for node in parent {
+        builder = builder.push(node).ok()?;
+    }
+    let parent = builder.finalize().ok()?;
+    let disk = disks
+        .iter()
+        .position(|disk| **disk == *parent)
+        .unwrap_or_else(|| {
+            disks.push(parent.to_boxed());
+            disks.len() - 1
+        });
+
+    Some(DiskPosition {
+        disk,
+        number: hd.partition_number(),
+        mbr_signature: match hd.partition_signature() {
+            PartitionSignature::Mbr(signature) => Some(u32::from_le_bytes(signature)),
+            _ => None,
+        },
+    })
+}
+
+/// Replaces the leading `HD()` node of `short` with the partition path in
+/// `partitions` ending in the same partition, see
+/// [`DiskManager::expand_device_path`].
+fn expand_hard_drive<'a>(
+    short: &DevicePath,
+    partitions: impl IntoIterator<Item = &'a DevicePath>,
+) -> Option<Box<DevicePath>> {
+    let hd = <&HardDrive>::try_from(short.node_iter().next()?).ok()?;
+    let same_partition = |node: &HardDrive| {
+        node.partition_signature() == hd.partition_signature()
+            && node.partition_number() == hd.partition_number()
+    };
+    let partition = partitions.into_iter().find(|path| {
+        path.node_iter()
+            .last()
+            .and_then(|node| <&HardDrive>::try_from(node).ok())
+            .is_some_and(same_partition)
+    })?;
+
+    let mut buf = Vec::new();
+    let mut builder = DevicePathBuilder::with_vec(&mut buf);
+    for node in partition.node_iter().chain(short.node_iter().skip(1)) {
+        builder = builder.push(&node).ok()?;
+    }
+    Some(builder.finalize().ok()?.to_boxed())
+}
+
+impl Environment for DiskManager {
+    fn has_partition(&self, location: &PartitionReference) -> bool {
+        self.find_partition(location).is_ok()
+    }
+
+    fn count_partitions(&self, location: &PartitionReference) -> usize {
+        self.partitions
+            .iter()
+            .filter(|part| location.matches(part))
+            .count()
+    }
+
+    fn has_file(&self, path: &PathReference) -> bool {
+        let Ok(partition) = self.locate(path) else {
+            return false;
+        };
+        CString16::try_from(path.path.as_str()).is_ok_and(|p| partition.open_file(&p).is_ok())
     }
 }
 
@@ -284,32 +365,186 @@ pub struct Partition {
     pub gpt_partition_info: Option<GptPartitionEntry>,
 
     /// MBR pt info, if avail.
-    #[allow(dead_code)]
     pub mbr_partition_info: Option<MbrPartitionRecord>,
 
+    /// Where the partition is on its disk, if its device path ends in a
+    /// `HD()` node.
+    pub position: Option<DiskPosition>,
+
     /// Whether marked as system partition (not necessarily boot partition).
-    #[allow(dead_code)]
     pub is_system: bool,
 
     /// Whether this is the partition from which the bootloader was launched.
     pub is_boot: bool,
 }
 
+/// The disk a partition is on and its number there, used by
+/// [`PartitionReference::Hd`] and [`PartitionReference::Mbr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskPosition {
+    /// Index of the disk, in the order the firmware lists the partitions.
+    /// Disks are told apart by the device path leading to the partitions.
+    pub disk: usize,
+    /// Number of the partition on the disk, from 1.
+    pub number: u32,
+    /// Signature of the disk, if it has an MBR partition table.
+    pub mbr_signature: Option<u32>,
+}
+
 impl Partition {
-    const fn guid(&self) -> Option<uefi::Guid> {
+    /// The GPT unique partition GUID (PARTUUID), if this is a GPT partition.
+    #[must_use]
+    pub const fn guid(&self) -> Option<uefi::Guid> {
         if let Some(gpt) = self.gpt_partition_info {
             Some(gpt.unique_partition_guid)
         } else {
             None
         }
     }
+
+    /// The GPT partition name (PARTLABEL), if this is a GPT partition with
+    /// a name.
+    #[must_use]
+    pub fn label(&self) -> Option<String> {
+        let name = self.gpt_partition_info?.partition_name;
+        let label: String = char::decode_utf16(
+            name.iter()
+                .map(|&c| u16::from(c))
+                .take_while(|&unit| unit != 0),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+        (!label.is_empty()).then_some(label)
+    }
+
+    /// The label of the file system on this partition, if the firmware can
+    /// read the file system and it has a label.
+    #[must_use]
+    pub fn volume_label(&self) -> Option<String> {
+        let mut sfs = open_protocol_get::<SimpleFileSystem>(self.handle).ok()?;
+        let info = sfs
+            .open_volume()
+            .ok()?
+            .get_boxed_info::<FileSystemVolumeLabel>()
+            .ok()?;
+        let label = info.volume_label().to_string();
+        (!label.is_empty()).then_some(label)
+    }
+
+    /// The volume serial number of the FAT or exFAT file system on this
+    /// partition, read from its boot sector.
+    #[must_use]
+    pub fn volume_serial(&self) -> Option<u32> {
+        let block_io = open_protocol_get::<BlockIO>(self.handle).ok()?;
+        let media = block_io.media();
+        let block_size = usize::try_from(media.block_size()).ok()?;
+        let align = usize::try_from(media.io_align()).ok()?.max(1);
+
+        // The buffer has to satisfy the alignment the device asks for.
+        let mut buf = alloc::vec![0; block_size + align];
+        let offset = buf.as_ptr().align_offset(align);
+        let sector = buf.get_mut(offset..offset + block_size)?;
+        block_io.read_blocks(media.media_id(), 0, sector).ok()?;
+        fat::volume_serial(sector)
+    }
+
+    /// The GPT partition type, if this is a GPT partition.
+    #[must_use]
+    pub const fn partition_type(&self) -> Option<GptPartitionType> {
+        if let Some(gpt) = self.gpt_partition_info {
+            Some(gpt.partition_type_guid)
+        } else {
+            None
+        }
+    }
+
+    /// Whether this is an EFI system partition.
+    #[must_use]
+    pub fn is_esp(&self) -> bool {
+        self.is_system || self.partition_type() == Some(GptPartitionType::EFI_SYSTEM_PARTITION)
+    }
+
+    /// Whether this is an Extended Boot Loader partition.
+    #[must_use]
+    pub fn is_xbootldr(&self) -> bool {
+        self.partition_type() == Some(XBOOTLDR_PARTITION)
+    }
+
+    /// A reference that addresses this partition in a `PathReference`.
+    ///
+    /// Returns `None` for partitions that cannot be addressed, i.e. neither
+    /// the boot partition nor on a GPT or MBR disk.
+    #[must_use]
+    pub const fn reference(&self) -> Option<PartitionReference> {
+        if self.is_boot {
+            return Some(PartitionReference::Boot);
+        }
+        match (self.guid(), self.position, self.mbr_partition_info) {
+            (Some(guid), _, _) => Some(PartitionReference::Guid(guid)),
+            (
+                None,
+                Some(DiskPosition {
+                    number,
+                    mbr_signature: Some(signature),
+                    ..
+                }),
+                Some(_),
+            ) => Some(PartitionReference::Mbr {
+                signature,
+                partition: number,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Opens the file system on this partition.
+    ///
+    /// # Errors
+    /// Returns an error if the partition has no `SimpleFileSystem` protocol,
+    /// e.g. because its file system is not supported by the firmware.
+    pub fn file_system(&self) -> uefi::Result<FileSystem> {
+        Ok(FileSystem::new(open_protocol_get::<SimpleFileSystem>(
+            self.handle,
+        )?))
+    }
+
+    /// Opens a regular file on this partition for reading.
+    ///
+    /// Unlike [`Self::file_system`], this allows reading parts of large files
+    /// without loading them into memory.
+    ///
+    /// # Errors
+    /// Returns an error if the file does not exist or is a directory.
+    pub fn open_file(&self, path: &CStr16) -> uefi::Result<RegularFile> {
+        let mut sfs = open_protocol_get::<SimpleFileSystem>(self.handle)?;
+        sfs.open_volume()?
+            .open(path, FileMode::Read, FileAttribute::empty())?
+            .into_regular_file()
+            .ok_or_else(|| uefi::Error::new(uefi::Status::INVALID_PARAMETER, ()))
+    }
 }
 
 impl PartitionReference {
     fn matches(&self, p: &Partition) -> bool {
         match &self {
             Self::Boot => p.is_boot,
+            // Depends on the path, see `DiskManager::locate`.
+            Self::Any => false,
             Self::Guid(id) => p.guid().as_ref() == Some(id),
+            Self::Label(label) => p.label().as_deref() == Some(label.as_str()),
+            Self::FsLabel(label) => p
+                .volume_label()
+                .is_some_and(|l| l.eq_ignore_ascii_case(label)),
+            Self::FsUuid(serial) => p.volume_serial() == Some(*serial),
+            Self::Hd { disk, partition } => p
+                .position
+                .is_some_and(|pos| pos.disk == *disk && pos.number == *partition),
+            Self::Mbr {
+                signature,
+                partition,
+            } => p.position.is_some_and(|pos| {
+                pos.mbr_signature == Some(*signature) && pos.number == *partition
+            }),
         }
     }
 }
@@ -332,3 +567,250 @@ pub fn open_protocol_get<P: ProtocolPointer + ?Sized>(
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uefi::cstr16;
+    use uefi::proto::device_path::build;
+    use uefi::proto::device_path::media::PartitionFormat;
+
+    fn hard_drive(number: u32, guid: uefi::Guid) -> build::media::HardDrive {
+        build::media::HardDrive {
+            partition_number: number,
+            partition_start: 2048,
+            partition_size: 1_048_576,
+            partition_signature: PartitionSignature::Guid(guid),
+            partition_format: PartitionFormat::GPT,
+        }
+    }
+
+    fn partition(buf: &mut Vec<u8>, number: u32, guid: uefi::Guid) -> &DevicePath {
+        DevicePathBuilder::with_vec(buf)
+            .push(&build::hardware::Pci {
+                function: 0,
+                device: 4,
+            })
+            .unwrap()
+            .push(&hard_drive(number, guid))
+            .unwrap()
+            .finalize()
+            .unwrap()
+    }
+
+    /// A GPT partition named `name`, not backed by any device.
+    fn named_partition(guid: uefi::Guid, name: &str) -> Partition {
+        let mut partition_name = [uefi::Char16::try_from(0u16).unwrap(); 36];
+        for (unit, c) in partition_name.iter_mut().zip(name.chars()) {
+            *unit = uefi::Char16::try_from(c).unwrap();
+        }
+        Partition {
+            // SAFETY: the handle is never passed to the firmware.
+            handle: unsafe { Handle::from_ptr(core::ptr::NonNull::dangling().as_ptr()) }.unwrap(),
+            gpt_partition_info: Some(GptPartitionEntry {
+                partition_type_guid: GptPartitionType::EFI_SYSTEM_PARTITION,
+                unique_partition_guid: guid,
+                starting_lba: 2048,
+                ending_lba: 1_050_623,
+                attributes: uefi::proto::media::partition::GptPartitionAttributes::empty(),
+                partition_name,
+            }),
+            mbr_partition_info: None,
+            position: None,
+            is_system: false,
+            is_boot: false,
+        }
+    }
+
+    #[test]
+    fn finds_partitions_by_label() {
+        let disks = DiskManager {
+            partitions: alloc::vec![
+                named_partition(guid!("550e8400-e29b-41d4-a716-446655440000"), "ESP"),
+                named_partition(guid!("8c8f8eff-ac95-4770-814a-21994f2dbc8f"), "root"),
+                named_partition(guid!("0a3407de-014b-458b-b5c1-848e92a327a3"), "root"),
+                named_partition(guid!("c12a7328-f81f-11d2-ba4b-00a0c93ec93b"), ""),
+            ],
+            search_order: Vec::new(),
+            found: RefCell::default(),
+        };
+        let label = |name: &str| PartitionReference::Label(String::from(name));
+
+        assert_eq!(disks.partitions[0].label().as_deref(), Some("ESP"));
+        assert_eq!(disks.partitions[3].label(), None);
+        let esp = disks.find_partition(&label("ESP")).unwrap();
+        assert_eq!(
+            esp.guid(),
+            Some(guid!("550e8400-e29b-41d4-a716-446655440000"))
+        );
+        assert!(matches!(
+            disks.find_partition(&label("esp")),
+            Err(PartitionError::NotFound(_))
+        ));
+        assert!(matches!(
+            disks.find_partition(&label("root")),
+            Err(PartitionError::Ambiguous { count: 2, .. })
+        ));
+        assert_eq!(disks.count_partitions(&label("root")), 2);
+    }
+
+    #[test]
+    fn orders_partitions_for_search() {
+        let mut partitions: Vec<_> = ["root", "ESP", "USB", "home"]
+            .iter()
+            .map(|name| named_partition(uefi::Guid::ZERO, name))
+            .collect();
+        partitions[1].is_boot = true;
+        let label = |name: &str| PartitionReference::Label(String::from(name));
+
+        assert_eq!(
+            search_order(&partitions, &[PartitionReference::Boot]),
+            [1, 0, 2, 3]
+        );
+        assert_eq!(
+            search_order(
+                &partitions,
+                &[label("USB"), PartitionReference::Boot, label("USB")]
+            ),
+            [2, 1, 0, 3]
+        );
+        assert_eq!(search_order(&partitions, &[]), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn groups_partitions_by_disk() {
+        let mbr = build::media::HardDrive {
+            partition_signature: PartitionSignature::Mbr(0x1234_abcd_u32.to_le_bytes()),
+            partition_format: PartitionFormat::MBR,
+            ..hard_drive(1, uefi::Guid::ZERO)
+        };
+        let on_disk = |buf: &mut Vec<u8>, device: u8, hd: Option<&build::media::HardDrive>| {
+            let mut builder = DevicePathBuilder::with_vec(buf)
+                .push(&build::hardware::Pci {
+                    function: 0,
+                    device,
+                })
+                .unwrap();
+            if let Some(hd) = hd {
+                builder = builder.push(hd).unwrap();
+            }
+            builder.finalize().unwrap().to_boxed()
+        };
+        let paths = [
+            on_disk(&mut Vec::new(), 4, Some(&hard_drive(1, uefi::Guid::ZERO))),
+            on_disk(&mut Vec::new(), 5, Some(&mbr)),
+            on_disk(&mut Vec::new(), 4, Some(&hard_drive(2, uefi::Guid::ZERO))),
+            on_disk(&mut Vec::new(), 6, None),
+        ];
+
+        let mut disks = Vec::new();
+        let positions: Vec<_> = paths
+            .iter()
+            .map(|path| disk_position(path, &mut disks))
+            .collect();
+        let position = |disk, number, mbr_signature| {
+            Some(DiskPosition {
+                disk,
+                number,
+                mbr_signature,
+            })
+        };
+        assert_eq!(
+            positions,
+            [
+                position(0, 1, None),
+                position(1, 1, Some(0x1234_abcd)),
+                position(0, 2, None),
+                None,
+            ]
+        );
+
+        let mut usb = named_partition(uefi::Guid::ZERO, "");
+        usb.gpt_partition_info = None;
+        usb.mbr_partition_info = Some(MbrPartitionRecord {
+            boot_indicator: 0x80,
+            starting_chs: [0; 3],
+            os_type: uefi::proto::media::partition::MbrOsType(0x0C),
+            ending_chs: [0; 3],
+            starting_lba: 2048,
+            size_in_lba: 1_048_576,
+        });
+        usb.position = positions[1];
+        let mut esp = named_partition(guid!("550e8400-e29b-41d4-a716-446655440000"), "ESP");
+        esp.position = positions[0];
+        let disks = DiskManager {
+            partitions: alloc::vec![esp, usb],
+            search_order: Vec::new(),
+            found: RefCell::default(),
+        };
+
+        let hd = PartitionReference::Hd {
+            disk: 1,
+            partition: 1,
+        };
+        let by_signature = PartitionReference::Mbr {
+            signature: 0x1234_abcd,
+            partition: 1,
+        };
+        assert_eq!(
+            disks.find_partition(&hd).unwrap().reference(),
+            Some(by_signature.clone())
+        );
+        assert!(disks.find_partition(&by_signature).is_ok());
+        assert!(disks
+            .find_partition(&PartitionReference::Hd {
+                disk: 0,
+                partition: 2
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn expands_short_form_paths() {
+        let esp = guid!("550e8400-e29b-41d4-a716-446655440000");
+        let root = guid!("8c8f8eff-ac95-4770-814a-21994f2dbc8f");
+        let (mut esp_buf, mut root_buf) = (Vec::new(), Vec::new());
+        let partitions = [
+            partition(&mut root_buf, 2, root),
+            partition(&mut esp_buf, 1, esp),
+        ];
+        let file = build::media::FilePath {
+            path_name: cstr16!("\\EFI\\BOOT\\BOOTX64.EFI"),
+        };
+
+        let mut buf = Vec::new();
+        let short = DevicePathBuilder::with_vec(&mut buf)
+            .push(&hard_drive(1, esp))
+            .unwrap()
+            .push(&file)
+            .unwrap()
+            .finalize()
+            .unwrap();
+        let expanded = expand_hard_drive(short, partitions).unwrap();
+        let mut buf = Vec::new();
+        let full = DevicePathBuilder::with_vec(&mut buf)
+            .push(&build::hardware::Pci {
+                function: 0,
+                device: 4,
+            })
+            .unwrap()
+            .push(&hard_drive(1, esp))
+            .unwrap()
+            .push(&file)
+            .unwrap()
+            .finalize()
+            .unwrap();
+        assert_eq!(&*expanded, full);
+
+        // Unknown partitions and paths that are already complete stay as
+        // they are.
+        let mut buf = Vec::new();
+        let unknown = DevicePathBuilder::with_vec(&mut buf)
+            .push(&hard_drive(3, esp))
+            .unwrap()
+            .finalize()
+            .unwrap();
+        assert!(expand_hard_drive(unknown, partitions).is_none());
+        assert!(expand_hard_drive(full, partitions).is_none());
+    }
+}
@@ -0,0 +1,606 @@
+//! Textual references to files on a partition, as written in `plex.toml`.
+//!
+//! Parsing and printing only, so this module builds and runs on the host,
+//! e.g. in `plex-lint`. Resolving a reference against the disks of the
+//! running machine is done by [`super::DiskManager`].
+
+use core::str::FromStr;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+
+/// URI-style path reference for locating files across partitions
+///
+/// Supports these addressing modes:
+/// - `boot():/path` - The partition where bootloader was loaded from
+/// - `guid:PARTUUID:/path` - Partition identified by GPT PARTUUID
+/// - `label(NAME):/path` - Partition identified by GPT partition name
+///   (PARTLABEL), also written `partlabel(NAME):/path`
+/// - `fslabel(NAME):/path` - Partition identified by file system label
+/// - `fsuuid(XXXX-XXXX):/path` - Partition identified by FAT volume serial
+/// - `hd(DISK,PART):/path` - Partition by its disk and partition number
+/// - `mbr(SIGNATURE,PART):/path` - Partition on an MBR disk by the disk
+///   signature and partition number
+/// - `any():/path` - The first partition that contains the path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathReference {
+    /// Which partition contains the file
+    pub location: PartitionReference,
+    /// Absolute path within that partition (must start with /)
+    pub path: String,
+}
+
+/// A reference to a specific partition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartitionReference {
+    /// The partition where the bootloader EFI executable was loaded from
+    ///
+    /// For UEFI systems: This is determined by examining the `LoadedImage`
+    /// protocol's `DeviceHandle`, which tells us which partition the firmware
+    /// loaded us from.
+    ///
+    /// Syntax: `boot():`
+    /// Example: `boot():/vmlinuz-linux`
+    Boot,
+
+    /// Partition identified by GPT Partition GUID (PARTUUID)
+    ///
+    /// This is the unique identifier from the GPT partition table entry,
+    /// NOT the filesystem UUID. Each partition in a GPT table has a unique
+    /// GUID assigned when the partition is created.
+    ///
+    /// To find the PARTUUID on Linux:
+    /// ```bash
+    /// blkid /dev/nvme0n1p2
+    /// # Shows: PARTUUID="550e8400-e29b-41d4-a716-446655440000"
+    /// ```
+    ///
+    /// Or inspect GPT directly:
+    /// ```bash
+    /// sgdisk -i 2 /dev/nvme0n1
+    /// # Shows partition unique GUID
+    /// ```
+    ///
+    /// Syntax: `guid(XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX)/path`
+    Guid(uefi::Guid),
+
+    /// Partition identified by its GPT partition name (PARTLABEL)
+    ///
+    /// Unlike the PARTUUID, the name survives re-creating the partition as
+    /// long as it is given the same name again. It is compared exactly,
+    /// and must match a single partition across all disks.
+    ///
+    /// To find the PARTLABEL on Linux:
+    /// ```bash
+    /// lsblk -o NAME,PARTLABEL
+    /// ```
+    ///
+    /// Syntax: `label(NAME)` or `partlabel(NAME)`
+    /// Example: `label(ESP):/EFI/BOOT/BOOTX64.EFI`
+    Label(String),
+
+    /// Partition identified by the label of its file system (LABEL)
+    ///
+    /// Read through the firmware's file system driver, so only file systems
+    /// the firmware can read, usually FAT, have one. FAT stores labels in
+    /// upper case, so they are compared without regard to ASCII case. Like
+    /// [`Self::Label`], it must match a single partition.
+    ///
+    /// To find the LABEL on Linux:
+    /// ```bash
+    /// lsblk -o NAME,LABEL
+    /// ```
+    ///
+    /// Syntax: `fslabel(NAME)`
+    /// Example: `fslabel(BOOT):/vmlinuz-linux`
+    FsLabel(String),
+
+    /// Partition identified by the volume serial number of its FAT or
+    /// exFAT file system, which Linux shows as its UUID
+    ///
+    /// It is set when the file system is created, so unlike the PARTUUID
+    /// it changes when the partition is reformatted, but survives copying
+    /// the file system to a new partition.
+    ///
+    /// To find the UUID on Linux:
+    /// ```bash
+    /// blkid /dev/nvme0n1p1
+    /// # Shows: UUID="1234-ABCD"
+    /// ```
+    ///
+    /// Syntax: `fsuuid(XXXX-XXXX)`
+    FsUuid(u32),
+
+    /// Partition identified by the disk it is on and its number on that
+    /// disk, for any partition table
+    ///
+    /// Disks are numbered from 0 in the order the firmware lists them,
+    /// which may change when disks are added or removed. Partitions are
+    /// numbered from 1, like `/dev/sda1`.
+    ///
+    /// Syntax: `hd(DISK,PART)`
+    /// Example: `hd(0,1):/EFI/BOOT/BOOTX64.EFI`
+    Hd {
+        /// Index of the disk, from 0.
+        disk: usize,
+        /// Number of the partition on the disk, from 1.
+        partition: u32,
+    },
+
+    /// Partition on an MBR disk, identified by the disk signature and the
+    /// partition number
+    ///
+    /// To find both on Linux, where the partition number follows the
+    /// signature in the PARTUUID:
+    /// ```bash
+    /// blkid /dev/sdb1
+    /// # Shows: PTUUID="1234abcd" PARTUUID="1234abcd-01"
+    /// ```
+    ///
+    /// Syntax: `mbr(SIGNATURE,PART)`
+    /// Example: `mbr(1234abcd,1):/vmlinuz`
+    Mbr {
+        /// The 32-bit disk signature stored in the MBR.
+        signature: u32,
+        /// Number of the partition on the disk, from 1.
+        partition: u32,
+    },
+
+    /// Whichever partition contains the path
+    ///
+    /// Partitions are searched in the configured `search_order`, the boot
+    /// partition first by default, so the same entry works from a USB
+    /// stick and from an internal disk. Which partition the path is on is
+    /// only known together with the path, see
+    /// [`super::DiskManager::locate`].
+    ///
+    /// Syntax: `any()`
+    /// Example: `any():/EFI/arch/vmlinuz-linux.efi`
+    Any,
+}
+
+impl PathReference {
+    /// Parse a URI-style path reference
+    ///
+    /// # Rules
+    /// - Resource and path separated by `:`
+    ///
+    /// # Examples
+    /// ```
+    /// use plex_boot::path::PathReference;
+    /// PathReference::parse("boot():/vmlinuz-linux").unwrap();
+    /// PathReference::parse("boot():/EFI/BOOT/BOOTX64.EFI").unwrap();
+    /// PathReference::parse("guid(550e8400-e29b-41d4-a716-446655440000):/vmlinuz").unwrap();
+    /// PathReference::parse("label(Linux: root):/boot/vmlinuz").unwrap();
+    /// PathReference::parse("fsuuid(1234-ABCD):/vmlinuz").unwrap();
+    /// PathReference::parse("hd(0, 1):/vmlinuz").unwrap();
+    /// PathReference::parse("any():/EFI/arch/vmlinuz-linux.efi").unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a `PathRefParseError` if the URI does not conform to the rules.
+    pub fn parse(s: &str) -> Result<Self, PathRefParseError> {
+        // Labels may contain `:` themselves, so split after the resource.
+        let (resource, path) = match s.find("):") {
+            Some(end) => (&s[..=end], &s[end + 2..]),
+            None => s
+                .split_once(':')
+                .ok_or(PathRefParseError::MissingDelimiter)?,
+        };
+
+        let location = PartitionReference::parse(resource)?;
+
+        Ok(Self {
+            location,
+            path: path.to_string(),
+        })
+    }
+
+    /// Convert back to canonical URI string
+    ///
+    /// # Example
+    /// ```
+    /// use plex_boot::path::PathReference;
+    /// let uri = PathReference::parse("boot():/vmlinuz-linux").unwrap().to_uri();
+    /// assert_eq!(uri, "boot():/vmlinuz-linux");
+    ///
+    /// let uri = PathReference::parse("partlabel(ESP):/EFI/BOOT/BOOTX64.EFI").unwrap().to_uri();
+    /// assert_eq!(uri, "label(ESP):/EFI/BOOT/BOOTX64.EFI");
+    /// assert_eq!(PathReference::parse(&uri).unwrap().to_uri(), uri);
+    ///
+    /// for uri in [
+    ///     "fslabel(BOOT):/vmlinuz",
+    ///     "fsuuid(1234-ABCD):/vmlinuz",
+    ///     "hd(1,2):/vmlinuz",
+    ///     "mbr(1234abcd,1):/vmlinuz",
+    ///     "any():/vmlinuz",
+    /// ] {
+    ///     assert_eq!(PathReference::parse(uri).unwrap().to_uri(), uri);
+    /// }
+    /// let uri = PathReference::parse("fsuuid(12ab-cdef):/vmlinuz").unwrap().to_uri();
+    /// assert_eq!(uri, "fsuuid(12AB-CDEF):/vmlinuz");
+    /// let uri = PathReference::parse("mbr(0x1234ABCD, 1):/vmlinuz").unwrap().to_uri();
+    /// assert_eq!(uri, "mbr(1234abcd,1):/vmlinuz");
+    /// ```
+    #[must_use]
+    pub fn to_uri(&self) -> String {
+        format!("{}{}", self.location.to_uri_prefix(), self.path)
+    }
+}
+
+impl PartitionReference {
+    /// Parse just the partition reference portion (before the final `:`)
+    ///
+    /// # Examples
+    /// ```
+    /// use plex_boot::path::PartitionReference;
+    /// PartitionReference::parse("boot()").unwrap();
+    /// PartitionReference::parse("guid(550e8400-e29b-41d4-a716-446655440000)").unwrap();
+    /// assert_eq!(
+    ///     PartitionReference::parse("partlabel(ESP)"),
+    ///     PartitionReference::parse("label(ESP)"),
+    /// );
+    /// assert!(PartitionReference::parse("label()").is_err());
+    /// assert_eq!(
+    ///     PartitionReference::parse("fsuuid(1234-ABCD)"),
+    ///     Ok(PartitionReference::FsUuid(0x1234_ABCD)),
+    /// );
+    /// assert!(PartitionReference::parse("fsuuid(1234ABCD)").is_err());
+    /// assert_eq!(
+    ///     PartitionReference::parse("hd(0,1)"),
+    ///     Ok(PartitionReference::Hd { disk: 0, partition: 1 }),
+    /// );
+    /// assert!(PartitionReference::parse("hd(0,0)").is_err());
+    /// assert!(PartitionReference::parse("mbr(1234abcd)").is_err());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a `PathRefParseError` if the partition reference is invalid.
+    pub fn parse(s: &str) -> Result<Self, PathRefParseError> {
+        let Some(lparen) = s.find('(') else {
+            return Err(PathRefParseError::InvalidSyntax);
+        };
+
+        let scheme = &s[..lparen];
+        let arg = s[lparen + 1..]
+            .strip_suffix(')')
+            .ok_or(PathRefParseError::MissingDelimiter)?;
+
+        match scheme {
+            "boot" => Ok(Self::Boot),
+            "any" => Ok(Self::Any),
+            "guid" => Ok(Self::Guid(
+                uefi::Guid::from_str(arg).map_err(|_| PathRefParseError::InvalidGuid)?,
+            )),
+            "label" | "partlabel" | "fslabel" if arg.is_empty() => {
+                Err(PathRefParseError::EmptyLabel)
+            }
+            "label" | "partlabel" => Ok(Self::Label(arg.to_string())),
+            "fslabel" => Ok(Self::FsLabel(arg.to_string())),
+            "fsuuid" => parse_volume_serial(arg)
+                .map(Self::FsUuid)
+                .ok_or(PathRefParseError::InvalidVolumeSerial),
+            "hd" => parse_disk_address(arg, parse_decimal)
+                .map(|(disk, partition)| Self::Hd { disk, partition }),
+            "mbr" => parse_disk_address(arg, |signature| {
+                let hex = signature.strip_prefix("0x").unwrap_or(signature);
+                (hex.len() == 8 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .then(|| u32::from_str_radix(hex, 16).ok())
+                    .flatten()
+            })
+            .map(|(signature, partition)| Self::Mbr {
+                signature,
+                partition,
+            }),
+            _ => Err(PathRefParseError::UnknownResource(scheme.to_string())),
+        }
+    }
+    /// Convert to URI prefix (everything before the path).
+    ///
+    /// # Example
+    /// ```
+    /// use plex_boot::path::PartitionReference;
+    /// assert_eq!(PartitionReference::Boot.to_uri_prefix(), "boot():");
+    /// ```
+    #[must_use]
+    pub fn to_uri_prefix(&self) -> String {
+        format!("{self}:")
+    }
+
+    /// Whether at most one partition can match. Labels, volume serials and
+    /// disk signatures are only unique by convention, so lookups check for
+    /// duplicates.
+    #[must_use]
+    pub const fn is_unique(&self) -> bool {
+        matches!(self, Self::Boot | Self::Guid(_) | Self::Hd { .. })
+    }
+}
+
+/// Parses `DISK,PART` with `disk` parsing the first half. Partitions are
+/// numbered from 1.
+fn parse_disk_address<T>(
+    s: &str,
+    disk: impl FnOnce(&str) -> Option<T>,
+) -> Result<(T, u32), PathRefParseError> {
+    let (first, partition) = s
+        .split_once(',')
+        .ok_or(PathRefParseError::InvalidDiskAddress)?;
+    let disk = disk(first.trim()).ok_or(PathRefParseError::InvalidDiskAddress)?;
+    match parse_decimal(partition.trim()) {
+        Some(partition) if partition > 0 => Ok((disk, partition)),
+        _ => Err(PathRefParseError::InvalidDiskAddress),
+    }
+}
+
+/// Parses a plain decimal number. Unlike `str::parse`, a leading `+` is
+/// rejected.
+fn parse_decimal<T: FromStr>(s: &str) -> Option<T> {
+    (!s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| s.parse().ok())
+        .flatten()
+}
+
+/// Parses a volume serial written the way Linux shows it, `XXXX-XXXX`.
+fn parse_volume_serial(s: &str) -> Option<u32> {
+    let (high, low) = s.split_once('-')?;
+    let half = |half: &str| {
+        (half.len() == 4 && half.bytes().all(|b| b.is_ascii_hexdigit()))
+            .then(|| u16::from_str_radix(half, 16).ok())
+            .flatten()
+    };
+    Some(u32::from(half(high)?) << 16 | u32::from(half(low)?))
+}
+
+/// Parses a reference as written in `plex.toml`, e.g. in `search_order`.
+impl<'de> serde::Deserialize<'de> for PartitionReference {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map_err(|e| {
+            serde::de::Error::custom(format!("invalid partition reference \"{s}\": {e}"))
+        })
+    }
+}
+
+/// Formats the reference as written in `plex.toml`, without the `:`.
+impl core::fmt::Display for PartitionReference {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Boot => write!(f, "boot()"),
+            Self::Any => write!(f, "any()"),
+            Self::Guid(guid) => write!(f, "guid({guid})"),
+            Self::Label(label) => write!(f, "label({label})"),
+            Self::FsLabel(label) => write!(f, "fslabel({label})"),
+            Self::FsUuid(serial) => {
+                write!(f, "fsuuid({:04X}-{:04X})", serial >> 16, serial & 0xFFFF)
+            }
+            Self::Hd { disk, partition } => write!(f, "hd({disk},{partition})"),
+            Self::Mbr {
+                signature,
+                partition,
+            } => write!(f, "mbr({signature:08x},{partition})"),
+        }
+    }
+}
+
+/// Errors that can occur when parsing a `PathReference`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror_no_std::Error)]
+pub enum PathRefParseError {
+    /// No `:` separator found between resource and path
+    #[error("Missing Delimiter")]
+    MissingDelimiter,
+
+    /// Path component doesn't start with `/` or is empty
+    #[error("Invalid Path")]
+    InvalidPath,
+
+    #[error("Unknown Resource: {0}")]
+    /// Unknown resource type (not "boot", "guid", "label", "partlabel",
+    /// "fslabel", "fsuuid", "hd", "mbr" or "any")
+    UnknownResource(String),
+
+    /// GUID format invalid
+    ///
+    /// Valid format: "xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx"
+    /// Must have exactly 36 chars (32 hex + 4 hyphens)
+    #[error("Invalid Guid")]
+    InvalidGuid,
+
+    /// `boot()` syntax error (something in the parens)
+    #[error("Invalid Syntax")]
+    InvalidSyntax,
+
+    /// `label()` or `fslabel()` without a name
+    #[error("Empty Label")]
+    EmptyLabel,
+
+    /// Volume serial format invalid
+    ///
+    /// Valid format: "XXXX-XXXX", 8 hex digits split by a hyphen
+    #[error("Invalid Volume Serial")]
+    InvalidVolumeSerial,
+
+    /// `hd()` or `mbr()` argument invalid
+    ///
+    /// Valid format: "DISK,PART" with a partition number from 1, and an
+    /// 8 digit hex signature as `DISK` for `mbr()`
+    #[error("Invalid Disk Address")]
+    InvalidDiskAddress,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `uri`, prints it and checks that the printed form parses to
+    /// the same reference. Returns the printed form.
+    fn round_trip(uri: &str) -> String {
+        let reference = PathReference::parse(uri).unwrap();
+        let printed = reference.to_uri();
+        assert_eq!(PathReference::parse(&printed).unwrap(), reference);
+        printed
+    }
+
+    fn parse_error(uri: &str) -> PathRefParseError {
+        PathReference::parse(uri).unwrap_err()
+    }
+
+    #[test]
+    fn round_trips_partition_labels() {
+        let uri = "label(ESP):\\EFI\\BOOT\\BOOTX64.EFI";
+        assert_eq!(round_trip(uri), uri);
+        assert_eq!(
+            round_trip("partlabel(ESP):\\vmlinuz"),
+            "label(ESP):\\vmlinuz"
+        );
+        assert_eq!(
+            PathReference::parse("partlabel(Linux: root):\\vmlinuz").unwrap(),
+            PathReference {
+                location: PartitionReference::Label(String::from("Linux: root")),
+                path: String::from("\\vmlinuz"),
+            }
+        );
+        assert_eq!(
+            round_trip("label(Linux: root):\\vmlinuz"),
+            "label(Linux: root):\\vmlinuz"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_partition_labels() {
+        assert_eq!(
+            parse_error("label():\\vmlinuz"),
+            PathRefParseError::EmptyLabel
+        );
+        assert_eq!(
+            parse_error("partlabel():\\vmlinuz"),
+            PathRefParseError::EmptyLabel
+        );
+        assert_eq!(
+            parse_error("label(ESP:\\vmlinuz"),
+            PathRefParseError::MissingDelimiter
+        );
+        assert_eq!(
+            PartitionReference::parse("label(ESP"),
+            Err(PathRefParseError::MissingDelimiter)
+        );
+    }
+
+    #[test]
+    fn round_trips_file_system_references() {
+        assert_eq!(
+            round_trip("fslabel(BOOT):\\vmlinuz"),
+            "fslabel(BOOT):\\vmlinuz"
+        );
+        assert_eq!(
+            round_trip("fsuuid(1234-ABCD):\\vmlinuz"),
+            "fsuuid(1234-ABCD):\\vmlinuz"
+        );
+        assert_eq!(
+            round_trip("fsuuid(0000-00ff):\\vmlinuz"),
+            "fsuuid(0000-00FF):\\vmlinuz"
+        );
+        assert_eq!(
+            PartitionReference::parse("fsuuid(12ab-CDEF)"),
+            Ok(PartitionReference::FsUuid(0x12AB_CDEF))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_file_system_references() {
+        assert_eq!(
+            parse_error("fslabel():\\vmlinuz"),
+            PathRefParseError::EmptyLabel
+        );
+        for serial in [
+            "",
+            "1234-ABC",
+            "12345-ABCD",
+            "1234-ABCDE",
+            "1234ABCD",
+            "12-34-ABCD",
+            "12G4-ABCD",
+            "+123-ABCD",
+            "1234 ABCD",
+        ] {
+            assert_eq!(
+                PartitionReference::parse(&format!("fsuuid({serial})")),
+                Err(PathRefParseError::InvalidVolumeSerial),
+                "{serial}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_disk_addresses() {
+        assert_eq!(round_trip("hd(0,1):\\vmlinuz"), "hd(0,1):\\vmlinuz");
+        assert_eq!(round_trip("hd( 1 , 12 ):\\vmlinuz"), "hd(1,12):\\vmlinuz");
+        assert_eq!(
+            round_trip("mbr(1234abcd,1):\\vmlinuz"),
+            "mbr(1234abcd,1):\\vmlinuz"
+        );
+        assert_eq!(
+            round_trip("mbr(0x0000ABCD, 4):\\vmlinuz"),
+            "mbr(0000abcd,4):\\vmlinuz"
+        );
+        assert_eq!(
+            PartitionReference::parse("mbr(1234ABCD,2)"),
+            Ok(PartitionReference::Mbr {
+                signature: 0x1234_ABCD,
+                partition: 2
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_disk_addresses() {
+        for reference in [
+            // Missing arguments
+            "hd()",
+            "hd(0)",
+            "hd(,1)",
+            "hd(0,)",
+            "mbr()",
+            "mbr(1234abcd)",
+            "mbr(,1)",
+            // Bad numbers and hex
+            "hd(-1,1)",
+            "hd(0,0)",
+            "hd(a,1)",
+            "mbr(1234abcg,1)",
+            "mbr(123abcd,1)",
+            "mbr(0x1234abcd0,1)",
+            "mbr(1234abcd,0)",
+            // Signs that `str::parse` and `from_str_radix` would accept
+            "hd(+1,1)",
+            "hd(0,+1)",
+            "mbr(+234abcd,1)",
+            "mbr(0x+234abcd,1)",
+            "mbr(1234abcd,+1)",
+            // Overflowing numbers
+            "hd(0,4294967296)",
+            "hd(99999999999999999999999,1)",
+            "mbr(1234abcd,4294967296)",
+        ] {
+            assert_eq!(
+                PartitionReference::parse(reference),
+                Err(PathRefParseError::InvalidDiskAddress),
+                "{reference}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_any_partition() {
+        let uri = "any():\\EFI\\arch\\vmlinuz-linux.efi";
+        assert_eq!(round_trip(uri), uri);
+        assert_eq!(
+            PathReference::parse(uri).map(|path| path.location),
+            Ok(PartitionReference::Any)
+        );
+        assert!(!PartitionReference::Any.is_unique());
+    }
+}
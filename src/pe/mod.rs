@@ -0,0 +1,288 @@
+//! Minimal PE/COFF section reader.
+//!
+//! Only understands as much of the format as is needed to locate named
+//! sections, e.g. the `.osrel`, `.cmdline` and `.uname` sections embedded
+//! in Unified Kernel Images. Parsing works on the image headers alone, so
+//! callers can read section contents from disk without loading the whole
+//! image into memory.
+
+use core::ops::Range;
+
+use alloc::vec::Vec;
+
+/// Offset of the `e_lfanew` field in the DOS header.
+const DOS_LFANEW_OFFSET: usize = 0x3c;
+/// Size of the `PE\0\0` signature and the COFF file header.
+const PE_HEADER_LEN: usize = 4 + 20;
+/// Size of a single section table entry.
+const SECTION_HEADER_LEN: usize = 40;
+
+/// An entry of the PE section table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionHeader {
+    /// Section name, padded with NUL bytes.
+    pub name: [u8; 8],
+    /// Size of the section when loaded into memory.
+    pub virtual_size: u32,
+    /// Address of the section relative to the image base when loaded.
+    pub virtual_address: u32,
+    /// Size of the initialized data on disk, rounded to the file alignment.
+    pub size_of_raw_data: u32,
+    /// File offset of the section data.
+    pub pointer_to_raw_data: u32,
+}
+
+impl SectionHeader {
+    fn parse(raw: &[u8]) -> Self {
+        let mut name = [0; 8];
+        name.copy_from_slice(&raw[..8]);
+        Self {
+            name,
+            virtual_size: read_u32(raw, 8).unwrap_or_default(),
+            virtual_address: read_u32(raw, 12).unwrap_or_default(),
+            size_of_raw_data: read_u32(raw, 16).unwrap_or_default(),
+            pointer_to_raw_data: read_u32(raw, 20).unwrap_or_default(),
+        }
+    }
+
+    /// The section name with NUL padding removed.
+    #[must_use]
+    pub fn name(&self) -> &[u8] {
+        let len = self.name.iter().position(|&c| c == 0).unwrap_or(8);
+        &self.name[..len]
+    }
+
+    /// Byte range of the section contents within the file.
+    ///
+    /// The raw data is padded to the file alignment, so the virtual size is
+    /// used to trim the padding when it is smaller.
+    #[must_use]
+    pub fn file_range(&self) -> Range<usize> {
+        let len = match self.virtual_size {
+            0 => self.size_of_raw_data,
+            size => size.min(self.size_of_raw_data),
+        };
+        let start = self.pointer_to_raw_data as usize;
+        start..start.saturating_add(len as usize)
+    }
+}
+
+/// The section table of a PE image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeSections {
+    sections: Vec<SectionHeader>,
+}
+
+impl PeSections {
+    /// Parses the section table from the start of a PE image.
+    ///
+    /// `headers` only needs to cover the image headers, see
+    /// [`Self::headers_len`] to find out how much that is.
+    ///
+    /// # Errors
+    /// Returns a `PeError` if the data is not a PE image or is truncated.
+    pub fn parse(headers: &[u8]) -> Result<Self, PeError> {
+        let table = Self::section_table(headers)?;
+        let raw = headers.get(table).ok_or(PeError::Truncated)?;
+        Ok(Self {
+            sections: raw
+                .as_chunks::<SECTION_HEADER_LEN>()
+                .0
+                .iter()
+                .map(|raw| SectionHeader::parse(raw))
+                .collect(),
+        })
+    }
+
+    /// Returns how many bytes from the start of the image are needed to
+    /// parse the section table.
+    ///
+    /// # Errors
+    /// Returns a `PeError` if `prefix` is too short to tell, or is not a PE image.
+    pub fn headers_len(prefix: &[u8]) -> Result<usize, PeError> {
+        Self::section_table(prefix).map(|table| table.end)
+    }
+
+    /// All sections, in the order of the section table.
+    #[must_use]
+    pub fn sections(&self) -> &[SectionHeader] {
+        &self.sections
+    }
+
+    /// Looks up a section by name, e.g. `".osrel"`.
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<&SectionHeader> {
+        self.sections.iter().find(|s| s.name() == name.as_bytes())
+    }
+
+    /// Returns the contents of the named section from a fully loaded image.
+    ///
+    /// # Errors
+    /// Returns `PeError::SectionOutOfBounds` if the section points outside
+    /// of `image`.
+    pub fn section_data<'a>(
+        &self,
+        image: &'a [u8],
+        name: &str,
+    ) -> Result<Option<&'a [u8]>, PeError> {
+        self.find(name)
+            .map(|s| image.get(s.file_range()).ok_or(PeError::SectionOutOfBounds))
+            .transpose()
+    }
+
+    fn section_table(data: &[u8]) -> Result<Range<usize>, PeError> {
+        if data.get(..2) != Some(b"MZ") {
+            return Err(PeError::BadDosMagic);
+        }
+
+        // Offsets come from the file, so every addition is checked, `usize`
+        // is only 32 bits wide on i686.
+        let offset = |base: usize, add: usize| base.checked_add(add).ok_or(PeError::Truncated);
+
+        let pe_offset = read_u32(data, DOS_LFANEW_OFFSET).ok_or(PeError::Truncated)? as usize;
+        if data
+            .get(pe_offset..offset(pe_offset, 4)?)
+            .ok_or(PeError::Truncated)?
+            != b"PE\0\0"
+        {
+            return Err(PeError::BadPeSignature);
+        }
+
+        let num_sections =
+            read_u16(data, offset(pe_offset, 6)?).ok_or(PeError::Truncated)? as usize;
+        let optional_header_len =
+            read_u16(data, offset(pe_offset, 20)?).ok_or(PeError::Truncated)? as usize;
+
+        let start = offset(offset(pe_offset, PE_HEADER_LEN)?, optional_header_len)?;
+        let len = num_sections
+            .checked_mul(SECTION_HEADER_LEN)
+            .ok_or(PeError::Truncated)?;
+        Ok(start..offset(start, len)?)
+    }
+}
+
+/// Errors that can occur when reading a PE image.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror_no_std::Error)]
+pub enum PeError {
+    /// The image does not start with the `MZ` DOS header magic.
+    #[error("Missing DOS header")]
+    BadDosMagic,
+
+    /// The `PE\0\0` signature was not found where the DOS header points.
+    #[error("Missing PE signature")]
+    BadPeSignature,
+
+    /// The headers end before the section table does.
+    #[error("Truncated PE headers")]
+    Truncated,
+
+    /// A section's data lies outside of the image.
+    #[error("Section out of bounds")]
+    SectionOutOfBounds,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset.checked_add(2)?)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset.checked_add(4)?)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stripped down UKI with `.osrel`, `.cmdline`, `.uname` and a dummy
+    /// `.linux` section, generated by `testdata/mkuki.py`.
+    const UKI: &[u8] = include_bytes!("testdata/uki-minimal.efi");
+
+    #[test]
+    fn lists_uki_sections() {
+        let sections = PeSections::parse(UKI).unwrap();
+        let names: Vec<&[u8]> = sections
+            .sections()
+            .iter()
+            .map(SectionHeader::name)
+            .collect();
+        assert_eq!(names, [&b".osrel"[..], b".cmdline", b".uname", b".linux"]);
+    }
+
+    #[test]
+    fn reads_section_contents() {
+        let sections = PeSections::parse(UKI).unwrap();
+        let osrel = sections.section_data(UKI, ".osrel").unwrap().unwrap();
+        assert!(osrel.starts_with(b"NAME=\"Arch Linux\"\n"));
+        assert_eq!(
+            sections.section_data(UKI, ".cmdline").unwrap(),
+            Some(&b"root=UUID=0a3407de rw quiet"[..])
+        );
+        assert_eq!(
+            sections.section_data(UKI, ".uname").unwrap(),
+            Some(&b"6.9.1-arch1-1"[..])
+        );
+        assert_eq!(sections.section_data(UKI, ".dtb").unwrap(), None);
+    }
+
+    #[test]
+    fn parses_from_headers_only() {
+        let len = PeSections::headers_len(&UKI[..512]).unwrap();
+        assert!(len <= 512);
+        assert_eq!(
+            PeSections::parse(&UKI[..len]).unwrap(),
+            PeSections::parse(UKI).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_non_pe_data() {
+        assert_eq!(
+            PeSections::parse(b"#!/bin/sh\n").unwrap_err(),
+            PeError::BadDosMagic
+        );
+
+        let mut bad_signature = UKI[..512].to_vec();
+        bad_signature[0x40] = b'X';
+        assert_eq!(
+            PeSections::parse(&bad_signature).unwrap_err(),
+            PeError::BadPeSignature
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_headers() {
+        assert_eq!(
+            PeSections::parse(&UKI[..0x40]).unwrap_err(),
+            PeError::Truncated
+        );
+        assert_eq!(
+            PeSections::parse(&UKI[..0x100]).unwrap_err(),
+            PeError::Truncated
+        );
+    }
+
+    #[test]
+    fn rejects_header_offsets_past_the_end() {
+        let mut far = UKI[..512].to_vec();
+        far[DOS_LFANEW_OFFSET..DOS_LFANEW_OFFSET + 4]
+            .copy_from_slice(&0xFFFF_FFF0_u32.to_le_bytes());
+        assert_eq!(PeSections::parse(&far).unwrap_err(), PeError::Truncated);
+        assert_eq!(
+            PeSections::headers_len(&far).unwrap_err(),
+            PeError::Truncated
+        );
+        assert_eq!(read_u32(&far, usize::MAX - 1), None);
+        assert_eq!(read_u16(&far, usize::MAX), None);
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_sections() {
+        let sections = PeSections::parse(UKI).unwrap();
+        assert_eq!(
+            sections.section_data(&UKI[..0x200], ".osrel").unwrap_err(),
+            PeError::SectionOutOfBounds
+        );
+    }
+}
@@ -0,0 +1,49 @@
+#!/usr/bin/env python3
+"""Generates uki-minimal.efi, a tiny PE32+ image laid out like a Unified
+Kernel Image. Only the headers and section table are meaningful, the
+`.linux` section holds a placeholder instead of a kernel."""
+
+import struct
+
+FILE_ALIGNMENT = 0x200
+PE_OFFSET = 0x40
+OPTIONAL_HEADER_LEN = 240
+
+SECTIONS = [
+    (b".osrel", b'NAME="Arch Linux"\nPRETTY_NAME="Arch Linux"\nID=arch\nBUILD_ID=rolling\n'),
+    (b".cmdline", b"root=UUID=0a3407de rw quiet"),
+    (b".uname", b"6.9.1-arch1-1"),
+    (b".linux", b"MZ placeholder kernel"),
+]
+
+
+def align(n):
+    return (n + FILE_ALIGNMENT - 1) // FILE_ALIGNMENT * FILE_ALIGNMENT
+
+
+dos = bytearray(PE_OFFSET)
+dos[0:2] = b"MZ"
+struct.pack_into("<I", dos, 0x3C, PE_OFFSET)
+
+coff = struct.pack("<HHIIIHH", 0x8664, len(SECTIONS), 0, 0, 0, OPTIONAL_HEADER_LEN, 0x0206)
+optional = bytearray(OPTIONAL_HEADER_LEN)
+struct.pack_into("<H", optional, 0, 0x20B)
+struct.pack_into("<II", optional, 32, 0x1000, FILE_ALIGNMENT)
+
+headers_len = PE_OFFSET + 4 + len(coff) + OPTIONAL_HEADER_LEN + 40 * len(SECTIONS)
+offset = align(headers_len)
+table = bytearray()
+body = bytearray()
+for i, (name, data) in enumerate(SECTIONS):
+    raw_size = align(len(data))
+    table += struct.pack(
+        "<8sIIIIIIHHI",
+        name, len(data), 0x1000 * (i + 1), raw_size, offset + len(body), 0, 0, 0, 0, 0x40000040,
+    )
+    body += data.ljust(raw_size, b"\0")
+
+image = bytes(dos) + b"PE\0\0" + coff + bytes(optional) + bytes(table)
+image = image.ljust(offset, b"\0") + bytes(body)
+
+with open("uki-minimal.efi", "wb") as f:
+    f.write(image)
@@ -1,17 +1,40 @@
 //! Boot menu interface.
 //!
 //! Renders the list of configured boot targets and handles user input
-//! to select and boot one.
+//! to select and boot one. Selecting a group opens its entries in a nested
+//! menu, which Left or Escape leave again. Ctrl+Enter reboots into the
+//! selected entry once, see [`DisplayEntry::boot_next`].
 
+use alloc::string::String;
+use serde::Deserialize;
 use uefi::proto::console::text::{Key, ScanCode};
+use uefi::runtime::ResetType;
+use uefi::Status;
 
 use crate::{
+    config::DefaultEntry,
     core::app::{App, AppCtx, AppResult, DisplayEntry},
+    core::input::InputEvent,
+    core::system::SystemInfo,
+    ui::editor::CmdlineEditor,
     ui::overlay::ErrorOverlay,
+    ui::system_info::SystemInfoView,
     ui::theme::Theme,
     AppError,
 };
 
+/// Whether the boot menu is shown, set by `menu` in `plex.toml`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuMode {
+    /// The menu is shown, and the default entry booted after `timeout`.
+    #[default]
+    Shown,
+    /// The default entry is booted right away, unless Space or Shift is held
+    /// during the `reveal_window`, see [`crate::core::input::reveal_requested`].
+    Hidden,
+}
+
 /// The main boot menu interface for displaying and selecting boot targets.
 pub struct BootMenu<'a, T>
 where
@@ -20,16 +43,110 @@ where
     targets: &'a mut [T],
     selected: usize,
     theme: Theme,
+    /// Seconds left before the selected entry is booted automatically.
+    countdown: Option<u64>,
+    /// Whether the countdown ran out, so a group holding the default entry
+    /// boots it without waiting.
+    timed_out: bool,
+    /// The entry selected at startup, looked up again inside groups.
+    default: DefaultEntry,
+    /// Shown with `I`, with the name of the machine profile in use.
+    system: Option<(&'a SystemInfo, Option<&'a str>)>,
+    /// Label of the group a nested menu shows, `None` at the top level.
+    title: Option<String>,
 }
 
 impl<'a, T: App + DisplayEntry> BootMenu<'a, T> {
     /// Creates a new boot menu to manage the provided list of targets.
+    ///
+    /// The `default` entry is preselected, and booted once `timeout`
+    /// seconds pass without a keypress.
     #[must_use]
-    pub const fn new(targets: &'a mut [T], theme: Theme) -> Self {
+    pub fn new(
+        targets: &'a mut [T],
+        theme: Theme,
+        timeout: Option<u64>,
+        default: &DefaultEntry,
+    ) -> Self {
+        let selected = Self::resolve_default(targets, default).unwrap_or_else(|| {
+            log::warn!("default entry {default:?} not found, selecting the first entry");
+            0
+        });
         Self {
             targets,
-            selected: 0,
+            selected,
             theme,
+            countdown: timeout,
+            timed_out: false,
+            default: default.clone(),
+            system: None,
+            title: None,
+        }
+    }
+
+    /// Turns the menu into the nested menu of the group labelled `title`,
+    /// which Left and Escape leave.
+    #[must_use]
+    pub(crate) fn nested(mut self, title: String) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    /// Lets the user look up `system` and the machine `profile` in use.
+    #[must_use]
+    pub const fn with_system_info(
+        mut self,
+        system: &'a SystemInfo,
+        profile: Option<&'a str>,
+    ) -> Self {
+        self.system = Some((system, profile));
+        self
+    }
+
+    fn resolve_default(targets: &[T], default: &DefaultEntry) -> Option<usize> {
+        // An entry inside a group selects the group.
+        let position = |matches: &dyn Fn(&T) -> bool| {
+            targets.iter().position(|t| {
+                matches(t)
+                    || t.entries()
+                        .is_some_and(|entries| entries.iter().any(matches))
+            })
+        };
+        match default {
+            DefaultEntry::First => Some(0),
+            DefaultEntry::Index(idx) => (*idx < targets.len()).then_some(*idx),
+            DefaultEntry::Label(label) => position(&|t| t.display_options().label == *label),
+            DefaultEntry::Id(id) => position(&|t| t.id() == *id),
+            DefaultEntry::Saved => {
+                let saved = crate::core::nvram::load_last_booted()?;
+                // The entry may have been removed since it was booted, in which
+                // case the caller falls back to the first entry.
+                position(&|t| t.id() == saved)
+            }
+        }
+    }
+
+    /// Finds the entry that `default` selects, looking inside groups, so it
+    /// can be booted without showing the menu. Unlike the menu, this does not
+    /// fall back to the first entry.
+    pub fn default_target<'t>(targets: &'t mut [T], default: &DefaultEntry) -> Option<&'t mut T> {
+        let selected = Self::resolve_default(targets, default)?;
+        let target = targets.get_mut(selected)?;
+        if target.entries().is_none() {
+            return Some(target);
+        }
+        let default = Self::group_default(default, true);
+        Self::default_target(target.entries_mut()?, &default)
+    }
+
+    /// The entry preselected in a group, `holds_default` telling whether the
+    /// group contains the `default` of its parent menu.
+    fn group_default(default: &DefaultEntry, holds_default: bool) -> DefaultEntry {
+        match default {
+            DefaultEntry::Label(_) | DefaultEntry::Id(_) | DefaultEntry::Saved if holds_default => {
+                default.clone()
+            }
+            _ => DefaultEntry::First,
         }
     }
 
@@ -45,68 +162,205 @@ impl<'a, T: App + DisplayEntry> BootMenu<'a, T> {
         self.selected
     }
 
+    /// Returns the label of the group shown by a nested menu.
+    #[must_use]
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Returns the seconds left before the selected entry is booted, if the
+    /// countdown is still running.
+    #[must_use]
+    pub const fn countdown(&self) -> Option<u64> {
+        self.countdown
+    }
+
+    /// Restarts the countdown with `seconds` left, or cancels it with `None`,
+    /// e.g. once an overlay shown before the menu used up the timeout.
+    pub const fn set_countdown(&mut self, seconds: Option<u64>) {
+        self.countdown = seconds;
+    }
+
     /// Draws boot options to the buff.
     ///
     /// # Errors
     /// Returns any drawing error from the current theme.
     pub fn draw(&mut self, ctx: &mut AppCtx) -> Result<(), AppError> {
-        self.theme.draw_boot_menu(ctx, self)
+        self.theme.draw_boot_menu(ctx.display, self)
     }
 
-    /// Handle arrow key input and return the selected index when Enter is pressed.
+    /// Opens the command line editor for the selected entry. Returns `true`
+    /// if the edit was confirmed and the entry should be booted.
+    fn edit_selected(&mut self, ctx: &mut AppCtx) -> Result<bool, AppError> {
+        let Some(target) = self.targets.get_mut(self.selected) else {
+            return Ok(false);
+        };
+        let Some(cmdline) = target.cmdline() else {
+            return Ok(false);
+        };
+
+        let label = target.display_options().label;
+        let mut editor = CmdlineEditor::new(&label, &cmdline, self.theme);
+        if let AppResult::Error(e) = editor.run(ctx) {
+            return Err(e);
+        }
+
+        match editor.accepted() {
+            Some(cmdline) => {
+                target.set_cmdline(&cmdline)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Arranges for the selected entry to be booted once on the next start
+    /// and resets the machine. Only returns if that is not possible.
+    fn reboot_into_selected(&self) -> AppError {
+        let Some(target) = self.targets.get(self.selected) else {
+            return AppError::Generic("no boot entries found");
+        };
+        if let Err(e) = target.boot_next() {
+            return e;
+        }
+        log::info!("rebooting into \"{}\"", target.display_options().label);
+        uefi::runtime::reset(ResetType::COLD, Status::SUCCESS, None)
+    }
+
+    /// Handle arrow key input and return the selected index when Enter is pressed,
+    /// when the countdown runs out, or when an edited command line is confirmed.
+    /// Returns `None` when a nested menu is left with Left or Escape.
     ///
     /// # Errors
     /// Returns any input or drawing error while interacting with the boot menu.
-    pub fn wait_for_selection(&mut self, ctx: &mut AppCtx) -> Result<usize, AppError> {
+    pub fn wait_for_selection(&mut self, ctx: &mut AppCtx) -> Result<Option<usize>, AppError> {
+        if self.countdown == Some(0) {
+            self.countdown = None;
+            self.timed_out = true;
+            return Ok(Some(self.selected));
+        }
+
         loop {
             self.draw(ctx)?;
 
-            // unchecked because Option::<NonNull>::None.unwrap_unchecked() == 0
-            // due to the niche optimization with valid size and alignment.
-            let mut events = [unsafe { ctx.input.wait_for_key_event().unwrap_unchecked() }];
-
-            uefi::boot::wait_for_event(&mut events)
-                .map_err(|_| uefi::Error::from(uefi::Status::INVALID_PARAMETER))?;
-
-            // Read the key
-            if let Some(key) = ctx.input.read_key()? {
-                match key {
-                    Key::Special(ScanCode::UP) => {
-                        if self.selected > 0 {
-                            self.selected -= 1;
-                        } else {
-                            // wrap around
-                            self.selected = self.targets.len().saturating_sub(1);
-                        }
+            let key = match ctx.input.next_event(self.countdown.is_some())? {
+                InputEvent::Tick => {
+                    let remaining = self.countdown.unwrap_or(0).saturating_sub(1);
+                    if remaining == 0 {
+                        self.countdown = None;
+                        self.timed_out = true;
+                        return Ok(Some(self.selected));
                     }
-                    Key::Special(ScanCode::DOWN) => {
-                        if self.selected < self.targets.len().saturating_sub(1) {
-                            self.selected += 1;
-                        } else {
-                            // wrap around
-                            self.selected = 0;
-                        }
+                    self.countdown = Some(remaining);
+                    continue;
+                }
+                InputEvent::Key(key) => key,
+            };
+
+            // Any keypress cancels the countdown.
+            self.countdown = None;
+
+            match key {
+                Key::Special(ScanCode::UP) => {
+                    if self.selected > 0 {
+                        self.selected -= 1;
+                    } else {
+                        // wrap around
+                        self.selected = self.targets.len().saturating_sub(1);
+                    }
+                }
+                Key::Special(ScanCode::DOWN) => {
+                    if self.selected < self.targets.len().saturating_sub(1) {
+                        self.selected += 1;
+                    } else {
+                        // wrap around
+                        self.selected = 0;
+                    }
+                }
+                // Ctrl+Enter boots the entry once after a reboot.
+                Key::Printable(c)
+                    if (c == '\r' || c == '\n')
+                        && self
+                            .targets
+                            .get(self.selected)
+                            .is_some_and(|t| t.entries().is_none())
+                        && ctx.input.control_held() =>
+                {
+                    let err = self.reboot_into_selected();
+                    if let AppResult::Error(e) = ErrorOverlay::new(&err, self.theme).run(ctx) {
+                        return Err(e);
                     }
-                    Key::Printable(c) if c == '\r' || c == '\n' => {
-                        return Ok(self.selected);
+                }
+                Key::Printable(c) if c == '\r' || c == '\n' => {
+                    return Ok(Some(self.selected));
+                }
+                Key::Special(ScanCode::RIGHT)
+                    if self
+                        .targets
+                        .get(self.selected)
+                        .is_some_and(|t| t.entries().is_some()) =>
+                {
+                    return Ok(Some(self.selected));
+                }
+                Key::Special(ScanCode::LEFT | ScanCode::ESCAPE) if self.title.is_some() => {
+                    return Ok(None);
+                }
+                Key::Printable(c) if c == 'i' || c == 'I' => {
+                    if let Some((system, profile)) = self.system {
+                        let mut view = SystemInfoView::new(system, profile, self.theme);
+                        if let AppResult::Error(e) = view.run(ctx) {
+                            return Err(e);
+                        }
                     }
-                    _ => {}
                 }
+                // Editing only boots if the new command line is confirmed.
+                Key::Printable(c) if (c == 'e' || c == 'E') && self.edit_selected(ctx)? => {
+                    return Ok(Some(self.selected));
+                }
+                _ => {}
             }
         }
     }
 }
 
+impl<T: App + DisplayEntry> BootMenu<'_, T> {
+    /// Runs the selected entry, or opens it as a nested menu if it is a
+    /// group.
+    fn run_selected(&mut self, ctx: &mut AppCtx, selection: usize) -> AppResult {
+        let timed_out = core::mem::take(&mut self.timed_out);
+        let Some(target) = self.targets.get(selection) else {
+            return AppResult::Error(AppError::Generic("no boot entries found"));
+        };
+        if target.entries().is_none() {
+            return self.targets[selection].run(ctx);
+        }
+
+        // Preselect the default entry if it is in this group.
+        let holds_default = Self::resolve_default(self.targets, &self.default) == Some(selection);
+        let default = Self::group_default(&self.default, holds_default);
+        let timeout = timed_out.then_some(0);
+        let title = target.display_options().label;
+        let (theme, system) = (self.theme, self.system);
+        let Some(entries) = self.targets[selection].entries_mut() else {
+            return AppResult::Done;
+        };
+
+        BootMenu {
+            system,
+            ..BootMenu::new(entries, theme, timeout, &default)
+        }
+        .nested(title)
+        .run(ctx)
+    }
+}
+
 impl<T: App + DisplayEntry> App for BootMenu<'_, T> {
     fn run(&mut self, ctx: &mut AppCtx) -> AppResult {
         loop {
-            let selection = self.wait_for_selection(ctx);
-            let result = match selection {
-                Ok(selection) => {
-                    let bootable = self.targets.get_mut(selection).unwrap();
-                    bootable.run(ctx)
-                }
-
+            let result = match self.wait_for_selection(ctx) {
+                Ok(Some(selection)) => self.run_selected(ctx, selection),
+                // Back to the parent menu.
+                Ok(None) => return AppResult::Done,
                 Err(e) => {
                     log::error!("encountered an error in boot menu loop: {e}");
                     AppResult::Done
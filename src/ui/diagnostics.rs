@@ -0,0 +1,220 @@
+//! Overlay listing the problems found in `plex.toml`.
+//!
+//! Shown at startup when loading or validating the configuration reported
+//! anything, see [`crate::config::validate`]. The list scrolls one
+//! diagnostic at a time. With a countdown, the overlay closes on its own
+//! when it runs out, so a warning does not keep the machine from booting.
+
+use alloc::format;
+use alloc::string::String;
+use uefi::proto::console::text::{Key, ScanCode};
+
+use crate::config::validate::{Diagnostic, Severity};
+use crate::core::app::{App, AppCtx, AppResult};
+use crate::core::input::InputEvent;
+use crate::ui::theme::Theme;
+
+/// A modal list of diagnostics, dismissed with Enter or Esc.
+pub struct DiagnosticsOverlay<'a> {
+    diagnostics: &'a [Diagnostic],
+    /// Index of the first diagnostic shown.
+    scroll: usize,
+    /// Seconds left before the overlay closes on its own.
+    countdown: Option<u64>,
+    theme: Theme,
+}
+
+impl<'a> DiagnosticsOverlay<'a> {
+    /// Diagnostics skipped by Page Up and Page Down.
+    const PAGE: usize = 5;
+
+    /// Creates an overlay showing `diagnostics`, scrolled to the top.
+    #[must_use]
+    pub const fn new(diagnostics: &'a [Diagnostic], theme: Theme) -> Self {
+        Self {
+            diagnostics,
+            scroll: 0,
+            countdown: None,
+            theme,
+        }
+    }
+
+    /// Closes the overlay on its own after `seconds`, unless a key is
+    /// pressed first. `None` waits for the user.
+    #[must_use]
+    pub const fn with_countdown(mut self, seconds: Option<u64>) -> Self {
+        self.countdown = seconds;
+        self
+    }
+
+    /// Returns the seconds left before the overlay closes on its own, if
+    /// the countdown is still running. `Some(0)` once it ran out.
+    #[must_use]
+    pub const fn countdown(&self) -> Option<u64> {
+        self.countdown
+    }
+
+    /// The diagnostics from the current scroll position on.
+    #[must_use]
+    pub fn visible(&self) -> &[Diagnostic] {
+        &self.diagnostics[self.scroll.min(self.diagnostics.len())..]
+    }
+
+    /// Whether diagnostics are hidden above the visible ones.
+    #[must_use]
+    pub const fn scrolled(&self) -> bool {
+        self.scroll > 0
+    }
+
+    /// A summary like `2 errors, 1 warning in plex.toml`.
+    #[must_use]
+    pub fn title(&self) -> String {
+        let count = |severity| {
+            self.diagnostics
+                .iter()
+                .filter(|d| d.severity == severity)
+                .count()
+        };
+        let plural = |n: usize, word: &str| {
+            if n == 1 {
+                format!("1 {word}")
+            } else {
+                format!("{n} {word}s")
+            }
+        };
+
+        let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+        let summary = match (errors, warnings) {
+            (0, warnings) => plural(warnings, "warning"),
+            (errors, 0) => plural(errors, "error"),
+            (errors, warnings) => {
+                format!(
+                    "{}, {}",
+                    plural(errors, "error"),
+                    plural(warnings, "warning")
+                )
+            }
+        };
+        format!("{summary} in plex.toml")
+    }
+
+    /// Counts down one second, returning `true` once the countdown ran out.
+    fn tick(&mut self) -> bool {
+        let remaining = self.countdown.unwrap_or(0).saturating_sub(1);
+        self.countdown = Some(remaining);
+        remaining == 0
+    }
+
+    /// Applies a key press, returning `true` once the overlay is dismissed.
+    /// Any key cancels the countdown.
+    fn handle_key(&mut self, key: Key) -> bool {
+        self.countdown = None;
+        let last = self.diagnostics.len().saturating_sub(1);
+        match key {
+            Key::Special(ScanCode::ESCAPE) => return true,
+            Key::Printable(c) if c == '\r' || c == '\n' => return true,
+            Key::Special(ScanCode::UP) => self.scroll = self.scroll.saturating_sub(1),
+            Key::Special(ScanCode::DOWN) => self.scroll = (self.scroll + 1).min(last),
+            Key::Special(ScanCode::PAGE_UP) => self.scroll = self.scroll.saturating_sub(Self::PAGE),
+            Key::Special(ScanCode::PAGE_DOWN) => self.scroll = (self.scroll + Self::PAGE).min(last),
+            Key::Special(ScanCode::HOME) => self.scroll = 0,
+            Key::Special(ScanCode::END) => self.scroll = last,
+            _ => {}
+        }
+        false
+    }
+}
+
+impl App for DiagnosticsOverlay<'_> {
+    fn run(&mut self, ctx: &mut AppCtx) -> AppResult {
+        if self.countdown == Some(0) {
+            return AppResult::Done;
+        }
+
+        loop {
+            if let Err(e) = self.theme.draw_diagnostics(ctx.display, self) {
+                return AppResult::Error(e);
+            }
+
+            let closed = match ctx.input.next_event(self.countdown.is_some()) {
+                Ok(InputEvent::Tick) => self.tick(),
+                Ok(InputEvent::Key(key)) => self.handle_key(key),
+                Err(e) => return AppResult::Error(e),
+            };
+            if closed {
+                return AppResult::Done;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostics(count: usize) -> alloc::vec::Vec<Diagnostic> {
+        (0..count)
+            .map(|idx| Diagnostic::warning(format!("duplicate label \"{idx}\"")))
+            .collect()
+    }
+
+    #[test]
+    fn scrolls_within_bounds() {
+        let diagnostics = diagnostics(8);
+        let mut overlay = DiagnosticsOverlay::new(&diagnostics, Theme::Default);
+
+        assert!(!overlay.handle_key(Key::Special(ScanCode::UP)));
+        assert!(!overlay.scrolled());
+        overlay.handle_key(Key::Special(ScanCode::DOWN));
+        assert_eq!(overlay.visible().len(), 7);
+        overlay.handle_key(Key::Special(ScanCode::PAGE_DOWN));
+        assert_eq!(overlay.visible().len(), 2);
+        overlay.handle_key(Key::Special(ScanCode::PAGE_DOWN));
+        assert_eq!(
+            overlay.visible().len(),
+            1,
+            "the last diagnostic stays visible"
+        );
+        overlay.handle_key(Key::Special(ScanCode::PAGE_UP));
+        assert_eq!(overlay.visible().len(), 6);
+        overlay.handle_key(Key::Special(ScanCode::HOME));
+        assert_eq!(overlay.visible().len(), 8);
+        overlay.handle_key(Key::Special(ScanCode::END));
+        assert_eq!(overlay.visible().len(), 1);
+        assert!(overlay.scrolled());
+
+        let mut empty = DiagnosticsOverlay::new(&[], Theme::Default);
+        empty.handle_key(Key::Special(ScanCode::DOWN));
+        empty.handle_key(Key::Special(ScanCode::END));
+        assert_eq!(empty.visible().len(), 0);
+        assert!(!empty.scrolled());
+    }
+
+    #[test]
+    fn closes_on_enter_or_escape() {
+        let diagnostics = diagnostics(1);
+        let mut overlay = DiagnosticsOverlay::new(&diagnostics, Theme::Default);
+
+        assert!(!overlay.handle_key(Key::Printable(uefi::Char16::try_from('a').unwrap())));
+        assert!(overlay.handle_key(Key::Printable(uefi::Char16::try_from('\r').unwrap())));
+        assert!(overlay.handle_key(Key::Special(ScanCode::ESCAPE)));
+    }
+
+    #[test]
+    fn closes_when_the_countdown_runs_out() {
+        let diagnostics = diagnostics(1);
+        let mut overlay =
+            DiagnosticsOverlay::new(&diagnostics, Theme::Default).with_countdown(Some(2));
+
+        assert!(!overlay.tick());
+        assert_eq!(overlay.countdown(), Some(1));
+        assert!(overlay.tick());
+        assert_eq!(overlay.countdown(), Some(0));
+
+        // Any key cancels the countdown, without closing the overlay.
+        let mut overlay =
+            DiagnosticsOverlay::new(&diagnostics, Theme::Default).with_countdown(Some(2));
+        assert!(!overlay.handle_key(Key::Special(ScanCode::DOWN)));
+        assert_eq!(overlay.countdown(), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! Single-line text editor overlay.
+//!
+//! Used to tweak a boot entry's command line right before booting it, e.g.
+//! to add `nomodeset` or `systemd.unit=rescue.target`. Changes only apply
+//! to the entry in memory, `plex.toml` is left untouched.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use uefi::proto::console::text::{Key, ScanCode};
+
+use crate::core::app::{App, AppCtx, AppResult};
+use crate::ui::theme::Theme;
+
+/// A modal editor for a single line of text.
+///
+/// Run it as an [`App`], then check [`Self::accepted`] to find out whether
+/// the user confirmed the edit with Enter or cancelled it with Esc.
+pub struct CmdlineEditor<'a> {
+    title: &'a str,
+    text: Vec<char>,
+    /// Cursor position, as an index into `text`.
+    cursor: usize,
+    accepted: bool,
+    theme: Theme,
+}
+
+impl<'a> CmdlineEditor<'a> {
+    /// Creates an editor for `text`, with the cursor placed at the end.
+    #[must_use]
+    pub fn new(title: &'a str, text: &str, theme: Theme) -> Self {
+        let text: Vec<char> = text.chars().collect();
+        Self {
+            title,
+            cursor: text.len(),
+            text,
+            accepted: false,
+            theme,
+        }
+    }
+
+    /// The title shown above the text, usually the entry's label.
+    #[must_use]
+    pub const fn title(&self) -> &str {
+        self.title
+    }
+
+    /// The current text.
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    /// The edited text if the user confirmed it, `None` if cancelled.
+    #[must_use]
+    pub fn accepted(&self) -> Option<String> {
+        self.accepted.then(|| self.text())
+    }
+
+    /// The part of the text that fits into `width` characters, scrolled so
+    /// the cursor stays visible, and the cursor column within it.
+    #[must_use]
+    pub fn visible(&self, width: usize) -> (String, usize) {
+        let width = width.max(1);
+        // Keep one column free for the cursor past the last character.
+        let start = (self.cursor + 1).saturating_sub(width);
+        let end = (start + width).min(self.text.len());
+        (self.text[start..end].iter().collect(), self.cursor - start)
+    }
+
+    /// Applies a key press, returning `true` once editing is finished.
+    fn handle_key(&mut self, key: Key) -> bool {
+        match key {
+            Key::Special(ScanCode::ESCAPE) => return true,
+            Key::Special(ScanCode::LEFT) => self.cursor = self.cursor.saturating_sub(1),
+            Key::Special(ScanCode::RIGHT) => self.cursor = (self.cursor + 1).min(self.text.len()),
+            Key::Special(ScanCode::HOME) => self.cursor = 0,
+            Key::Special(ScanCode::END) => self.cursor = self.text.len(),
+            Key::Special(ScanCode::DELETE) => {
+                if self.cursor < self.text.len() {
+                    self.text.remove(self.cursor);
+                }
+            }
+            Key::Printable(c) => match char::from(c) {
+                '\r' | '\n' => {
+                    self.accepted = true;
+                    return true;
+                }
+                '\u{8}' => {
+                    if self.cursor > 0 {
+                        self.cursor -= 1;
+                        self.text.remove(self.cursor);
+                    }
+                }
+                c if !c.is_control() => {
+                    self.text.insert(self.cursor, c);
+                    self.cursor += 1;
+                }
+                _ => {}
+            },
+            Key::Special(_) => {}
+        }
+        false
+    }
+}
+
+impl App for CmdlineEditor<'_> {
+    fn run(&mut self, ctx: &mut AppCtx) -> AppResult {
+        loop {
+            if let Err(e) = self.theme.draw_cmdline_editor(ctx.display, self) {
+                return AppResult::Error(e);
+            }
+
+            match ctx.input.next_key() {
+                Ok(key) => {
+                    if self.handle_key(key) {
+                        return AppResult::Done;
+                    }
+                }
+                Err(e) => return AppResult::Error(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> Key {
+        Key::Printable(uefi::Char16::try_from(c).unwrap())
+    }
+
+    fn special(scan_code: ScanCode) -> Key {
+        Key::Special(scan_code)
+    }
+
+    #[test]
+    fn edits_at_the_cursor() {
+        let mut editor = CmdlineEditor::new("Arch", "rw quiet", Theme::Default);
+        for c in " nomodeset".chars() {
+            assert!(!editor.handle_key(key(c)));
+        }
+        assert_eq!(editor.text(), "rw quiet nomodeset");
+
+        editor.handle_key(special(ScanCode::HOME));
+        editor.handle_key(special(ScanCode::DELETE));
+        editor.handle_key(key('o'));
+        assert_eq!(editor.text(), "ow quiet nomodeset");
+
+        editor.handle_key(special(ScanCode::END));
+        editor.handle_key(special(ScanCode::LEFT));
+        editor.handle_key(key('\u{8}'));
+        assert_eq!(editor.text(), "ow quiet nomodest");
+        // Control characters other than backspace are ignored.
+        editor.handle_key(key('\t'));
+        assert_eq!(editor.text(), "ow quiet nomodest");
+    }
+
+    #[test]
+    fn keeps_the_cursor_in_bounds() {
+        let mut editor = CmdlineEditor::new("Arch", "ro", Theme::Default);
+        editor.handle_key(special(ScanCode::RIGHT));
+        editor.handle_key(special(ScanCode::DELETE));
+        assert_eq!(editor.text(), "ro");
+        assert_eq!(editor.visible(80), (String::from("ro"), 2));
+
+        editor.handle_key(special(ScanCode::HOME));
+        editor.handle_key(special(ScanCode::LEFT));
+        editor.handle_key(key('\u{8}'));
+        assert_eq!(editor.text(), "ro");
+        assert_eq!(editor.visible(80), (String::from("ro"), 0));
+
+        let mut empty = CmdlineEditor::new("Arch", "", Theme::Default);
+        empty.handle_key(key('\u{8}'));
+        empty.handle_key(special(ScanCode::DELETE));
+        empty.handle_key(special(ScanCode::LEFT));
+        assert_eq!(empty.visible(80), (String::new(), 0));
+    }
+
+    #[test]
+    fn scrolls_to_the_cursor() {
+        let mut editor = CmdlineEditor::new("Arch", "root=/dev/sda2 rw", Theme::Default);
+        // One column stays free for the cursor after the last character.
+        assert_eq!(editor.visible(8), (String::from("sda2 rw"), 7));
+        editor.handle_key(special(ScanCode::HOME));
+        assert_eq!(editor.visible(8), (String::from("root=/de"), 0));
+        assert_eq!(editor.visible(0), (String::from("r"), 0));
+    }
+
+    #[test]
+    fn accepts_or_cancels() {
+        let mut editor = CmdlineEditor::new("Arch", "rw", Theme::Default);
+        editor.handle_key(key(' '));
+        assert!(editor.handle_key(special(ScanCode::ESCAPE)));
+        assert_eq!(editor.accepted(), None);
+
+        let mut editor = CmdlineEditor::new("Arch", "rw", Theme::Default);
+        editor.handle_key(key('!'));
+        assert!(editor.handle_key(key('\r')));
+        assert_eq!(editor.accepted().as_deref(), Some("rw!"));
+    }
+}
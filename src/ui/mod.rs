@@ -4,5 +4,9 @@
 //! boot menu and error overlays, built on top of the `embedded-graphics` crate.
 
 pub mod boot_menu;
+pub mod diagnostics;
+pub mod editor;
 pub mod overlay;
+pub mod system_info;
+pub mod text;
 pub mod theme;
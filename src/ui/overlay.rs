@@ -25,24 +25,21 @@ impl<'a> ErrorOverlay<'a> {
 
 impl App for ErrorOverlay<'_> {
     fn run(&mut self, ctx: &mut AppCtx) -> AppResult {
-        if let Err(e) = self.theme.draw_error_overlay(ctx, self.error) {
+        if let Err(e) = self.theme.draw_error_overlay(ctx.display, self.error) {
             log::error!("failed to draw error overlay: {e}");
         }
 
         loop {
-            let mut events = [unsafe { ctx.input.wait_for_key_event().unwrap_unchecked() }];
+            let key = match ctx.input.next_key() {
+                Ok(key) => key,
+                Err(e) => return AppResult::Error(e),
+            };
 
-            if uefi::boot::wait_for_event(&mut events).is_err() {
-                return AppResult::Error(uefi::Status::INVALID_PARAMETER.into());
+            if matches!(key, Key::Printable(c) if c == '\r' || c == '\n') {
+                return AppResult::Done;
             }
-
-            if let Ok(Some(key)) = ctx.input.read_key() {
-                if matches!(key, Key::Printable(c) if c == '\r' || c == '\n') {
-                    return AppResult::Done;
-                }
-                if matches!(key, Key::Special(ScanCode::END | ScanCode::ESCAPE)) {
-                    return AppResult::Done;
-                }
+            if matches!(key, Key::Special(ScanCode::END | ScanCode::ESCAPE)) {
+                return AppResult::Done;
             }
         }
     }
@@ -0,0 +1,119 @@
+//! Overlay showing what plex knows about the machine.
+//!
+//! Opened with `I` from the boot menu. Lists the facts `when` conditions
+//! and machine profiles are matched against, and the profile in use, to
+//! help writing them.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use uefi::proto::console::text::{Key, ScanCode};
+
+use crate::core::app::{App, AppCtx, AppResult};
+use crate::core::smbios::Smbios;
+use crate::core::system::SystemInfo;
+use crate::ui::theme::Theme;
+
+/// A modal view of the [`SystemInfo`], dismissed with Enter or Esc.
+pub struct SystemInfoView<'a> {
+    system: &'a SystemInfo,
+    profile: Option<&'a str>,
+    theme: Theme,
+}
+
+impl<'a> SystemInfoView<'a> {
+    /// Title of the overlay.
+    pub const TITLE: &'static str = "System information";
+
+    /// Creates a view of `system`, where `profile` names the machine profile
+    /// in use, if any.
+    #[must_use]
+    pub const fn new(system: &'a SystemInfo, profile: Option<&'a str>, theme: Theme) -> Self {
+        Self {
+            system,
+            profile,
+            theme,
+        }
+    }
+
+    /// The facts shown, as name and value.
+    #[must_use]
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let or_unknown = |value: &str| {
+            if value.is_empty() {
+                String::from("unknown")
+            } else {
+                String::from(value)
+            }
+        };
+        let smbios = self.system.smbios.as_ref();
+        let smbios_field = |field: fn(&Smbios) -> &String| {
+            smbios.map_or_else(
+                || String::from("no SMBIOS tables"),
+                |s| or_unknown(field(s)),
+            )
+        };
+
+        alloc::vec![
+            ("Architecture", String::from(self.system.arch)),
+            (
+                "Secure Boot",
+                String::from(if self.system.secure_boot {
+                    "enabled"
+                } else {
+                    "disabled"
+                }),
+            ),
+            ("Firmware", or_unknown(&self.system.firmware_vendor)),
+            (
+                "Firmware revision",
+                format!("{:#x}", self.system.firmware_revision)
+            ),
+            ("Manufacturer", smbios_field(|s| &s.manufacturer)),
+            ("Product", smbios_field(|s| &s.product)),
+            ("Serial", smbios_field(|s| &s.serial)),
+            (
+                "UUID",
+                smbios
+                    .and_then(|s| s.uuid)
+                    .map_or_else(|| String::from("unknown"), |uuid| format!("{uuid}")),
+            ),
+            (
+                "Machine profile",
+                String::from(self.profile.unwrap_or("none")),
+            ),
+        ]
+    }
+
+    /// The rows formatted as `name: value` lines, with the values aligned.
+    #[must_use]
+    pub fn lines(&self) -> Vec<String> {
+        let rows = self.rows();
+        // Room for the longest name and its colon.
+        let width = rows
+            .iter()
+            .map(|(name, _)| name.len() + 1)
+            .max()
+            .unwrap_or(0);
+        rows.into_iter()
+            .map(|(name, value)| format!("{:<width$}  {value}", format!("{name}:")))
+            .collect()
+    }
+}
+
+impl App for SystemInfoView<'_> {
+    fn run(&mut self, ctx: &mut AppCtx) -> AppResult {
+        loop {
+            if let Err(e) = self.theme.draw_system_info(ctx.display, self) {
+                return AppResult::Error(e);
+            }
+
+            match ctx.input.next_key() {
+                Ok(Key::Special(ScanCode::ESCAPE)) => return AppResult::Done,
+                Ok(Key::Printable(c)) if c == '\r' || c == '\n' => return AppResult::Done,
+                Ok(_) => {}
+                Err(e) => return AppResult::Error(e),
+            }
+        }
+    }
+}
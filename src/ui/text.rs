@@ -0,0 +1,224 @@
+//! Text-mode rendering of the boot menu and overlays.
+//!
+//! Used on a [`TextDisplay`] instead of the graphical themes, e.g. on headless
+//! machines without GOP or when the console is mirrored to a serial line.
+//! Frames are drawn with box-drawing characters, which the firmware
+//! translates for terminals that cannot display them.
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::{
+    config::validate::Severity,
+    core::app::{App, DisplayEntry},
+    core::display::{TextDisplay, TextStyle},
+    ui::boot_menu::BootMenu,
+    ui::diagnostics::DiagnosticsOverlay,
+    ui::editor::CmdlineEditor,
+    ui::system_info::SystemInfoView,
+    ui::theme::{
+        countdown_text, diagnostic_lines, diagnostics_hint, entry_label, system_info_lines,
+        LineWrapper, EDITOR_HINT, SYSTEM_INFO_HINT,
+    },
+    AppError,
+};
+
+const TITLE: &str = " plex ~ boot ";
+
+/// # Errors
+/// Returns any error from the underlying console.
+pub fn draw_boot_menu<T: App + DisplayEntry>(
+    display: &mut TextDisplay,
+    menu: &BootMenu<'_, T>,
+) -> Result<(), AppError> {
+    let (cols, rows) = display.size();
+    display.clear();
+    display.draw_box(0, 0, cols, rows, TextStyle::Accent);
+    // Nested menus add the label of their group.
+    let title = menu
+        .title()
+        .map_or_else(|| String::from(TITLE), |group| format!("{TITLE}> {group} "));
+    display.put_str(
+        cols.saturating_sub(title.chars().count()) / 2,
+        0,
+        &title,
+        TextStyle::Accent,
+    );
+
+    // Rows between the title border and the footer.
+    let list_top = 2;
+    let list_rows = rows.saturating_sub(list_top + 3).max(1);
+    let first = (menu.selected() + 1).saturating_sub(list_rows);
+    let inner_width = cols.saturating_sub(4);
+
+    for (i, target) in menu
+        .targets()
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(list_rows)
+    {
+        let row = list_top + i - first;
+        let label = entry_label(target);
+        if i == menu.selected() {
+            display.fill(2, row, inner_width, 1, ' ', TextStyle::Selected);
+            display.put_str(2, row, &format!("> {label}"), TextStyle::Selected);
+        } else {
+            display.put_str(4, row, &label, TextStyle::Normal);
+        }
+    }
+
+    let hint = menu.countdown().map_or_else(
+        || String::from("UP/DOWN to navigate, ENTER to boot, E to edit, I for info"),
+        countdown_text,
+    );
+    display.put_str(2, rows.saturating_sub(2), &hint, TextStyle::Dim);
+
+    display.flush()
+}
+
+/// # Errors
+/// Returns any error from the underlying console.
+pub fn draw_error_overlay(display: &mut TextDisplay, error: &AppError) -> Result<(), AppError> {
+    let text = format!("{error}");
+
+    let (cols, rows) = display.size();
+    let box_w = (cols * 2 / 3).max(30).min(cols);
+    let box_h = (rows / 3).max(7).min(rows);
+    let left = (cols - box_w) / 2;
+    let top = (rows - box_h) / 2;
+
+    display.draw_box(left, top, box_w, box_h, TextStyle::Error);
+    display.put_str(left + 2, top, " Error ", TextStyle::Error);
+
+    let wrapper = LineWrapper {
+        text: &text,
+        max_chars: box_w.saturating_sub(4).max(1),
+        max_lines: box_h.saturating_sub(4).max(1),
+        lines_yielded: 0,
+    };
+    for (idx, line) in wrapper.enumerate() {
+        display.put_str(left + 2, top + 1 + idx, line, TextStyle::Normal);
+    }
+
+    display.put_str(
+        left + 2,
+        top + box_h - 2,
+        "Press Enter or Esc to continue...",
+        TextStyle::Dim,
+    );
+
+    display.flush()
+}
+
+/// # Errors
+/// Returns any error from the underlying console.
+pub fn draw_cmdline_editor(
+    display: &mut TextDisplay,
+    editor: &CmdlineEditor,
+) -> Result<(), AppError> {
+    let (cols, rows) = display.size();
+    let box_w = cols.saturating_sub(4).max(20).min(cols);
+    let box_h = 6.min(rows);
+    let left = (cols - box_w) / 2;
+    let top = (rows - box_h) / 2;
+
+    display.draw_box(left, top, box_w, box_h, TextStyle::Accent);
+    display.put_str(
+        left + 2,
+        top,
+        &format!(" {} ", editor.title()),
+        TextStyle::Accent,
+    );
+
+    let (visible, cursor) = editor.visible(box_w.saturating_sub(4).max(1));
+    display.put_str(left + 2, top + 2, &visible, TextStyle::Normal);
+
+    // The console cursor is hidden, show it as a highlighted cell instead.
+    let under_cursor = visible.chars().nth(cursor).unwrap_or(' ');
+    let mut buf = [0; 4];
+    display.put_str(
+        left + 2 + cursor,
+        top + 2,
+        under_cursor.encode_utf8(&mut buf),
+        TextStyle::Selected,
+    );
+
+    display.put_str(left + 2, top + box_h - 2, EDITOR_HINT, TextStyle::Dim);
+
+    display.flush()
+}
+
+/// # Errors
+/// Returns any error from the underlying console.
+pub fn draw_diagnostics(
+    display: &mut TextDisplay,
+    overlay: &DiagnosticsOverlay,
+) -> Result<(), AppError> {
+    let (cols, rows) = display.size();
+    let box_w = cols.saturating_sub(4).max(30).min(cols);
+    let box_h = rows.saturating_sub(4).max(7).min(rows);
+    let left = (cols - box_w) / 2;
+    let top = (rows - box_h) / 2;
+
+    display.draw_box(left, top, box_w, box_h, TextStyle::Error);
+    display.put_str(
+        left + 2,
+        top,
+        &format!(" {} ", overlay.title()),
+        TextStyle::Error,
+    );
+
+    let lines = diagnostic_lines(
+        overlay,
+        box_w.saturating_sub(4).max(1),
+        box_h.saturating_sub(4).max(1),
+    );
+    for (idx, (severity, line)) in lines.iter().enumerate() {
+        let style = match severity {
+            Severity::Error => TextStyle::Normal,
+            Severity::Warning => TextStyle::Accent,
+        };
+        display.put_str(left + 2, top + 1 + idx, line, style);
+    }
+
+    display.put_str(
+        left + 2,
+        top + box_h - 2,
+        &diagnostics_hint(overlay),
+        TextStyle::Dim,
+    );
+
+    display.flush()
+}
+
+/// # Errors
+/// Returns any error from the underlying console.
+pub fn draw_system_info(display: &mut TextDisplay, view: &SystemInfoView) -> Result<(), AppError> {
+    let (cols, rows) = display.size();
+    let box_w = cols.saturating_sub(8).max(30).min(cols);
+    let box_h = rows.saturating_sub(4).clamp(7, 15).min(rows);
+    let left = (cols - box_w) / 2;
+    let top = (rows - box_h) / 2;
+
+    display.draw_box(left, top, box_w, box_h, TextStyle::Accent);
+    display.put_str(
+        left + 2,
+        top,
+        &format!(" {} ", SystemInfoView::TITLE),
+        TextStyle::Accent,
+    );
+
+    let lines = system_info_lines(
+        view,
+        box_w.saturating_sub(4).max(1),
+        box_h.saturating_sub(4).max(1),
+    );
+    for (idx, line) in lines.iter().enumerate() {
+        display.put_str(left + 2, top + 1 + idx, line, TextStyle::Normal);
+    }
+
+    display.put_str(left + 2, top + box_h - 2, SYSTEM_INFO_HINT, TextStyle::Dim);
+
+    display.flush()
+}
@@ -7,19 +7,26 @@ use embedded_graphics::{
 };
 
 use crate::{
-    core::app::{App, AppCtx, DisplayEntry},
+    config::validate::Severity,
+    core::app::{App, DisplayEntry},
+    core::display::Canvas,
     ui::boot_menu::BootMenu,
-    ui::theme::LineWrapper,
+    ui::diagnostics::DiagnosticsOverlay,
+    ui::editor::CmdlineEditor,
+    ui::system_info::SystemInfoView,
+    ui::theme::{
+        countdown_text, diagnostic_lines, diagnostics_hint, entry_label, system_info_lines,
+        LineWrapper, EDITOR_HINT, SYSTEM_INFO_HINT,
+    },
     AppError,
 };
 
 /// # Errors
 /// Returns any drawing error from the underlying display.
 pub fn draw_boot_menu<T: App + DisplayEntry>(
-    ctx: &mut AppCtx,
+    display: &mut Canvas,
     menu: &BootMenu<'_, T>,
 ) -> Result<(), AppError> {
-    let display = &mut *ctx.display;
     display.clear(Rgb888::new(0, 0, 0));
 
     let text_style = MonoTextStyle::new(&FONT_9X15, Rgb888::WHITE);
@@ -28,8 +35,13 @@ pub fn draw_boot_menu<T: App + DisplayEntry>(
     let start_y = 100;
     let line_height = 25;
 
+    if let Some(title) = menu.title() {
+        Text::new(title, Point::new(50, start_y - 40), text_style)
+            .draw(display)
+            .ok();
+    }
+
     for (i, target) in menu.targets().iter().enumerate() {
-        let display_opts = target.display_options();
         let y = start_y + i32::try_from(i * line_height).unwrap_or(i32::MAX);
         let position = Point::new(50, y);
 
@@ -46,12 +58,22 @@ pub fn draw_boot_menu<T: App + DisplayEntry>(
         } else {
             text_style
         };
-        Text::new(display_opts.label.as_str(), position, this_text_style)
+        Text::new(&entry_label(target), position, this_text_style)
             .draw(display)
             .ok();
     }
 
     let size = display.size();
+    if let Some(seconds) = menu.countdown() {
+        Text::new(
+            &countdown_text(seconds),
+            Point::new(50, size.height.cast_signed() - 75),
+            text_style,
+        )
+        .draw(display)
+        .ok();
+    }
+
     let quote = "Hello, world!";
     Text::new(
         quote,
@@ -67,10 +89,10 @@ pub fn draw_boot_menu<T: App + DisplayEntry>(
 
 /// # Errors
 /// Returns any drawing error from the underlying display.
-pub fn draw_error_overlay(ctx: &mut AppCtx, error: &AppError) -> Result<(), AppError> {
+pub fn draw_error_overlay(display: &mut Canvas, error: &AppError) -> Result<(), AppError> {
     let text = alloc::format!("{error}");
 
-    let size = ctx.display.size();
+    let size = display.size();
     let screen_w = size.width.cast_signed();
     let screen_h = size.height.cast_signed();
     let box_w = (screen_w * 2 / 3).max(280);
@@ -90,7 +112,7 @@ pub fn draw_error_overlay(ctx: &mut AppCtx, error: &AppError) -> Result<(), AppE
         Size::new(box_width_u32, box_height_u32),
     )
     .into_styled(background)
-    .draw(ctx.display)
+    .draw(display)
     .ok();
 
     let title_style = MonoTextStyle::new(&FONT_9X15, Rgb888::new(255, 80, 80));
@@ -108,7 +130,7 @@ pub fn draw_error_overlay(ctx: &mut AppCtx, error: &AppError) -> Result<(), AppE
         Point::new(left + padding_x, top + padding_y),
         title_style,
     )
-    .draw(ctx.display)
+    .draw(display)
     .ok();
 
     let wrapper = LineWrapper {
@@ -120,9 +142,214 @@ pub fn draw_error_overlay(ctx: &mut AppCtx, error: &AppError) -> Result<(), AppE
     for (idx, line) in wrapper.enumerate() {
         let y = top + padding_y + line_height * (i32::try_from(idx).unwrap_or(i32::MAX) + 1);
         Text::new(line, Point::new(left + padding_x, y), body_style)
-            .draw(ctx.display)
+            .draw(display)
             .ok();
     }
 
-    ctx.display.flush().map_err(Into::into)
+    display.flush()
+}
+
+/// # Errors
+/// Returns any drawing error from the underlying display.
+pub fn draw_cmdline_editor(display: &mut Canvas, editor: &CmdlineEditor) -> Result<(), AppError> {
+    let size = display.size();
+    let screen_w = size.width.cast_signed();
+    let screen_h = size.height.cast_signed();
+    let box_w = (screen_w * 5 / 6).max(280);
+    let box_h = 100;
+    let left = (screen_w - box_w) / 2;
+    let top = (screen_h - box_h) / 2;
+
+    let background = PrimitiveStyleBuilder::new()
+        .fill_color(Rgb888::new(20, 20, 20))
+        .stroke_color(Rgb888::new(220, 220, 220))
+        .stroke_width(2)
+        .build();
+    Rectangle::new(
+        Point::new(left, top),
+        Size::new(
+            u32::try_from(box_w).unwrap_or(u32::MAX),
+            u32::try_from(box_h).unwrap_or(u32::MAX),
+        ),
+    )
+    .into_styled(background)
+    .draw(display)
+    .ok();
+
+    let text_style = MonoTextStyle::new(&FONT_9X15, Rgb888::WHITE);
+    let padding_x = 12;
+    let max_chars = usize::try_from(((box_w - padding_x * 2) / 9).max(1)).unwrap_or(usize::MAX);
+
+    Text::new(
+        editor.title(),
+        Point::new(left + padding_x, top + 20),
+        text_style,
+    )
+    .draw(display)
+    .ok();
+
+    let (visible, cursor) = editor.visible(max_chars);
+    let text_y = top + 50;
+    Text::new(&visible, Point::new(left + padding_x, text_y), text_style)
+        .draw(display)
+        .ok();
+
+    let cursor_x = left + padding_x + 9 * i32::try_from(cursor).unwrap_or(i32::MAX);
+    Rectangle::new(Point::new(cursor_x, text_y + 3), Size::new(9, 2))
+        .into_styled(PrimitiveStyle::with_fill(Rgb888::WHITE))
+        .draw(display)
+        .ok();
+
+    Text::new(
+        EDITOR_HINT,
+        Point::new(left + padding_x, top + box_h - 15),
+        MonoTextStyle::new(&FONT_9X15, Rgb888::new(150, 150, 150)),
+    )
+    .draw(display)
+    .ok();
+
+    display.flush()
+}
+
+/// # Errors
+/// Returns any drawing error from the underlying display.
+pub fn draw_diagnostics(
+    display: &mut Canvas,
+    overlay: &DiagnosticsOverlay,
+) -> Result<(), AppError> {
+    let size = display.size();
+    let screen_w = size.width.cast_signed();
+    let screen_h = size.height.cast_signed();
+    let box_w = (screen_w * 5 / 6).max(280);
+    let box_h = (screen_h * 2 / 3).max(160);
+    let left = (screen_w - box_w) / 2;
+    let top = (screen_h - box_h) / 2;
+
+    let background = PrimitiveStyleBuilder::new()
+        .fill_color(Rgb888::new(20, 20, 20))
+        .stroke_color(Rgb888::new(220, 220, 220))
+        .stroke_width(2)
+        .build();
+    Rectangle::new(
+        Point::new(left, top),
+        Size::new(
+            u32::try_from(box_w).unwrap_or(u32::MAX),
+            u32::try_from(box_h).unwrap_or(u32::MAX),
+        ),
+    )
+    .into_styled(background)
+    .draw(display)
+    .ok();
+
+    let padding_x = 12;
+    let padding_y = 16;
+    let line_height = 18;
+    let max_chars = usize::try_from(((box_w - padding_x * 2) / 9).max(1)).unwrap_or(usize::MAX);
+    // Leave room for the title and the footer.
+    let max_lines =
+        usize::try_from(((box_h - padding_y * 2) / line_height - 2).max(1)).unwrap_or(usize::MAX);
+
+    Text::new(
+        &overlay.title(),
+        Point::new(left + padding_x, top + padding_y),
+        MonoTextStyle::new(&FONT_9X15, Rgb888::new(255, 80, 80)),
+    )
+    .draw(display)
+    .ok();
+
+    for (idx, (severity, line)) in diagnostic_lines(overlay, max_chars, max_lines)
+        .iter()
+        .enumerate()
+    {
+        let color = match severity {
+            Severity::Error => Rgb888::WHITE,
+            Severity::Warning => Rgb888::new(255, 200, 80),
+        };
+        let y = top + padding_y + line_height * (i32::try_from(idx).unwrap_or(i32::MAX) + 1);
+        Text::new(
+            line,
+            Point::new(left + padding_x, y),
+            MonoTextStyle::new(&FONT_9X15, color),
+        )
+        .draw(display)
+        .ok();
+    }
+
+    Text::new(
+        &diagnostics_hint(overlay),
+        Point::new(left + padding_x, top + box_h - 15),
+        MonoTextStyle::new(&FONT_9X15, Rgb888::new(150, 150, 150)),
+    )
+    .draw(display)
+    .ok();
+
+    display.flush()
+}
+
+/// # Errors
+/// Returns any drawing error from the underlying display.
+pub fn draw_system_info(display: &mut Canvas, view: &SystemInfoView) -> Result<(), AppError> {
+    let size = display.size();
+    let screen_w = size.width.cast_signed();
+    let screen_h = size.height.cast_signed();
+    let box_w = (screen_w * 2 / 3).max(280);
+    let box_h = (screen_h / 2).max(220);
+    let left = (screen_w - box_w) / 2;
+    let top = (screen_h - box_h) / 2;
+
+    let background = PrimitiveStyleBuilder::new()
+        .fill_color(Rgb888::new(20, 20, 20))
+        .stroke_color(Rgb888::new(220, 220, 220))
+        .stroke_width(2)
+        .build();
+    Rectangle::new(
+        Point::new(left, top),
+        Size::new(
+            u32::try_from(box_w).unwrap_or(u32::MAX),
+            u32::try_from(box_h).unwrap_or(u32::MAX),
+        ),
+    )
+    .into_styled(background)
+    .draw(display)
+    .ok();
+
+    let padding_x = 12;
+    let padding_y = 16;
+    let line_height = 18;
+    let max_chars = usize::try_from(((box_w - padding_x * 2) / 9).max(1)).unwrap_or(usize::MAX);
+    // Leave room for the title and the footer.
+    let max_lines =
+        usize::try_from(((box_h - padding_y * 2) / line_height - 2).max(1)).unwrap_or(usize::MAX);
+
+    Text::new(
+        SystemInfoView::TITLE,
+        Point::new(left + padding_x, top + padding_y),
+        MonoTextStyle::new(&FONT_9X15, Rgb888::WHITE),
+    )
+    .draw(display)
+    .ok();
+
+    for (idx, line) in system_info_lines(view, max_chars, max_lines)
+        .iter()
+        .enumerate()
+    {
+        let y = top + padding_y + line_height * (i32::try_from(idx).unwrap_or(i32::MAX) + 1);
+        Text::new(
+            line,
+            Point::new(left + padding_x, y),
+            MonoTextStyle::new(&FONT_9X15, Rgb888::new(200, 200, 200)),
+        )
+        .draw(display)
+        .ok();
+    }
+
+    Text::new(
+        SYSTEM_INFO_HINT,
+        Point::new(left + padding_x, top + box_h - 15),
+        MonoTextStyle::new(&FONT_9X15, Rgb888::new(150, 150, 150)),
+    )
+    .draw(display)
+    .ok();
+
+    display.flush()
 }
@@ -11,10 +11,20 @@ use embedded_graphics::{
     text::{Alignment, Text, TextStyleBuilder},
 };
 
+use alloc::string::String;
+
 use crate::{
-    core::app::{App, AppCtx, DisplayEntry},
+    config::validate::Severity,
+    core::app::{App, DisplayEntry},
+    core::display::Canvas,
     ui::boot_menu::BootMenu,
-    ui::theme::LineWrapper,
+    ui::diagnostics::DiagnosticsOverlay,
+    ui::editor::CmdlineEditor,
+    ui::system_info::SystemInfoView,
+    ui::theme::{
+        countdown_text, diagnostic_lines, diagnostics_hint, entry_label, system_info_lines,
+        LineWrapper, EDITOR_HINT, SYSTEM_INFO_HINT,
+    },
     AppError,
 };
 
@@ -33,6 +43,7 @@ const BLUE: Rgb888 = Rgb888::new(0x89, 0xB4, 0xFA);
 const MAUVE: Rgb888 = Rgb888::new(0xCB, 0xA6, 0xF7);
 const GREEN: Rgb888 = Rgb888::new(0xA6, 0xE3, 0xA1);
 const RED: Rgb888 = Rgb888::new(0xF3, 0x8B, 0xA8);
+const YELLOW: Rgb888 = Rgb888::new(0xF9, 0xE2, 0xAF);
 
 const LOGO: &[&str] = &[
     r"    ___ _             ",
@@ -51,10 +62,9 @@ const LOGO: &[&str] = &[
 /// # Errors
 /// Returns any drawing error from the underlying display.
 pub fn draw_boot_menu<T: App + DisplayEntry>(
-    ctx: &mut AppCtx,
+    display: &mut Canvas,
     menu: &BootMenu<'_, T>,
 ) -> Result<(), AppError> {
-    let display = &mut *ctx.display;
     let size = display.size();
     let w = i32::try_from(size.width).unwrap_or(i32::MAX);
     let h = i32::try_from(size.height).unwrap_or(i32::MAX);
@@ -84,7 +94,14 @@ pub fn draw_boot_menu<T: App + DisplayEntry>(
     let right_panel_width = box_width - left_panel_width - (if show_logo { 40 } else { 80 });
 
     draw_boot_entries(display, menu, right_panel_x, box_y + 60, right_panel_width);
-    draw_footer(display, box_x, box_y, box_width, box_height);
+    draw_footer(
+        display,
+        box_x,
+        box_y,
+        box_width,
+        box_height,
+        menu.countdown(),
+    );
 
     display.flush()?;
     Ok(())
@@ -223,8 +240,9 @@ fn draw_boot_entries<D, T>(
         .text_color(SUBTEXT0)
         .build();
 
+    // Nested menus are headed by the label of their group.
     Text::new(
-        "Select Boot Entry:",
+        menu.title().unwrap_or("Select Boot Entry:"),
         Point::new(panel_x, panel_y + 20),
         list_header_style,
     )
@@ -235,7 +253,7 @@ fn draw_boot_entries<D, T>(
     let item_height = 40;
 
     for (i, target) in menu.targets().iter().enumerate() {
-        let display_opts = target.display_options();
+        let label = entry_label(target);
         let y = item_start_y + i32::try_from(i).unwrap_or(i32::MAX) * item_height;
         let panel_width_u32 = u32::try_from(panel_width).unwrap_or(u32::MAX);
         let item_height_u32 = u32::try_from(item_height).unwrap_or(u32::MAX);
@@ -244,7 +262,7 @@ fn draw_boot_entries<D, T>(
             Size::new(panel_width_u32, item_height_u32),
         );
 
-        let label = display_opts.label.as_str();
+        let label = label.as_str();
         let is_selected = i == menu.selected();
 
         if is_selected {
@@ -289,8 +307,14 @@ fn draw_boot_entries<D, T>(
     }
 }
 
-fn draw_footer<D>(display: &mut D, box_x: i32, box_y: i32, box_width: i32, box_height: i32)
-where
+fn draw_footer<D>(
+    display: &mut D,
+    box_x: i32,
+    box_y: i32,
+    box_width: i32,
+    box_height: i32,
+    countdown: Option<u64>,
+) where
     D: DrawTarget<Color = Rgb888>,
 {
     let footer_style = MonoTextStyleBuilder::new()
@@ -300,8 +324,13 @@ where
 
     let center_style = TextStyleBuilder::new().alignment(Alignment::Center).build();
 
+    let hint = countdown.map_or_else(
+        || String::from("Use UP/DOWN to navigate, ENTER to boot, E to edit, I for info"),
+        countdown_text,
+    );
+
     Text::with_text_style(
-        "Use UP/DOWN to navigate, ENTER to boot",
+        &hint,
         Point::new(box_x + box_width / 2, box_y + box_height - 15),
         footer_style,
         center_style,
@@ -312,10 +341,9 @@ where
 
 /// # Errors
 /// Returns any drawing error from the underlying display.
-pub fn draw_error_overlay(ctx: &mut AppCtx, error: &AppError) -> Result<(), AppError> {
+pub fn draw_error_overlay(display: &mut Canvas, error: &AppError) -> Result<(), AppError> {
     let text = alloc::format!("{error}");
 
-    let display = &mut *ctx.display;
     let size = display.size();
     let screen_w = i32::try_from(size.width).unwrap_or(i32::MAX);
     let screen_h = i32::try_from(size.height).unwrap_or(i32::MAX);
@@ -402,5 +430,273 @@ pub fn draw_error_overlay(ctx: &mut AppCtx, error: &AppError) -> Result<(), AppE
     .draw(display)
     .ok();
 
-    display.flush().map_err(Into::into)
+    display.flush()
+}
+
+/// # Errors
+/// Returns any drawing error from the underlying display.
+pub fn draw_cmdline_editor(display: &mut Canvas, editor: &CmdlineEditor) -> Result<(), AppError> {
+    let size = display.size();
+    let screen_w = i32::try_from(size.width).unwrap_or(i32::MAX);
+    let screen_h = i32::try_from(size.height).unwrap_or(i32::MAX);
+    let box_w = (screen_w * 5 / 6).max(400);
+    let box_h = 140;
+    let left = (screen_w - box_w) / 2;
+    let top = (screen_h - box_h) / 2;
+
+    let box_width_u32 = u32::try_from(box_w).unwrap_or(u32::MAX);
+    let box_height_u32 = u32::try_from(box_h).unwrap_or(u32::MAX);
+    let shadow_rect = Rectangle::new(
+        Point::new(left + 8, top + 8),
+        Size::new(box_width_u32, box_height_u32),
+    );
+    RoundedRectangle::with_equal_corners(shadow_rect, Size::new(12, 12))
+        .into_styled(PrimitiveStyleBuilder::new().fill_color(CRUST).build())
+        .draw(display)
+        .ok();
+
+    let background = PrimitiveStyleBuilder::new()
+        .fill_color(BASE)
+        .stroke_color(SURFACE1)
+        .stroke_width(2)
+        .build();
+    let modal_rect = Rectangle::new(
+        Point::new(left, top),
+        Size::new(box_width_u32, box_height_u32),
+    );
+    RoundedRectangle::with_equal_corners(modal_rect, Size::new(12, 12))
+        .into_styled(background)
+        .draw(display)
+        .ok();
+
+    let title_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(MAUVE)
+        .build();
+    let body_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(TEXT)
+        .build();
+    let center_style = TextStyleBuilder::new().alignment(Alignment::Center).build();
+
+    Text::with_text_style(
+        editor.title(),
+        Point::new(left + box_w / 2, top + 30),
+        title_style,
+        center_style,
+    )
+    .draw(display)
+    .ok();
+
+    let padding_x = 20;
+    let max_chars = usize::try_from(((box_w - padding_x * 2) / 10).max(1)).unwrap_or(usize::MAX);
+    let (visible, cursor) = editor.visible(max_chars);
+    let text_y = top + 75;
+    Text::new(&visible, Point::new(left + padding_x, text_y), body_style)
+        .draw(display)
+        .ok();
+
+    let cursor_x = left + padding_x + 10 * i32::try_from(cursor).unwrap_or(i32::MAX);
+    Rectangle::new(Point::new(cursor_x, text_y - 15), Size::new(2, 20))
+        .into_styled(PrimitiveStyle::with_fill(BLUE))
+        .draw(display)
+        .ok();
+
+    let footer_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(OVERLAY0)
+        .build();
+    Text::with_text_style(
+        EDITOR_HINT,
+        Point::new(left + box_w / 2, top + box_h - 15),
+        footer_style,
+        center_style,
+    )
+    .draw(display)
+    .ok();
+
+    display.flush()
+}
+
+/// # Errors
+/// Returns any drawing error from the underlying display.
+pub fn draw_diagnostics(
+    display: &mut Canvas,
+    overlay: &DiagnosticsOverlay,
+) -> Result<(), AppError> {
+    let size = display.size();
+    let screen_w = i32::try_from(size.width).unwrap_or(i32::MAX);
+    let screen_h = i32::try_from(size.height).unwrap_or(i32::MAX);
+    let box_w = (screen_w * 5 / 6).max(400);
+    let box_h = (screen_h * 2 / 3).max(200);
+    let left = (screen_w - box_w) / 2;
+    let top = (screen_h - box_h) / 2;
+
+    let box_width_u32 = u32::try_from(box_w).unwrap_or(u32::MAX);
+    let box_height_u32 = u32::try_from(box_h).unwrap_or(u32::MAX);
+    let shadow_rect = Rectangle::new(
+        Point::new(left + 8, top + 8),
+        Size::new(box_width_u32, box_height_u32),
+    );
+    RoundedRectangle::with_equal_corners(shadow_rect, Size::new(12, 12))
+        .into_styled(PrimitiveStyleBuilder::new().fill_color(CRUST).build())
+        .draw(display)
+        .ok();
+
+    let background = PrimitiveStyleBuilder::new()
+        .fill_color(BASE)
+        .stroke_color(RED)
+        .stroke_width(2)
+        .build();
+    let modal_rect = Rectangle::new(
+        Point::new(left, top),
+        Size::new(box_width_u32, box_height_u32),
+    );
+    RoundedRectangle::with_equal_corners(modal_rect, Size::new(12, 12))
+        .into_styled(background)
+        .draw(display)
+        .ok();
+
+    let title_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(RED)
+        .build();
+    let center_style = TextStyleBuilder::new().alignment(Alignment::Center).build();
+
+    Text::with_text_style(
+        &overlay.title(),
+        Point::new(left + box_w / 2, top + 30),
+        title_style,
+        center_style,
+    )
+    .draw(display)
+    .ok();
+
+    let padding_x = 20;
+    let padding_y = 60;
+    let line_height = 20;
+    let max_chars = usize::try_from(((box_w - padding_x * 2) / 10).max(1)).unwrap_or(usize::MAX);
+    let max_lines =
+        usize::try_from(((box_h - padding_y * 2) / line_height).max(1)).unwrap_or(usize::MAX);
+
+    for (idx, (severity, line)) in diagnostic_lines(overlay, max_chars, max_lines)
+        .iter()
+        .enumerate()
+    {
+        let color = match severity {
+            Severity::Error => TEXT,
+            Severity::Warning => YELLOW,
+        };
+        let style = MonoTextStyleBuilder::new()
+            .font(&FONT_10X20)
+            .text_color(color)
+            .build();
+        let y = top + padding_y + line_height * i32::try_from(idx).unwrap_or(i32::MAX);
+        Text::new(line, Point::new(left + padding_x, y), style)
+            .draw(display)
+            .ok();
+    }
+
+    let footer_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(OVERLAY0)
+        .build();
+    Text::with_text_style(
+        &diagnostics_hint(overlay),
+        Point::new(left + box_w / 2, top + box_h - 15),
+        footer_style,
+        center_style,
+    )
+    .draw(display)
+    .ok();
+
+    display.flush()
+}
+
+/// # Errors
+/// Returns any drawing error from the underlying display.
+pub fn draw_system_info(display: &mut Canvas, view: &SystemInfoView) -> Result<(), AppError> {
+    let size = display.size();
+    let screen_w = i32::try_from(size.width).unwrap_or(i32::MAX);
+    let screen_h = i32::try_from(size.height).unwrap_or(i32::MAX);
+    let box_w = (screen_w * 5 / 6).max(400);
+    let box_h = (screen_h * 2 / 3).max(280);
+    let left = (screen_w - box_w) / 2;
+    let top = (screen_h - box_h) / 2;
+
+    let box_width_u32 = u32::try_from(box_w).unwrap_or(u32::MAX);
+    let box_height_u32 = u32::try_from(box_h).unwrap_or(u32::MAX);
+    let shadow_rect = Rectangle::new(
+        Point::new(left + 8, top + 8),
+        Size::new(box_width_u32, box_height_u32),
+    );
+    RoundedRectangle::with_equal_corners(shadow_rect, Size::new(12, 12))
+        .into_styled(PrimitiveStyleBuilder::new().fill_color(CRUST).build())
+        .draw(display)
+        .ok();
+
+    let background = PrimitiveStyleBuilder::new()
+        .fill_color(BASE)
+        .stroke_color(BLUE)
+        .stroke_width(2)
+        .build();
+    let modal_rect = Rectangle::new(
+        Point::new(left, top),
+        Size::new(box_width_u32, box_height_u32),
+    );
+    RoundedRectangle::with_equal_corners(modal_rect, Size::new(12, 12))
+        .into_styled(background)
+        .draw(display)
+        .ok();
+
+    let title_style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BLUE)
+        .build();
+    let center_style = TextStyleBuilder::new().alignment(Alignment::Center).build();
+
+    Text::with_text_style(
+        SystemInfoView::TITLE,
+        Point::new(left + box_w / 2, top + 30),
+        title_style,
+        center_style,
+    )
+    .draw(display)
+    .ok();
+
+    let padding_x = 20;
+    let padding_y = 60;
+    let line_height = 20;
+    let max_chars = usize::try_from(((box_w - padding_x * 2) / 10).max(1)).unwrap_or(usize::MAX);
+    let max_lines =
+        usize::try_from(((box_h - padding_y * 2) / line_height).max(1)).unwrap_or(usize::MAX);
+
+    let style = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(TEXT)
+        .build();
+    for (idx, line) in system_info_lines(view, max_chars, max_lines)
+        .iter()
+        .enumerate()
+    {
+        let y = top + padding_y + line_height * i32::try_from(idx).unwrap_or(i32::MAX);
+        Text::new(line, Point::new(left + padding_x, y), style)
+            .draw(display)
+            .ok();
+    }
+
+    let footer_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(OVERLAY0)
+        .build();
+    Text::with_text_style(
+        SYSTEM_INFO_HINT,
+        Point::new(left + box_w / 2, top + box_h - 15),
+        footer_style,
+        center_style,
+    )
+    .draw(display)
+    .ok();
+
+    display.flush()
 }
@@ -1,8 +1,17 @@
 use crate::{
-    core::app::{App, AppCtx, DisplayEntry},
+    config::validate::Severity,
+    core::app::{App, DisplayEntry},
+    core::display::{Canvas, Display},
     ui::boot_menu::BootMenu,
+    ui::diagnostics::DiagnosticsOverlay,
+    ui::editor::CmdlineEditor,
+    ui::system_info::SystemInfoView,
+    ui::text,
     AppError,
 };
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
 use serde::Deserialize;
 
 pub mod default;
@@ -13,7 +22,13 @@ pub mod mocha;
 #[cfg(feature = "wii")]
 pub mod wii;
 
+#[cfg(test)]
+mod tests;
+
 /// A UI Theme that determines how the boot menu and overlays are drawn.
+///
+/// Themes only apply to graphical displays, on a text console everything is
+/// drawn by [`crate::ui::text`].
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Theme {
@@ -34,15 +49,19 @@ impl Theme {
     /// Returns any drawing error from the selected theme implementation.
     pub fn draw_boot_menu<T: App + DisplayEntry>(
         &self,
-        ctx: &mut AppCtx,
+        display: &mut Display,
         menu: &BootMenu<'_, T>,
     ) -> Result<(), AppError> {
+        let display = &mut match display {
+            Display::Graphics(framebuffer) => Canvas::new(&mut **framebuffer),
+            Display::Text(display) => return text::draw_boot_menu(display, menu),
+        };
         match self {
-            Self::Default => default::draw_boot_menu(ctx, menu),
+            Self::Default => default::draw_boot_menu(display, menu),
             #[cfg(feature = "mocha")]
-            Self::Mocha => mocha::draw_boot_menu(ctx, menu),
+            Self::Mocha => mocha::draw_boot_menu(display, menu),
             #[cfg(feature = "wii")]
-            Self::Wii => wii::draw_boot_menu(ctx, menu),
+            Self::Wii => wii::draw_boot_menu(display, menu),
         }
     }
 
@@ -50,17 +69,164 @@ impl Theme {
     ///
     /// # Errors
     /// Returns any drawing error from the selected theme implementation.
-    pub fn draw_error_overlay(&self, ctx: &mut AppCtx, error: &AppError) -> Result<(), AppError> {
+    pub fn draw_error_overlay(
+        &self,
+        display: &mut Display,
+        error: &AppError,
+    ) -> Result<(), AppError> {
+        let display = &mut match display {
+            Display::Graphics(framebuffer) => Canvas::new(&mut **framebuffer),
+            Display::Text(display) => return text::draw_error_overlay(display, error),
+        };
+        match self {
+            Self::Default => default::draw_error_overlay(display, error),
+            #[cfg(feature = "mocha")]
+            Self::Mocha => mocha::draw_error_overlay(display, error),
+            #[cfg(feature = "wii")]
+            Self::Wii => wii::draw_error_overlay(display, error),
+        }
+    }
+
+    /// Draw the command line editor overlay.
+    ///
+    /// # Errors
+    /// Returns any drawing error from the selected theme implementation.
+    pub fn draw_cmdline_editor(
+        &self,
+        display: &mut Display,
+        editor: &CmdlineEditor,
+    ) -> Result<(), AppError> {
+        let display = &mut match display {
+            Display::Graphics(framebuffer) => Canvas::new(&mut **framebuffer),
+            Display::Text(display) => return text::draw_cmdline_editor(display, editor),
+        };
+        match self {
+            Self::Default => default::draw_cmdline_editor(display, editor),
+            #[cfg(feature = "mocha")]
+            Self::Mocha => mocha::draw_cmdline_editor(display, editor),
+            #[cfg(feature = "wii")]
+            Self::Wii => wii::draw_cmdline_editor(display, editor),
+        }
+    }
+
+    /// Draw the diagnostics overlay.
+    ///
+    /// # Errors
+    /// Returns any drawing error from the selected theme implementation.
+    pub fn draw_diagnostics(
+        &self,
+        display: &mut Display,
+        overlay: &DiagnosticsOverlay,
+    ) -> Result<(), AppError> {
+        let display = &mut match display {
+            Display::Graphics(framebuffer) => Canvas::new(&mut **framebuffer),
+            Display::Text(display) => return text::draw_diagnostics(display, overlay),
+        };
+        match self {
+            Self::Default => default::draw_diagnostics(display, overlay),
+            #[cfg(feature = "mocha")]
+            Self::Mocha => mocha::draw_diagnostics(display, overlay),
+            #[cfg(feature = "wii")]
+            Self::Wii => wii::draw_diagnostics(display, overlay),
+        }
+    }
+
+    /// Draw the system information overlay.
+    ///
+    /// # Errors
+    /// Returns any drawing error from the selected theme implementation.
+    pub fn draw_system_info(
+        &self,
+        display: &mut Display,
+        view: &SystemInfoView,
+    ) -> Result<(), AppError> {
+        let display = &mut match display {
+            Display::Graphics(framebuffer) => Canvas::new(&mut **framebuffer),
+            Display::Text(display) => return text::draw_system_info(display, view),
+        };
         match self {
-            Self::Default => default::draw_error_overlay(ctx, error),
+            Self::Default => default::draw_system_info(display, view),
             #[cfg(feature = "mocha")]
-            Self::Mocha => mocha::draw_error_overlay(ctx, error),
+            Self::Mocha => mocha::draw_system_info(display, view),
             #[cfg(feature = "wii")]
-            Self::Wii => wii::draw_error_overlay(ctx, error),
+            Self::Wii => wii::draw_system_info(display, view),
         }
     }
 }
 
+/// The label of a boot menu entry, marked if it is a group that opens a
+/// nested menu.
+pub(crate) fn entry_label<T: DisplayEntry>(target: &T) -> String {
+    let label = target.display_options().label;
+    if target.entries().is_some() {
+        format!("{label} >")
+    } else {
+        label
+    }
+}
+
+/// Footer text shown while the auto-boot countdown is running.
+pub(crate) fn countdown_text(seconds: u64) -> String {
+    format!("Booting in {seconds}s, press any key to cancel")
+}
+
+/// Key hints shown below the command line editor.
+pub(crate) const EDITOR_HINT: &str = "ENTER to boot, ESC to cancel, HOME/END to jump";
+
+/// Key hints shown below the diagnostics overlay.
+pub(crate) const DIAGNOSTICS_HINT: &str = "UP/DOWN to scroll, ENTER to continue";
+
+/// Footer text of the diagnostics overlay, the countdown while it runs.
+pub(crate) fn diagnostics_hint(overlay: &DiagnosticsOverlay) -> String {
+    overlay
+        .countdown()
+        .map_or_else(|| String::from(DIAGNOSTICS_HINT), countdown_text)
+}
+
+/// Key hints shown below the system information overlay.
+pub(crate) const SYSTEM_INFO_HINT: &str = "ENTER or ESC to close";
+
+/// The diagnostics visible in `overlay`, wrapped to `max_chars` and cut off
+/// after `max_lines`.
+pub(crate) fn diagnostic_lines(
+    overlay: &DiagnosticsOverlay,
+    max_chars: usize,
+    max_lines: usize,
+) -> Vec<(Severity, String)> {
+    let mut lines = Vec::new();
+    for diagnostic in overlay.visible() {
+        let text = format!("{diagnostic}");
+        let wrapper = LineWrapper {
+            text: &text,
+            max_chars,
+            max_lines: max_lines.saturating_sub(lines.len()),
+            lines_yielded: 0,
+        };
+        lines.extend(wrapper.map(|line| (diagnostic.severity, String::from(line))));
+    }
+    lines
+}
+
+/// The lines of `view`, wrapped to `max_chars` and cut off after
+/// `max_lines`.
+pub(crate) fn system_info_lines(
+    view: &SystemInfoView,
+    max_chars: usize,
+    max_lines: usize,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in view.lines() {
+        let wrapper = LineWrapper {
+            text: &line,
+            max_chars,
+            max_lines: max_lines.saturating_sub(lines.len()),
+            lines_yielded: 0,
+        };
+        lines.extend(wrapper.map(String::from));
+    }
+    lines
+}
+
 pub(crate) struct LineWrapper<'a> {
     pub text: &'a str,
     pub max_chars: usize,
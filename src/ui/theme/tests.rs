@@ -0,0 +1,282 @@
+//! Snapshot tests rendering every theme into a [`MemoryFramebuffer`].
+//!
+//! Each case is compared to a golden PNG in `src/ui/theme/snapshots`. A
+//! missing golden is written on the first run. To accept a changed layout,
+//! rerun with `PLEX_UPDATE_SNAPSHOTS=1`. On a mismatch the rendered image is
+//! written next to the golden as `*.actual.png` for inspection.
+
+extern crate std;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::RgbColor;
+use std::path::PathBuf;
+
+use super::Theme;
+use crate::config::validate::Diagnostic;
+use crate::config::DefaultEntry;
+use crate::core::app::{App, AppCtx, AppResult, DisplayEntry};
+use crate::core::bootables::DisplayOptions;
+use crate::core::display::{Display, Framebuffer, MemoryFramebuffer};
+use crate::core::smbios::Smbios;
+use crate::core::system::SystemInfo;
+use crate::error::AppError;
+use crate::ui::boot_menu::BootMenu;
+use crate::ui::diagnostics::DiagnosticsOverlay;
+use crate::ui::editor::CmdlineEditor;
+use crate::ui::system_info::SystemInfoView;
+
+/// A menu entry that only has a label, or a group of entries.
+struct Entry(String, Option<Vec<Self>>);
+
+impl App for Entry {
+    fn run(&mut self, _ctx: &mut AppCtx) -> AppResult {
+        AppResult::Done
+    }
+}
+
+impl DisplayEntry for Entry {
+    fn display_options(&self) -> DisplayOptions {
+        DisplayOptions {
+            label: self.0.clone(),
+        }
+    }
+
+    fn entries(&self) -> Option<&[Self]> {
+        self.1.as_deref()
+    }
+}
+
+fn entries(labels: &[&str]) -> Vec<Entry> {
+    labels
+        .iter()
+        .map(|l| Entry((*l).to_string(), None))
+        .collect()
+}
+
+fn themes() -> Vec<(&'static str, Theme)> {
+    alloc::vec![
+        ("default", Theme::Default),
+        #[cfg(feature = "mocha")]
+        ("mocha", Theme::Mocha),
+        #[cfg(feature = "wii")]
+        ("wii", Theme::Wii),
+    ]
+}
+
+/// Renders `draw` with every theme at the given resolution and checks the
+/// result against the golden image `{theme}_{case}.png`.
+fn check<F>(case: &str, (width, height): (usize, usize), draw: F)
+where
+    F: Fn(Theme, &mut Display) -> Result<(), AppError>,
+{
+    for (name, theme) in themes() {
+        let mut framebuffer = MemoryFramebuffer::new(width, height);
+        draw(theme, &mut Display::Graphics(&mut framebuffer))
+            .unwrap_or_else(|e| panic!("{name}_{case}: drawing failed: {e}"));
+        assert!(framebuffer.flushes() > 0, "{name}_{case}: never flushed");
+        compare(&format!("{name}_{case}"), &framebuffer);
+    }
+}
+
+fn compare(snapshot: &str, framebuffer: &MemoryFramebuffer) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/ui/theme/snapshots");
+    let golden = dir.join(format!("{snapshot}.png"));
+    let actual = encode_png(framebuffer);
+
+    if std::env::var_os("PLEX_UPDATE_SNAPSHOTS").is_some() || !golden.exists() {
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&golden, &actual).unwrap();
+        return;
+    }
+
+    let (expected, size) = decode_png(&std::fs::read(&golden).unwrap());
+    if size == framebuffer.resolution() && expected == framebuffer.pixels() {
+        return;
+    }
+
+    let actual_path = dir.join(format!("{snapshot}.actual.png"));
+    std::fs::write(&actual_path, &actual).unwrap();
+    panic!(
+        "{snapshot} differs from {}, see {}.\nRerun with PLEX_UPDATE_SNAPSHOTS=1 to accept the change.",
+        golden.display(),
+        actual_path.display()
+    );
+}
+
+fn encode_png(framebuffer: &MemoryFramebuffer) -> Vec<u8> {
+    let (width, height) = framebuffer.resolution();
+    let data: Vec<u8> = framebuffer
+        .pixels()
+        .iter()
+        .flat_map(|p| [p.r(), p.g(), p.b()])
+        .collect();
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(
+        &mut out,
+        u32::try_from(width).unwrap(),
+        u32::try_from(height).unwrap(),
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&data).unwrap();
+    writer.finish().unwrap();
+    out
+}
+
+fn decode_png(bytes: &[u8]) -> (Vec<Rgb888>, (usize, usize)) {
+    let mut reader = png::Decoder::new(std::io::Cursor::new(bytes))
+        .read_info()
+        .unwrap();
+    let mut data = alloc::vec![0; reader.output_buffer_size().unwrap()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!(
+        info.color_type,
+        png::ColorType::Rgb,
+        "unexpected PNG format"
+    );
+
+    let pixels = data[..info.buffer_size()]
+        .as_chunks::<3>()
+        .0
+        .iter()
+        .map(|&[r, g, b]| Rgb888::new(r, g, b))
+        .collect();
+    (pixels, (info.width as usize, info.height as usize))
+}
+
+#[test]
+fn boot_menu() {
+    let mut targets = entries(&["Arch Linux", "Windows Boot Manager", "UEFI Shell"]);
+    let menu = BootMenu::new(&mut targets, Theme::Default, None, &DefaultEntry::Index(1));
+    check("boot_menu", (800, 600), |theme, display| {
+        theme.draw_boot_menu(display, &menu)
+    });
+}
+
+#[test]
+fn boot_menu_countdown() {
+    let mut targets = entries(&["Arch Linux", "Windows Boot Manager"]);
+    let menu = BootMenu::new(&mut targets, Theme::Default, Some(5), &DefaultEntry::First);
+    check("boot_menu_countdown", (800, 600), |theme, display| {
+        theme.draw_boot_menu(display, &menu)
+    });
+}
+
+#[test]
+fn boot_menu_long_labels() {
+    let mut targets = entries(&[
+        "Fedora Linux 40 (Workstation Edition) 6.9.7-200.fc40.x86_64 with a very long suffix",
+        "Windows Boot Manager",
+        "Ubuntu 24.04 LTS (6.8.0-36-generic) recovery mode, no graphics, single user",
+        "Arch Linux (linux-zen)",
+        "Arch Linux (linux-lts)",
+        "Arch Linux (fallback initramfs)",
+        "NixOS generation 412",
+        "NixOS generation 411",
+        "NixOS generation 410",
+        "memtest86+",
+        "UEFI Shell",
+        "Reboot into firmware setup",
+    ]);
+    let menu = BootMenu::new(&mut targets, Theme::Default, None, &DefaultEntry::Index(10));
+    check("boot_menu_long_labels", (640, 480), |theme, display| {
+        theme.draw_boot_menu(display, &menu)
+    });
+}
+
+#[test]
+fn boot_menu_group() {
+    let mut targets = entries(&["Windows Boot Manager", "UEFI Shell"]);
+    targets.insert(
+        0,
+        Entry(
+            String::from("Arch Linux"),
+            Some(entries(&["Arch Linux (6.9.7)", "Arch Linux (6.9.6)"])),
+        ),
+    );
+    let menu = BootMenu::new(&mut targets, Theme::Default, None, &DefaultEntry::First);
+    check("boot_menu_group", (800, 600), |theme, display| {
+        theme.draw_boot_menu(display, &menu)
+    });
+
+    let mut kernels = entries(&["Arch Linux (6.9.7)", "Arch Linux (6.9.6)"]);
+    let menu = BootMenu::new(&mut kernels, Theme::Default, None, &DefaultEntry::Index(1))
+        .nested(String::from("Arch Linux"));
+    check("boot_menu_nested", (800, 600), |theme, display| {
+        theme.draw_boot_menu(display, &menu)
+    });
+}
+
+#[test]
+fn error_overlay() {
+    let error = AppError::Generic(
+        "failed to load \\EFI\\Linux\\arch-linux.efi from guid(8c8f8eff-ac95-4770-814a-21994f2dbc8f): \
+         the file does not exist on the partition, check the path in plex.toml and that the \
+         partition is formatted with a file system the firmware can read",
+    );
+    check("error_overlay", (1024, 768), |theme, display| {
+        theme.draw_error_overlay(display, &error)
+    });
+}
+
+#[test]
+fn cmdline_editor() {
+    let editor = CmdlineEditor::new(
+        "Arch Linux",
+        "root=UUID=0a3407de-014b-458b-b5c1-848e92a327a3 rw quiet splash loglevel=3",
+        Theme::Default,
+    );
+    check("cmdline_editor", (800, 600), |theme, display| {
+        theme.draw_cmdline_editor(display, &editor)
+    });
+}
+
+#[test]
+fn diagnostics() {
+    let source =
+        "[[boot_targets]]\ntype = \"generic\"\nlabel = \"Arch\"\nexecutable = \"\\\\vmlinuz\"\n";
+    let diagnostics = [
+        Diagnostic::error("invalid path reference \"\\vmlinuz\": Missing Delimiter")
+            .at(source, Some(61..71))
+            .in_entry(0, "executable"),
+        Diagnostic::error("no partition matches guid(550e8400-e29b-41d4-a716-446655440000)")
+            .in_entry(1, "kernel"),
+        Diagnostic::warning("duplicate label \"Arch\"").in_entry(2, "label"),
+    ];
+    let overlay = DiagnosticsOverlay::new(&diagnostics, Theme::Default);
+    check("diagnostics", (800, 600), |theme, display| {
+        theme.draw_diagnostics(display, &overlay)
+    });
+
+    let overlay =
+        DiagnosticsOverlay::new(&diagnostics[2..], Theme::Default).with_countdown(Some(5));
+    check("diagnostics_countdown", (800, 600), |theme, display| {
+        theme.draw_diagnostics(display, &overlay)
+    });
+}
+
+#[test]
+fn system_info() {
+    let system = SystemInfo {
+        arch: "x86_64",
+        secure_boot: true,
+        firmware_vendor: String::from("Lenovo"),
+        firmware_revision: 0x0001_0040,
+        smbios: Some(Smbios {
+            manufacturer: String::from("LENOVO"),
+            product: String::from("20XWCTO1WW"),
+            serial: String::from("PF2ABCDE"),
+            uuid: Some(uefi::guid!("0a3407de-014b-458b-b5c1-848e92a327a3")),
+        }),
+        firmware_setup: true,
+    };
+    let view = SystemInfoView::new(&system, Some("Office ThinkPad"), Theme::Default);
+    check("system_info", (800, 600), |theme, display| {
+        theme.draw_system_info(display, &view)
+    });
+}